@@ -0,0 +1,7 @@
+fn main() {
+    //Shown in the startup banner
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+}
@@ -0,0 +1,781 @@
+use rusty_vjoy::{HidUsage, JoystickPosition};
+
+use serde::{Deserialize, Serialize};
+
+use std::time::{Duration, Instant};
+
+use crate::calibrate::AxisCalibration;
+use crate::config::{AxisConfig, AxisMode};
+use crate::filter::{Filter, Integrator, NoiseGate, SlewLimiter, UpdateHold};
+
+//Largest raw value the SpaceNavigator reports at full deflection
+pub const MAX_DEFLECTION: i32 = 350;
+
+/// The six device axes, in `JoystickPosition` order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+    RX,
+    RY,
+    RZ,
+}
+
+impl Axis {
+    pub const ALL: [Axis; 6] = [Axis::X, Axis::Y, Axis::Z, Axis::RX, Axis::RY, Axis::RZ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Axis::X => "X",
+            Axis::Y => "Y",
+            Axis::Z => "Z",
+            Axis::RX => "RX",
+            Axis::RY => "RY",
+            Axis::RZ => "RZ",
+        }
+    }
+}
+
+/// Axis fields of `JoystickPosition`, each device axis can feed any of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Field {
+    X,
+    Y,
+    Z,
+    RX,
+    RY,
+    RZ,
+    Throttle,
+    Rudder,
+    Aileron,
+    Slider,
+    Dial,
+    Wheel,
+    VX,
+    VY,
+    VZ,
+    VBRX,
+    VBRY,
+    VBRZ,
+}
+
+impl From<Axis> for Field {
+    /// Field an axis feeds unless remapped.
+    fn from(axis: Axis) -> Self {
+        match axis {
+            Axis::X => Field::X,
+            Axis::Y => Field::Y,
+            Axis::Z => Field::Z,
+            Axis::RX => Field::RX,
+            Axis::RY => Field::RY,
+            Axis::RZ => Field::RZ,
+        }
+    }
+}
+
+impl Field {
+    /// HID usage vJoy reports the capabilities of, `None` for the fields it cannot be asked about.
+    pub fn usage(self) -> Option<HidUsage> {
+        match self {
+            Field::X => Some(HidUsage::X),
+            Field::Y => Some(HidUsage::Y),
+            Field::Z => Some(HidUsage::Z),
+            Field::RX => Some(HidUsage::RX),
+            Field::RY => Some(HidUsage::RY),
+            Field::RZ => Some(HidUsage::RZ),
+            Field::Slider => Some(HidUsage::SL0),
+            Field::Dial => Some(HidUsage::SL1),
+            Field::Wheel => Some(HidUsage::WHL),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Field::X => "X",
+            Field::Y => "Y",
+            Field::Z => "Z",
+            Field::RX => "RX",
+            Field::RY => "RY",
+            Field::RZ => "RZ",
+            Field::Throttle => "Throttle",
+            Field::Rudder => "Rudder",
+            Field::Aileron => "Aileron",
+            Field::Slider => "Slider",
+            Field::Dial => "Dial",
+            Field::Wheel => "Wheel",
+            Field::VX => "VX",
+            Field::VY => "VY",
+            Field::VZ => "VZ",
+            Field::VBRX => "VBRX",
+            Field::VBRY => "VBRY",
+            Field::VBRZ => "VBRZ",
+        }
+    }
+}
+
+/// Set a `JoystickPosition` field.
+pub fn write_field(position: &mut JoystickPosition, field: Field, value: i32) {
+    match field {
+        Field::X => position.wAxisX = value,
+        Field::Y => position.wAxisY = value,
+        Field::Z => position.wAxisZ = value,
+        Field::RX => position.wAxisXRot = value,
+        Field::RY => position.wAxisYRot = value,
+        Field::RZ => position.wAxisZRot = value,
+        Field::Throttle => position.wThrottle = value,
+        Field::Rudder => position.wRudder = value,
+        Field::Aileron => position.wAileron = value,
+        Field::Slider => position.wSlider = value,
+        Field::Dial => position.wDial = value,
+        Field::Wheel => position.wWheel = value,
+        Field::VX => position.wAxisVX = value,
+        Field::VY => position.wAxisVY = value,
+        Field::VZ => position.wAxisVZ = value,
+        Field::VBRX => position.wAxisVBRX = value,
+        Field::VBRY => position.wAxisVBRY = value,
+        Field::VBRZ => position.wAxisVBRZ = value,
+    }
+}
+
+/// Get a `JoystickPosition` field.
+pub fn read_field(position: &JoystickPosition, field: Field) -> i32 {
+    match field {
+        Field::X => position.wAxisX,
+        Field::Y => position.wAxisY,
+        Field::Z => position.wAxisZ,
+        Field::RX => position.wAxisXRot,
+        Field::RY => position.wAxisYRot,
+        Field::RZ => position.wAxisZRot,
+        Field::Throttle => position.wThrottle,
+        Field::Rudder => position.wRudder,
+        Field::Aileron => position.wAileron,
+        Field::Slider => position.wSlider,
+        Field::Dial => position.wDial,
+        Field::Wheel => position.wWheel,
+        Field::VX => position.wAxisVX,
+        Field::VY => position.wAxisVY,
+        Field::VZ => position.wAxisVZ,
+        Field::VBRX => position.wAxisVBRX,
+        Field::VBRY => position.wAxisVBRY,
+        Field::VBRZ => position.wAxisVBRZ,
+    }
+}
+
+/// Range of values accepted by a vJoy axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl Default for AxisRange {
+    // vJoy default axis range
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: i16::MAX as i32,
+        }
+    }
+}
+
+impl AxisRange {
+    pub fn center(&self) -> i32 {
+        self.min + (self.max - self.min) / 2
+    }
+
+    /// Gain mapping a full device deflection onto the whole range.
+    pub fn gain(&self) -> f32 {
+        let span = (self.max - self.min) as f32;
+
+        span / (2 * MAX_DEFLECTION) as f32
+    }
+}
+
+/// Resolved conversion for one axis.
+///
+/// Config values take precedence over the calibration, which takes precedence over the vJoy range.
+#[derive(Debug, Clone)]
+pub struct AxisMapping {
+    /// Raw rest value, subtracted first
+    pub offset: i16,
+    /// Calibrated raw rest, subtracted next when each side of the axis is scaled on its own
+    pub rest: i16,
+    pub gain: f32,
+    /// Gain of the values below rest, the same as `gain` unless the axis is asymmetric
+    pub negative_gain: f32,
+    pub center: i32,
+    /// Shift of the center in vJoy units, see `set_trim`
+    pub trim: i32,
+    /// Multiplier of the gain, lowered while precision mode is held
+    pub sensitivity: f32,
+    /// Multiplier of the gain in the fine stage
+    pub fine_gain: f32,
+    /// Whether the fine stage is toggled on
+    pub fine: bool,
+    pub deadzone: i32,
+    /// Added to the deadzone while precision mode is held
+    pub extra_deadzone: i32,
+    pub invert: bool,
+    pub expo: f32,
+    pub curve: Option<Curve>,
+    /// Share of full deflection any output off center starts from, see `apply_anti_deadzone`
+    pub anti_deadzone: f32,
+    pub range: AxisRange,
+}
+
+impl AxisMapping {
+    pub fn new(
+        config: &AxisConfig,
+        range: AxisRange,
+        calibration: Option<&AxisCalibration>,
+    ) -> Self {
+        //The calibrated rest can't be folded into the center with two gains
+        let (gain, negative_gain, center, rest) = match calibration {
+            Some(calibration) if config.asymmetric => {
+                let (gain, negative_gain) = calibration.asymmetric_gains(range);
+                (gain, negative_gain, range.center(), calibration.center)
+            }
+            Some(calibration) => {
+                let (gain, center) = calibration.mapping(range);
+                (gain, gain, center, 0)
+            }
+            None => (range.gain(), range.gain(), range.center(), 0),
+        };
+
+        let mut mapping = Self {
+            offset: 0,
+            rest,
+            gain: config.gain.unwrap_or(gain),
+            negative_gain: config
+                .negative_gain
+                .or(config.gain)
+                .unwrap_or(negative_gain),
+            center: config.center.unwrap_or(center),
+            trim: 0,
+            sensitivity: 1.0,
+            fine_gain: config.fine_gain.max(0.0),
+            fine: false,
+            deadzone: (config.deadzone as i32).min(MAX_DEFLECTION - 1),
+            extra_deadzone: 0,
+            invert: config.invert,
+            expo: config.expo.clamp(0.0, 1.0),
+            curve: config.curve.clone(),
+            anti_deadzone: config.anti_deadzone.clamp(0.0, 1.0),
+            range,
+        };
+
+        mapping.set_trim(config.trim);
+
+        mapping
+    }
+
+    /// Shift the center by `trim`, only as far as full deflection still reaches both ends of the range.
+    ///
+    /// Returns the trim kept.
+    pub fn set_trim(&mut self, trim: i32) -> i32 {
+        let (up, down) = self.gains();
+        let reach_up = reach(up);
+        let reach_down = reach(down);
+        let low = self.range.max.saturating_sub(reach_up);
+        let high = self.range.min.saturating_add(reach_down);
+
+        //An untrimmed center stays allowed whatever the gain
+        let (low, high) = if low <= high {
+            (low.min(self.center), high.max(self.center))
+        } else {
+            (self.center, self.center)
+        };
+
+        self.trim = self.center.saturating_add(trim).clamp(low, high) - self.center;
+        self.trim
+    }
+
+    /// Gains of the values fed above and below the center.
+    ///
+    /// `negative_gain` is for raw values below rest, fed above the center once inverted.
+    fn gains(&self) -> (f32, f32) {
+        if self.invert {
+            (self.negative_gain, self.gain)
+        } else {
+            (self.gain, self.negative_gain)
+        }
+    }
+
+    pub fn apply(&self, raw: i16) -> i32 {
+        let raw = raw.saturating_sub(self.offset).saturating_sub(self.rest);
+
+        // Flip before scaling so an inverted axis still rests on center
+        let raw = if self.invert {
+            raw.saturating_neg()
+        } else {
+            raw
+        };
+
+        let deadzone = self.deadzone.saturating_add(self.extra_deadzone);
+        let raw = apply_deadzone(raw, deadzone.min(MAX_DEFLECTION - 1));
+
+        let raw = if self.expo > 0.0 {
+            reshape(raw, |normalized| apply_expo(normalized, self.expo))
+        } else {
+            raw
+        };
+
+        let raw = match &self.curve {
+            Some(curve) => reshape(raw, |normalized| curve.eval(normalized)),
+            None => raw,
+        };
+
+        let stage = if self.fine { self.fine_gain } else { 1.0 };
+        let (up, down) = self.gains();
+        let gain = if raw < 0 { down } else { up };
+        let gain = gain * self.sensitivity * stage;
+        let center = self.center.saturating_add(self.trim);
+        let value = scale_axis(raw, gain, center);
+
+        let value = if self.anti_deadzone > 0.0 {
+            center.saturating_add(apply_anti_deadzone(
+                value.saturating_sub(center),
+                reach(gain),
+                self.anti_deadzone,
+            ))
+        } else {
+            value
+        };
+
+        clamp_axis(value, self.range.min, self.range.max)
+    }
+
+    /// Share of the range from the trimmed center to the end on its side reached by `value`, from -1 to 1.
+    pub fn deflection(&self, value: i32) -> f64 {
+        let center = self.center.saturating_add(self.trim);
+        let reach = if value < center {
+            center - self.range.min
+        } else {
+            self.range.max - center
+        };
+
+        if reach <= 0 {
+            return 0.0;
+        }
+
+        (value - center) as f64 / reach as f64
+    }
+}
+
+/// Conversion, smoothing and gating of one axis, keeping state between reports.
+#[derive(Debug, Clone)]
+pub struct AxisChannel {
+    pub mapping: AxisMapping,
+    /// Set in relative mode
+    integrator: Option<Integrator>,
+    filter: Filter,
+    slew: SlewLimiter,
+    gate: NoiseGate,
+    hold: UpdateHold,
+}
+
+impl AxisChannel {
+    pub fn new(
+        config: &AxisConfig,
+        range: AxisRange,
+        calibration: Option<&AxisCalibration>,
+    ) -> Self {
+        let integrator = match config.mode {
+            AxisMode::Absolute => None,
+            AxisMode::Relative => Some(Integrator::new(config.rate, config.decay)),
+        };
+
+        Self {
+            mapping: AxisMapping::new(config, range, calibration),
+            integrator,
+            filter: Filter::new(config),
+            slew: SlewLimiter::new(config.slew),
+            gate: NoiseGate::new(config.gate),
+            hold: UpdateHold::new(Duration::from_millis(config.interval_ms)),
+        }
+    }
+
+    pub fn convert(&mut self, raw: i16) -> i32 {
+        let value = self.mapping.apply(raw);
+
+        let value = match &mut self.integrator {
+            Some(integrator) => integrator.apply(value, &self.mapping, Instant::now()),
+            None => value,
+        };
+
+        let value = self.filter.apply(value);
+        let value = self.slew.apply(value);
+        let value = self.gate.apply(value);
+
+        self.hold.apply(value, Instant::now())
+    }
+
+    /// Keep a relative, slew limited or held axis moving between reports, `None` if it stays put.
+    pub fn tick(&mut self) -> Option<i32> {
+        let now = Instant::now();
+
+        let value = match &mut self.integrator {
+            Some(integrator) if !integrator.is_settled(&self.mapping) => {
+                let value = integrator.advance(&self.mapping, now);
+                let value = self.filter.apply(value);
+
+                self.slew.apply(value)
+            }
+            _ if !self.slew.is_settled() => self.slew.advance(),
+            _ => return self.hold.advance(now),
+        };
+
+        let value = self.gate.apply(value);
+
+        Some(self.hold.apply(value, now))
+    }
+
+    /// Whether `tick` has anything to do.
+    pub fn is_settled(&self) -> bool {
+        self.slew.is_settled()
+            && self.hold.is_settled()
+            && self
+                .integrator
+                .as_ref()
+                .is_none_or(|integrator| integrator.is_settled(&self.mapping))
+    }
+
+    /// Restart the filters from the center, e.g. after a reconnect.
+    pub fn reset(&mut self) {
+        if let Some(integrator) = &mut self.integrator {
+            integrator.reset();
+        }
+
+        self.filter.reset(self.mapping.center);
+        self.slew.reset(self.mapping.center);
+        self.gate.reset(self.mapping.center);
+        self.hold.reset(self.mapping.center);
+    }
+
+    /// Restart the filters from `value`, the output so far, to switch without a jump.
+    pub fn resume(&mut self, value: i32) {
+        if let Some(integrator) = &mut self.integrator {
+            integrator.resume(value);
+        }
+
+        self.filter.reset(value);
+        self.slew.reset(value);
+        self.gate.reset(value);
+        self.hold.reset(value);
+    }
+}
+
+/// Convert a raw 16 bit device value to a vJoy axis value.
+///
+/// Saturates instead of overflowing with a large configured gain or center.
+pub fn scale_axis(raw: i16, gain: f32, center: i32) -> i32 {
+    ((raw as f32 * gain).round() as i32).saturating_add(center)
+}
+
+/// vJoy units from the center to full deflection with `gain`, on either side.
+fn reach(gain: f32) -> i32 {
+    (MAX_DEFLECTION as f32 * gain.abs()).round() as i32
+}
+
+/// Keep a vJoy axis value inside the range the driver accepts.
+pub fn clamp_axis(value: i32, min: i32, max: i32) -> i32 {
+    value.clamp(min, max)
+}
+
+/// Push an output `deviation` off center past `floor` of `reach`, the full deflection.
+///
+/// The rest is scaled down so full deflection still ends at `reach`, for games ignoring
+/// small values with a deadzone of their own.
+pub fn apply_anti_deadzone(deviation: i32, reach: i32, floor: f32) -> i32 {
+    if deviation == 0 || reach <= 0 {
+        return deviation;
+    }
+
+    let start = (floor * reach as f32).ceil() as i64;
+    let magnitude = (deviation as i64).abs();
+    let reach = reach as i64;
+
+    let shifted = start + magnitude * (reach - start) / reach;
+
+    (shifted * deviation.signum() as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// Zero raw values inside the deadzone.
+///
+/// Values outside are shifted towards center and stretched back to
+/// full deflection so the output ramps up from zero at the edge.
+pub fn apply_deadzone(raw: i16, deadzone: i32) -> i16 {
+    let magnitude = (raw as i32).abs();
+
+    if deadzone <= 0 {
+        return raw;
+    }
+
+    if magnitude <= deadzone {
+        return 0;
+    }
+
+    let ramped = (magnitude - deadzone) * MAX_DEFLECTION / (MAX_DEFLECTION - deadzone);
+
+    (ramped * (raw as i32).signum()).clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Apply a response curve to a raw value normalized by the full deflection.
+fn reshape(raw: i16, curve: impl Fn(f32) -> f32) -> i16 {
+    let normalized = (raw as f32 / MAX_DEFLECTION as f32).clamp(-1.0, 1.0);
+
+    (curve(normalized) * MAX_DEFLECTION as f32).round() as i16
+}
+
+/// Blend between linear (factor 0) and cubic (factor 1) response.
+///
+/// Small inputs get smaller while -1, 0 and 1 map onto themselves.
+pub fn apply_expo(normalized: f32, factor: f32) -> f32 {
+    (1.0 - factor) * normalized + factor * normalized.powi(3)
+}
+
+/// Piecewise linear response curve through (input, output) points,
+/// both normalized to the full deflection.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "Vec<(f32, f32)>", into = "Vec<(f32, f32)>")]
+pub struct Curve {
+    points: Vec<(f32, f32)>,
+}
+
+impl TryFrom<Vec<(f32, f32)>> for Curve {
+    type Error = String;
+
+    fn try_from(mut points: Vec<(f32, f32)>) -> Result<Self, Self::Error> {
+        if points.len() < 2 {
+            return Err("a curve needs at least 2 points".to_owned());
+        }
+
+        if points
+            .iter()
+            .any(|(input, output)| !input.is_finite() || !output.is_finite())
+        {
+            return Err("curve points must be finite numbers".to_owned());
+        }
+
+        points.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        if let Some(pair) = points.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("curve has several points at input {}", pair[0].0));
+        }
+
+        Ok(Self { points })
+    }
+}
+
+impl From<Curve> for Vec<(f32, f32)> {
+    fn from(curve: Curve) -> Self {
+        curve.points
+    }
+}
+
+impl Curve {
+    /// Interpolate the output for `input`, clamped to the first and last points.
+    pub fn eval(&self, input: f32) -> f32 {
+        let (first, last) = (self.points[0], self.points[self.points.len() - 1]);
+
+        if input <= first.0 {
+            return first.1;
+        }
+
+        if input >= last.0 {
+            return last.1;
+        }
+
+        let next = self.points.partition_point(|point| point.0 <= input);
+        let (x0, y0) = self.points[next - 1];
+        let (x1, y1) = self.points[next];
+
+        y0 + (y1 - y0) * (input - x0) / (x1 - x0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_negative_gain_follows_the_raw_side_when_inverted() {
+        let config = AxisConfig {
+            gain: Some(40.0),
+            negative_gain: Some(60.0),
+            invert: true,
+            ..AxisConfig::default()
+        };
+        let mapping = AxisMapping::new(&config, AxisRange::default(), None);
+        let center = AxisRange::default().center();
+
+        //Below rest, fed above the center
+        assert_eq!(mapping.apply(-100), center + 100 * 60);
+        assert_eq!(mapping.apply(100), center - 100 * 40);
+    }
+
+    #[test]
+    fn scale_axis_rests_on_the_center() {
+        assert_eq!(scale_axis(0, 47.0, 16383), 16383);
+    }
+
+    #[test]
+    fn scale_axis_reaches_both_ends_at_full_deflection() {
+        assert_eq!(scale_axis(350, 47.0, 16383), 16383 + 350 * 47);
+        assert_eq!(scale_axis(-350, 47.0, 16383), 16383 - 350 * 47);
+        assert_eq!(scale_axis(i16::MAX, 1.0, 0), 32767);
+        assert_eq!(scale_axis(i16::MIN, 1.0, 0), -32768);
+    }
+
+    #[test]
+    fn scale_axis_saturates_instead_of_overflowing() {
+        assert_eq!(scale_axis(i16::MAX, f32::MAX, 0), i32::MAX);
+        assert_eq!(scale_axis(i16::MIN, f32::MAX, 0), i32::MIN);
+        assert_eq!(scale_axis(1, 1.0, i32::MAX), i32::MAX);
+    }
+
+    //A slider, dial or wheel set up in vJoy with a range of its own
+    const SLIDER: AxisRange = AxisRange {
+        min: 1000,
+        max: 8000,
+    };
+
+    #[test]
+    fn a_custom_range_has_its_own_center_and_gain() {
+        assert_eq!(SLIDER.center(), 4500);
+        assert_eq!(SLIDER.gain(), 10.0);
+        assert_eq!(scale_axis(0, SLIDER.gain(), SLIDER.center()), 4500);
+        assert_eq!(scale_axis(350, SLIDER.gain(), SLIDER.center()), 8000);
+        assert_eq!(scale_axis(-350, SLIDER.gain(), SLIDER.center()), 1000);
+    }
+
+    #[test]
+    fn full_deflection_spans_a_narrow_range() {
+        //Narrower than twice the full deflection, the gain stays a fraction
+        let narrow = AxisRange { min: 0, max: 300 };
+        let mapping = AxisMapping::new(&AxisConfig::default(), narrow, None);
+
+        assert_eq!(mapping.apply(0), 150);
+        assert_eq!(mapping.apply(350), 300);
+        assert_eq!(mapping.apply(-350), 0);
+        assert_eq!(mapping.apply(175), 225);
+
+        //Rounding a gain of 1.43 to 1 would have stopped 150 short of each end
+        let small = AxisRange { min: 0, max: 1000 };
+        let mapping = AxisMapping::new(&AxisConfig::default(), small, None);
+
+        assert_eq!(mapping.apply(350), 1000);
+        assert_eq!(mapping.apply(-350), 0);
+    }
+
+    #[test]
+    fn full_deflection_spans_a_custom_range() {
+        let mapping = AxisMapping::new(&AxisConfig::default(), SLIDER, None);
+
+        assert_eq!(mapping.apply(0), SLIDER.center());
+        assert_eq!(mapping.apply(350), SLIDER.max);
+        assert_eq!(mapping.apply(-350), SLIDER.min);
+        assert_eq!(mapping.apply(i16::MAX), SLIDER.max);
+        assert_eq!(mapping.apply(i16::MIN), SLIDER.min);
+    }
+
+    #[test]
+    fn slider_dial_and_wheel_are_fields_of_their_own() {
+        let mut position = crate::vjoy::new_position(1);
+
+        for (field, value) in [(Field::Slider, 1), (Field::Dial, 2), (Field::Wheel, 3)] {
+            write_field(&mut position, field, value);
+        }
+
+        assert_eq!(position.wSlider, 1);
+        assert_eq!(position.wDial, 2);
+        assert_eq!(position.wWheel, 3);
+        assert_eq!(read_field(&position, Field::Dial), 2);
+    }
+
+    #[test]
+    fn expo_zero_is_linear() {
+        for normalized in [-1.0, -0.5, -0.1, 0.0, 0.25, 0.5, 1.0] {
+            assert_eq!(apply_expo(normalized, 0.0), normalized);
+        }
+    }
+
+    #[test]
+    fn expo_is_monotonic_and_keeps_the_ends() {
+        for factor in [0.0, 0.3, 0.7, 1.0] {
+            let outputs: Vec<f32> = (-100..=100)
+                .map(|step| apply_expo(step as f32 / 100.0, factor))
+                .collect();
+
+            assert!(outputs.windows(2).all(|pair| pair[0] < pair[1]));
+            assert_eq!(apply_expo(-1.0, factor), -1.0);
+            assert_eq!(apply_expo(0.0, factor), 0.0);
+            assert_eq!(apply_expo(1.0, factor), 1.0);
+        }
+    }
+
+    #[test]
+    fn expo_softens_small_inputs() {
+        assert!(apply_expo(0.2, 0.5) < 0.2);
+        assert!(apply_expo(-0.2, 0.5) > -0.2);
+    }
+
+    #[test]
+    fn anti_deadzone_starts_past_the_floor() {
+        //Smallest deviation on either side jumps to a quarter of the reach
+        assert_eq!(apply_anti_deadzone(1, 1000, 0.25), 250);
+        assert_eq!(apply_anti_deadzone(-1, 1000, 0.25), -250);
+        assert_eq!(apply_anti_deadzone(0, 1000, 0.25), 0);
+        assert_eq!(apply_anti_deadzone(1000, 1000, 0.25), 1000);
+        assert_eq!(apply_anti_deadzone(500, 1000, 0.25), 625);
+    }
+
+    #[test]
+    fn anti_deadzone_is_applied_around_the_center() {
+        let config = AxisConfig {
+            anti_deadzone: 0.2,
+            ..AxisConfig::default()
+        };
+        let mapping = AxisMapping::new(&config, AxisRange::default(), None);
+        let center = AxisRange::default().center();
+        let reach = reach(AxisRange::default().gain());
+
+        assert_eq!(mapping.apply(0), center);
+        assert!(mapping.apply(1) >= center + reach / 5);
+        assert!(mapping.apply(-1) <= center - reach / 5);
+        assert_eq!(mapping.apply(350), AxisRange::default().max);
+    }
+
+    #[test]
+    fn anti_deadzone_starts_at_the_edge_of_the_deadzone() {
+        let config = AxisConfig {
+            deadzone: 20,
+            anti_deadzone: 0.2,
+            ..AxisConfig::default()
+        };
+        let mapping = AxisMapping::new(&config, AxisRange::default(), None);
+        let center = AxisRange::default().center();
+        let reach = reach(AxisRange::default().gain());
+
+        assert_eq!(mapping.apply(20), center);
+        assert_eq!(mapping.apply(-20), center);
+        assert!(mapping.apply(21) >= center + reach / 5);
+        assert!(mapping.apply(-21) <= center - reach / 5);
+    }
+
+    #[test]
+    fn anti_deadzone_saturates_with_an_extreme_center() {
+        let config = AxisConfig {
+            anti_deadzone: 0.5,
+            center: Some(i32::MAX - 100),
+            ..AxisConfig::default()
+        };
+        let mapping = AxisMapping::new(&config, AxisRange::default(), None);
+
+        assert_eq!(mapping.apply(1), AxisRange::default().max);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::axis::Axis;
+use crate::buttons;
+use crate::config::{AxisButtonConfig, Direction};
+use crate::hysteresis::Hysteresis;
+
+/// vJoy button held while the converted output of an axis stays past a threshold.
+#[derive(Debug, Clone)]
+pub struct AxisButton {
+    pub axis: Axis,
+    /// Bit of the vJoy button
+    pub button: u64,
+    /// 1 or -1, the side of center pressing the button
+    sign: f64,
+    pressed: Hysteresis,
+}
+
+impl AxisButton {
+    pub fn new(config: &AxisButtonConfig) -> Self {
+        Self {
+            axis: config.axis,
+            button: buttons::button_mask(&[config.button]),
+            sign: match config.direction {
+                Direction::Positive => 1.0,
+                Direction::Negative => -1.0,
+            },
+            pressed: Hysteresis::below(config.threshold as f64, config.hysteresis as f64),
+        }
+    }
+
+    /// Follow the `deflection` of the axis output, from -1 to 1, true while the button is held.
+    pub fn update(&mut self, deflection: f64) -> bool {
+        self.pressed.update(self.sign * deflection)
+    }
+
+    /// Bit of the button if held, 0 otherwise.
+    pub fn output(&self) -> u64 {
+        if self.pressed.is_on() {
+            self.button
+        } else {
+            0
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.pressed.reset();
+    }
+}
+
+/// Highest vJoy button pressed by `configs`, 0 if none.
+pub fn button_count(configs: &[AxisButtonConfig]) -> u32 {
+    configs
+        .iter()
+        .map(|config| config.button)
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,193 @@
+use rusty_vjoy::JoystickPosition;
+
+use crate::report::MAX_BUTTONS;
+
+/// Set vJoy buttons 1-32 and 33-64 from a button bitmask.
+pub fn write_buttons(position: &mut JoystickPosition, buttons: u64) {
+    position.lButtons = buttons as u32 as i32;
+    position.lButtonsEx1 = (buttons >> 32) as u32 as i32;
+}
+
+/// Button bitmask currently set in `position`.
+pub fn read_buttons(position: &JoystickPosition) -> u64 {
+    position.lButtons as u32 as u64 | (position.lButtonsEx1 as u32 as u64) << 32
+}
+
+/// Bitmask of 1-based button numbers, out of range numbers are ignored.
+pub fn button_mask(numbers: &[u32]) -> u64 {
+    numbers
+        .iter()
+        .filter(|&&number| (1..=MAX_BUTTONS).contains(&number))
+        .fold(0, |mask, number| mask | 1 << (number - 1))
+}
+
+/// Turns momentary buttons into toggles, press once to latch on and again to release.
+#[derive(Debug, Clone, Default)]
+pub struct ButtonLatch {
+    toggles: u64,
+    latched: u64,
+    last: u64,
+}
+
+impl ButtonLatch {
+    pub fn new(toggles: u64) -> Self {
+        Self {
+            toggles,
+            ..Self::default()
+        }
+    }
+
+    /// Mix the momentary buttons of `bits` with the latched toggles.
+    pub fn apply(&mut self, bits: u64) -> u64 {
+        let pressed = bits & !self.last;
+
+        self.latched ^= pressed & self.toggles;
+        self.last = bits;
+
+        (bits & !self.toggles) | self.latched
+    }
+}
+
+/// Moves device buttons onto other vJoy buttons, the others keep their number.
+#[derive(Debug, Clone, Default)]
+pub struct ButtonRemap {
+    /// Device and vJoy button bits
+    pairs: Vec<(u64, u64)>,
+    sources: u64,
+}
+
+impl ButtonRemap {
+    /// `remap` pairs device and vJoy button numbers from 1, for a device with `count` buttons.
+    ///
+    /// Two fed buttons may only land on the same vJoy button when `shared`, those in `unfed` don't count.
+    pub fn new(remap: &[(u32, u32)], count: u32, unfed: u64, shared: bool) -> Result<Self, String> {
+        let mut pairs = Vec::new();
+        let mut sources = 0;
+
+        for &(from, to) in remap {
+            for number in [from, to] {
+                if !(1..=MAX_BUTTONS).contains(&number) {
+                    return Err(format!(
+                        "remapped buttons must be between 1 and {}, got {}",
+                        MAX_BUTTONS, number
+                    ));
+                }
+            }
+
+            let from_bit = button_mask(&[from]);
+
+            if sources & from_bit != 0 {
+                return Err(format!("button {} is remapped more than once", from));
+            }
+
+            sources |= from_bit;
+            pairs.push((from_bit, button_mask(&[to])));
+        }
+
+        let remap = Self { pairs, sources };
+
+        if !shared {
+            let mut taken = 0u64;
+
+            for number in 1..=count.min(MAX_BUTTONS) {
+                let bit = button_mask(&[number]);
+
+                if unfed & bit != 0 {
+                    continue;
+                }
+
+                let target = remap.apply(bit);
+
+                if taken & target != 0 {
+                    return Err(format!(
+                        "button {} lands on vJoy button {} like another one, set remap_shared to allow it",
+                        number,
+                        target.trailing_zeros() + 1
+                    ));
+                }
+
+                taken |= target;
+            }
+        }
+
+        Ok(remap)
+    }
+
+    pub fn apply(&self, bits: u64) -> u64 {
+        self.pairs
+            .iter()
+            .filter(|&&(from, _)| bits & from != 0)
+            .fold(bits & !self.sources, |mapped, &(_, to)| mapped | to)
+    }
+}
+
+/// Highest vJoy button `remap` moves a button to, 0 if none.
+pub fn remap_count(remap: &[(u32, u32)]) -> u32 {
+    remap.iter().map(|&(_, to)| to).max().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_remap_onto_a_fed_button_is_rejected() {
+        //Button 1 onto 2, which the device still feeds
+        assert!(ButtonRemap::new(&[(1, 2)], 2, 0, false).is_err());
+    }
+
+    #[test]
+    fn the_same_collision_is_allowed_when_shared() {
+        let remap = ButtonRemap::new(&[(1, 2)], 2, 0, true).unwrap();
+
+        assert_eq!(remap.apply(0b01), 0b10);
+        assert_eq!(remap.apply(0b10), 0b10);
+    }
+
+    #[test]
+    fn unfed_buttons_dont_collide() {
+        //Button 2 goes elsewhere, e.g. to a key, so 1 may take its place
+        let remap = ButtonRemap::new(&[(1, 2)], 2, 0b10, false).unwrap();
+
+        assert_eq!(remap.apply(0b01), 0b10);
+    }
+
+    #[test]
+    fn swapped_buttons_dont_collide() {
+        let remap = ButtonRemap::new(&[(1, 2), (2, 1)], 2, 0, false).unwrap();
+
+        assert_eq!(remap.apply(0b01), 0b10);
+        assert_eq!(remap.apply(0b10), 0b01);
+        assert_eq!(remap.apply(0b11), 0b11);
+    }
+
+    #[test]
+    fn a_button_remapped_twice_or_out_of_range_is_rejected() {
+        assert!(ButtonRemap::new(&[(1, 3), (1, 4)], 2, 0, true).is_err());
+        assert!(ButtonRemap::new(&[(0, 3)], 2, 0, true).is_err());
+        assert!(ButtonRemap::new(&[(1, MAX_BUTTONS + 1)], 2, 0, true).is_err());
+    }
+
+    #[test]
+    fn a_toggle_latches_on_a_press_and_releases_on_the_next() {
+        let mut latch = ButtonLatch::new(0b01);
+
+        assert_eq!(latch.apply(0b01), 0b01);
+        assert_eq!(latch.apply(0b00), 0b01);
+        assert_eq!(latch.apply(0b01), 0b00);
+        assert_eq!(latch.apply(0b00), 0b00);
+    }
+
+    #[test]
+    fn a_held_toggle_does_not_retoggle() {
+        let mut latch = ButtonLatch::new(0b01);
+
+        for _ in 0..5 {
+            assert_eq!(latch.apply(0b01), 0b01);
+        }
+
+        //Momentary buttons pass as they are
+        assert_eq!(latch.apply(0b11), 0b11);
+        assert_eq!(latch.apply(0b01), 0b01);
+    }
+}
@@ -0,0 +1,298 @@
+use hidapi::HidDevice;
+
+use log::info;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::axis::{Axis, AxisRange};
+use crate::config::ConfigError;
+use crate::device::READ_TIMEOUT_MS;
+use crate::error::FeederError;
+use crate::report::{Report, ReportParser};
+use crate::source::InputSource;
+
+//How long the puck is sampled at rest
+const REST_SAMPLING: Duration = Duration::from_secs(2);
+
+//Under the platform config directory
+const CALIBRATION_PATH: [&str; 2] = ["vjoy-feeder", "calibration.json"];
+
+/// Raw values observed for one axis while calibrating.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AxisCalibration {
+    /// Average at rest
+    pub center: i16,
+    pub min: i16,
+    pub max: i16,
+}
+
+impl AxisCalibration {
+    /// Gain and center mapping the observed deflection onto `range`, with rest on its center.
+    ///
+    /// The larger side of the deflection sets the gain so neither end overshoots.
+    pub fn mapping(&self, range: AxisRange) -> (f32, i32) {
+        let center = self.center as i32;
+        let extent = (self.max as i32 - center)
+            .max(center - self.min as i32)
+            .max(1);
+
+        let gain = (range.max - range.min) as f32 / (2 * extent) as f32;
+
+        (gain, range.center() - (center as f32 * gain).round() as i32)
+    }
+
+    /// Gains above and below rest mapping each side of the observed deflection onto half of `range`.
+    pub fn asymmetric_gains(&self, range: AxisRange) -> (f32, f32) {
+        let half_span = (range.max - range.min) as f32 / 2.0;
+        let center = self.center as i32;
+
+        let positive = (self.max as i32 - center).max(1);
+        let negative = (center - self.min as i32).max(1);
+
+        (half_span / positive as f32, half_span / negative as f32)
+    }
+}
+
+/// Calibration of one unit, in `Axis::ALL` order.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceCalibration {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub axes: [AxisCalibration; 6],
+}
+
+/// Calibrations of every unit seen, by serial number.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Calibrations {
+    pub devices: BTreeMap<String, DeviceCalibration>,
+}
+
+impl Calibrations {
+    /// Where the calibrations are stored, `None` if the platform has no config directory.
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| {
+            CALIBRATION_PATH
+                .iter()
+                .fold(dir, |path, part| path.join(part))
+        })
+    }
+
+    /// Load the stored calibrations, none if nothing was saved yet.
+    pub fn load() -> Result<Self, ConfigError> {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(Self::default()),
+        };
+
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(ConfigError::Io(error)),
+        };
+
+        let calibrations = serde_json::from_str(&text).map_err(ConfigError::Calibration)?;
+
+        info!("Calibration loaded from {}", path.display());
+
+        Ok(calibrations)
+    }
+
+    pub fn save(&self) -> Result<(), ConfigError> {
+        let path = Self::path().ok_or_else(|| {
+            ConfigError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no config directory on this platform",
+            ))
+        })?;
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(ConfigError::Io)?;
+        }
+
+        let text = serde_json::to_string_pretty(self).map_err(ConfigError::Calibration)?;
+
+        fs::write(&path, text).map_err(ConfigError::Io)?;
+
+        info!("Calibration saved to {}", path.display());
+
+        Ok(())
+    }
+
+    /// Calibration of the unit with `serial`, if it is the same model.
+    pub fn get(
+        &self,
+        serial: &str,
+        vendor_id: u16,
+        product_id: u16,
+    ) -> Option<&[AxisCalibration; 6]> {
+        self.devices
+            .get(serial)
+            .filter(|device| device.vendor_id == vendor_id && device.product_id == product_id)
+            .map(|device| &device.axes)
+    }
+}
+
+/// Serial number of `device`, empty if it has none.
+pub fn serial_of(device: &HidDevice) -> String {
+    device
+        .get_serial_number_string()
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+}
+
+/// Walk the user through capturing the rest position then the extents of each axis.
+///
+/// Returns `None` if `running` is cleared midway.
+pub fn calibrate(
+    source: &mut dyn InputSource,
+    parser: &dyn ReportParser,
+    running: &AtomicBool,
+) -> Result<Option<[AxisCalibration; 6]>, FeederError> {
+    let mut buffer = vec![0u8; parser.report_len()];
+    let mut calibration = [AxisCalibration::default(); 6];
+
+    println!("Leave the puck at rest and press enter");
+    wait_for_enter(source, &mut buffer, running)?;
+
+    println!("Sampling the rest position...");
+
+    let rest = match sample_rest(source, parser, REST_SAMPLING, running)? {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+
+    for (index, center) in rest.into_iter().enumerate() {
+        calibration[index] = AxisCalibration {
+            center,
+            min: center,
+            max: center,
+        };
+    }
+
+    for axis in Axis::ALL {
+        println!(
+            "Move axis {} fully both ways, then press enter",
+            axis.name()
+        );
+
+        let done = enter_pressed();
+
+        while !done.load(Ordering::Relaxed) {
+            if !running.load(Ordering::Relaxed) {
+                return Ok(None);
+            }
+
+            let report = read_report(source, parser, &mut buffer)?;
+
+            if let Some(raw) = report.axes[axis as usize] {
+                let axis = &mut calibration[axis as usize];
+                axis.min = axis.min.min(raw);
+                axis.max = axis.max.max(raw);
+            }
+        }
+
+        let observed = calibration[axis as usize];
+
+        println!(
+            "Axis {}: center {}, min {}, max {}",
+            axis.name(),
+            observed.center,
+            observed.min,
+            observed.max
+        );
+    }
+
+    Ok(Some(calibration))
+}
+
+/// Average raw value of each axis over `window`, `None` if `running` is cleared meanwhile.
+///
+/// Axes that report nothing average to 0.
+pub fn sample_rest(
+    source: &mut dyn InputSource,
+    parser: &dyn ReportParser,
+    window: Duration,
+    running: &AtomicBool,
+) -> Result<Option<[i16; 6]>, FeederError> {
+    let mut buffer = vec![0u8; parser.report_len()];
+    let mut sums = [0i64; 6];
+    let mut counts = [0i64; 6];
+    let deadline = Instant::now() + window;
+
+    while Instant::now() < deadline {
+        if !running.load(Ordering::Relaxed) {
+            return Ok(None);
+        }
+
+        let report = read_report(source, parser, &mut buffer)?;
+
+        for axis in Axis::ALL {
+            if let Some(raw) = report.axes[axis as usize] {
+                sums[axis as usize] += raw as i64;
+                counts[axis as usize] += 1;
+            }
+        }
+    }
+
+    Ok(Some(Axis::ALL.map(|axis| {
+        let index = axis as usize;
+
+        if counts[index] > 0 {
+            (sums[index] / counts[index]) as i16
+        } else {
+            0
+        }
+    })))
+}
+
+/// Next report, empty if none came or `source` ran dry.
+fn read_report(
+    source: &mut dyn InputSource,
+    parser: &dyn ReportParser,
+    buffer: &mut [u8],
+) -> Result<Report, FeederError> {
+    let mut report = Report::default();
+    let size = source.read(buffer, READ_TIMEOUT_MS)?.unwrap_or(0);
+
+    parser.parse(&buffer[..size], &mut report);
+
+    Ok(report)
+}
+
+/// Keep draining reports so none are stale once enter is pressed.
+fn wait_for_enter(
+    source: &mut dyn InputSource,
+    buffer: &mut [u8],
+    running: &AtomicBool,
+) -> Result<(), FeederError> {
+    let done = enter_pressed();
+
+    while !done.load(Ordering::Relaxed) && running.load(Ordering::Relaxed) {
+        source.read(buffer, READ_TIMEOUT_MS)?;
+    }
+
+    Ok(())
+}
+
+/// Set once a line is read from stdin, so reports can be read meanwhile.
+fn enter_pressed() -> Arc<AtomicBool> {
+    let done = Arc::new(AtomicBool::new(false));
+    let done_clone = done.clone();
+
+    thread::spawn(move || {
+        let _ = io::stdin().read_line(&mut String::new());
+        done_clone.store(true, Ordering::Relaxed);
+    });
+
+    done
+}
@@ -0,0 +1,193 @@
+use std::time::{Duration, Instant};
+
+use crate::buttons;
+use crate::config::{ChordConfig, PressAction};
+
+/// Recognizes buttons pressed together, and keeps them from vJoy meanwhile.
+#[derive(Debug, Clone)]
+pub struct Chord {
+    /// Bits of the buttons making the chord
+    pub buttons: u64,
+    pub action: Option<PressAction>,
+    window: Duration,
+    /// When the first button went down, while the chord may still form
+    since: Option<Instant>,
+    /// Formed, the buttons stay off until all are released
+    active: bool,
+    /// Didn't form in time, the buttons pass until all are released
+    missed: bool,
+    /// Whether all the buttons are still down since it formed
+    held: bool,
+}
+
+impl Chord {
+    pub fn new(config: &ChordConfig) -> Self {
+        Self {
+            buttons: buttons::button_mask(&config.buttons),
+            action: config.action,
+            window: Duration::from_millis(config.window_ms),
+            since: None,
+            active: false,
+            missed: false,
+            held: false,
+        }
+    }
+
+    /// Follow the buttons `bits` at `now`, true when the chord just formed.
+    pub fn update(&mut self, bits: u64, now: Instant) -> bool {
+        let down = bits & self.buttons;
+
+        if down == 0 {
+            self.reset();
+            return false;
+        }
+
+        if self.active {
+            self.held &= down == self.buttons;
+            return false;
+        }
+
+        if self.missed {
+            return false;
+        }
+
+        let since = *self.since.get_or_insert(now);
+
+        if now > since + self.window {
+            self.missed = true;
+            return false;
+        }
+
+        if down == self.buttons {
+            self.active = true;
+            self.held = true;
+        }
+
+        self.active
+    }
+
+    /// `bits` without the buttons held back while the chord may form or lasts.
+    pub fn filter(&self, bits: u64) -> u64 {
+        if self.active || (self.since.is_some() && !self.missed) {
+            bits & !self.buttons
+        } else {
+            bits
+        }
+    }
+
+    /// vJoy button held while all the buttons are, 0 for none.
+    pub fn output(&self) -> u64 {
+        match self.action {
+            Some(PressAction::Button(button)) if self.held => buttons::button_mask(&[button]),
+            _ => 0,
+        }
+    }
+
+    /// When the held back buttons pass if the chord isn't complete by then.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.since {
+            Some(since) if !self.active && !self.missed => Some(since + self.window),
+            _ => None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.since = None;
+        self.active = false;
+        self.missed = false;
+        self.held = false;
+    }
+}
+
+/// Highest vJoy button pressed by `chords`, 0 if none.
+pub fn button_count(chords: &[ChordConfig]) -> u32 {
+    chords
+        .iter()
+        .filter_map(|chord| match chord.action {
+            Some(PressAction::Button(button)) => Some(button),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    //Buttons 1 and 2 together hold button 9
+    fn chord() -> Chord {
+        Chord::new(&ChordConfig {
+            buttons: vec![1, 2],
+            action: Some(PressAction::Button(9)),
+            window_ms: 100,
+        })
+    }
+
+    #[test]
+    fn buttons_pressed_within_the_window_form_the_chord() {
+        let start = Instant::now();
+        let mut chord = chord();
+
+        assert!(!chord.update(0b01, start));
+        assert_eq!(chord.deadline(), Some(start + ms(100)));
+        assert!(chord.update(0b11, start + ms(100)));
+        assert!(!chord.update(0b11, start + ms(150)));
+
+        assert_eq!(chord.output(), 1 << 8);
+        assert_eq!(chord.filter(0b111), 0b100);
+        assert_eq!(chord.deadline(), None);
+    }
+
+    #[test]
+    fn the_first_button_is_held_back_then_let_through_on_a_miss() {
+        let start = Instant::now();
+        let mut chord = chord();
+
+        chord.update(0b01, start);
+        assert_eq!(chord.filter(0b01), 0);
+
+        assert!(!chord.update(0b01, start + ms(101)));
+        assert_eq!(chord.filter(0b01), 0b01);
+
+        //Too late, the second button passes as well
+        assert!(!chord.update(0b11, start + ms(150)));
+        assert_eq!(chord.filter(0b11), 0b11);
+        assert_eq!(chord.output(), 0);
+    }
+
+    #[test]
+    fn the_output_ends_with_the_first_button_released() {
+        let start = Instant::now();
+        let mut chord = chord();
+
+        chord.update(0b11, start);
+        assert_eq!(chord.output(), 1 << 8);
+
+        chord.update(0b10, start + ms(200));
+        assert_eq!(chord.output(), 0);
+
+        //Pressed again before all are released, it doesn't form again
+        assert!(!chord.update(0b11, start + ms(250)));
+        assert_eq!(chord.output(), 0);
+        assert_eq!(chord.filter(0b11), 0);
+    }
+
+    #[test]
+    fn releasing_all_the_buttons_starts_over() {
+        let start = Instant::now();
+        let mut chord = chord();
+
+        chord.update(0b01, start);
+        chord.update(0b01, start + ms(200));
+        chord.update(0, start + ms(300));
+
+        assert_eq!(chord.deadline(), None);
+        assert!(!chord.update(0b10, start + ms(400)));
+        assert!(chord.update(0b11, start + ms(450)));
+    }
+}
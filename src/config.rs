@@ -0,0 +1,911 @@
+use log::info;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use toml::value::{Table, Value};
+
+use crate::axis::{Axis, Curve, Field};
+use crate::report::ByteOrder;
+use crate::VENDOR_ID;
+
+pub const DEFAULT_CONFIG_PATH: &str = "vjoy-feeder.toml";
+
+/// Commented config with the defaults, written by `--generate-config`.
+pub const STARTER_CONFIG: &str = r#"# vjoy-feeder config, every setting shows its default
+# flags given on the command line take precedence
+
+# HID device to read from
+[device]
+# USB vendor ID, 0x046d is 3Dconnexion
+vendor_id = 0x046d
+# USB product ID, unset matches any known 3Dconnexion model, 0xc626 is the SpaceNavigator
+# product_id = 0xc626
+# serial number of the unit to read when several match the IDs, see --list-devices
+# serial = ""
+# average the rest position at startup for this many ms and subtract it, 0 skips it
+auto_zero_ms = 0
+# zero the axes again each time they all stayed this many ms within recenter_band raw units of rest, 0 never does
+recenter_ms = 0
+recenter_band = 10
+# byte order of the 16 bit axis values, "little" or "big"
+byte_order = "little"
+# reopen the device after this many ms without a report, 0 never does
+watchdog_ms = 0
+# poll the device instead of waiting for each report
+non_blocking = false
+# hold each vJoy update up to this many ms for both the translation and rotation reports, 0 sends each
+coalesce_ms = 0
+
+# vJoy device to feed
+[vjoy]
+# from 1 to 16, or a list such as [1, 2] to feed several devices the same state
+id = 1
+# tries at acquiring each device, e.g. while another feeder releases it
+acquire_attempts = 5
+
+# buttons, numbered from 1
+[buttons]
+# buttons that latch on and off on each press
+toggle = []
+# gain multiplier and added deadzone while the precision button is held
+precision_gain = 0.25
+precision_deadzone = 0
+# gain multiplier while the boost button is held, outputs still stop at the ends of the range
+boost_gain = 2.0
+
+# raw * gain + center for each of x, y, z, rx, ry and rz
+# unset gain and center fit the full deflection onto the vJoy axis range,
+# e.g. gain = 46.81 and center = 16383 on the default range
+[axes.x]
+enabled = true
+# raw values up to this magnitude read as center
+deadzone = 0
+# reverse the axis direction
+invert = false
+# response curve from 0 (linear) to 1 (cubic)
+expo = 0.0
+# smoothing, "none", "ema" or "window"
+filter = "none"
+
+[axes.y]
+enabled = true
+deadzone = 0
+
+[axes.z]
+enabled = true
+deadzone = 0
+
+[axes.rx]
+enabled = true
+deadzone = 0
+
+[axes.ry]
+enabled = true
+deadzone = 0
+
+[axes.rz]
+enabled = true
+deadzone = 0
+"#;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Calibration(serde_json::Error),
+    Write(toml::ser::Error),
+    Exists(PathBuf),
+}
+
+impl std::error::Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(error) => write!(f, "cannot read config file: {}", error),
+            ConfigError::Parse(error) => write!(f, "invalid config file: {}", error),
+            ConfigError::Calibration(error) => write!(f, "invalid calibration file: {}", error),
+            ConfigError::Write(error) => write!(f, "cannot write config file: {}", error),
+            ConfigError::Exists(path) => write!(
+                f,
+                "{} already exists, add --force to overwrite it",
+                path.display()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub device: DeviceConfig,
+    pub vjoy: VJoyConfig,
+    pub axes: AxesConfig,
+    pub buttons: ButtonsConfig,
+    pub split: Option<SplitConfig>,
+    pub hat: Option<HatConfig>,
+    pub osc: Option<OscConfig>,
+    pub gestures: Vec<GestureConfig>,
+    /// Keyboard keys sent by buttons, which vJoy then doesn't get
+    pub keys: Vec<KeyConfig>,
+    /// Buttons doing different things on a tap, a long press or a double press
+    pub presses: Vec<PressConfig>,
+    /// Buttons pressed together doing something of their own
+    pub chords: Vec<ChordConfig>,
+    /// vJoy buttons held while axes are pushed past a point
+    pub axis_buttons: Vec<AxisButtonConfig>,
+    /// Profile used at startup, the `[axes]` settings if unset
+    pub profile: Option<String>,
+    pub profiles: BTreeMap<String, ProfileConfig>,
+    /// Profiles following the application in focus, on Windows
+    pub apps: Option<AppsConfig>,
+    /// Devices fed by this process, each to its own vJoy devices, `[device]` alone if empty
+    pub bindings: Vec<BindingConfig>,
+}
+
+impl Config {
+    /// Settings as a config file, without the comments.
+    pub fn to_toml(&self) -> Result<String, ConfigError> {
+        //Going through a value puts the plain settings before the tables
+        toml::Value::try_from(self)
+            .and_then(|value| toml::to_string(&value))
+            .map_err(ConfigError::Write)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("config maps have string keys")
+    }
+}
+
+/// HID device to read from
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeviceConfig {
+    pub vendor_id: u16,
+    /// Any known 3Dconnexion model if unset
+    pub product_id: Option<u16>,
+    /// Unit to read when several match the IDs
+    pub serial: Option<String>,
+    /// Average the rest position for this long at startup and subtract it, 0 to skip
+    pub auto_zero_ms: u64,
+    /// Zero the axes again once they all stayed this long near rest, 0 to never
+    pub recenter_ms: u64,
+    /// Raw units from rest each axis may move while still idle
+    pub recenter_band: u16,
+    /// Byte order of the axis values, "little" or "big"
+    pub byte_order: ByteOrder,
+    /// Reopen the device after this long without a report, 0 to never
+    pub watchdog_ms: u64,
+    /// Poll the device instead of waiting for each report
+    pub non_blocking: bool,
+    /// Wait this long for both the translation and rotation reports before updating vJoy, 0 to send each
+    pub coalesce_ms: u64,
+}
+
+impl Default for DeviceConfig {
+    fn default() -> Self {
+        Self {
+            vendor_id: VENDOR_ID,
+            product_id: None,
+            serial: None,
+            auto_zero_ms: 0,
+            recenter_ms: 0,
+            recenter_band: 10,
+            byte_order: ByteOrder::default(),
+            watchdog_ms: 0,
+            non_blocking: false,
+            coalesce_ms: 0,
+        }
+    }
+}
+
+/// vJoy device to feed
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VJoyConfig {
+    /// Devices fed the same state, `id = 1` or `id = [1, 2]`
+    #[serde(rename = "id", deserialize_with = "one_or_many")]
+    pub ids: Vec<u32>,
+    /// Tries at acquiring each device, e.g. while another feeder releases it
+    pub acquire_attempts: u32,
+}
+
+impl Default for VJoyConfig {
+    fn default() -> Self {
+        Self {
+            ids: vec![1],
+            acquire_attempts: 5,
+        }
+    }
+}
+
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u32>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(u32),
+        Many(Vec<u32>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(id) => vec![id],
+        OneOrMany::Many(ids) => ids,
+    })
+}
+
+/// HID device feeding its own vJoy devices, the other settings are shared
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct BindingConfig {
+    /// Serial number of the unit, as shown by `--list-devices`
+    pub serial: String,
+    /// Devices fed its state, `vjoy_id = 2` or `vjoy_id = [2, 3]`
+    #[serde(rename = "vjoy_id", deserialize_with = "one_or_many")]
+    pub vjoy_ids: Vec<u32>,
+}
+
+/// Second vJoy device fed the rotation axes, for games reading three axes per device
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SplitConfig {
+    pub vjoy_id: u32,
+    /// Field of the second device fed by each rotation axis
+    pub rx: Field,
+    pub ry: Field,
+    pub rz: Field,
+    pub buttons: SplitButtons,
+}
+
+impl Default for SplitConfig {
+    fn default() -> Self {
+        Self {
+            vjoy_id: 2,
+            rx: Field::X,
+            ry: Field::Y,
+            rz: Field::Z,
+            buttons: SplitButtons::First,
+        }
+    }
+}
+
+impl SplitConfig {
+    /// Field of the second device fed by `axis`, `None` for the translation axes.
+    pub fn target(&self, axis: Axis) -> Option<Field> {
+        match axis {
+            Axis::RX => Some(self.rx),
+            Axis::RY => Some(self.ry),
+            Axis::RZ => Some(self.rz),
+            _ => None,
+        }
+    }
+}
+
+/// vJoy devices fed the buttons when splitting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitButtons {
+    #[default]
+    First,
+    Second,
+    Both,
+}
+
+/// POV hat driven by a pair of axes, thresholded into eight directions
+///
+/// The axes are read raw, after the swaps and zeroing, whether or not they are also fed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HatConfig {
+    /// Axis pointing the hat east
+    pub x: Axis,
+    /// Axis pointing the hat south
+    pub y: Axis,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    /// Share of the full deflection pointing the hat
+    pub threshold: f32,
+    /// Share of the deflection, and of a direction sector, the hat holds on past before moving
+    pub hysteresis: f32,
+}
+
+impl Default for HatConfig {
+    fn default() -> Self {
+        Self {
+            x: Axis::RY,
+            y: Axis::RX,
+            invert_x: false,
+            invert_y: false,
+            threshold: 0.5,
+            hysteresis: 0.1,
+        }
+    }
+}
+
+/// Axis held past a threshold acting like a button, e.g. a hard push down or a twist
+///
+/// The axis is read raw like for the hat, whether or not it is also fed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GestureConfig {
+    pub axis: Axis,
+    /// Share of the full deflection, negative for the other direction
+    pub threshold: f32,
+    /// How long the axis stays past the threshold before the gesture fires
+    pub hold_ms: u64,
+    /// Share of the deflection the axis falls back past before the gesture ends
+    pub hysteresis: f32,
+    /// vJoy button held while the gesture lasts, from 1
+    pub button: Option<u32>,
+    /// Switch to the next profile when it fires
+    pub next_profile: bool,
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self {
+            axis: Axis::Z,
+            threshold: 0.8,
+            hold_ms: 200,
+            hysteresis: 0.1,
+            button: None,
+            next_profile: false,
+        }
+    }
+}
+
+/// vJoy button held while an axis is pushed past a threshold, e.g. push down to fire.
+///
+/// Unlike for gestures, the axis is read after its conversion: the deadzone, gain, curves and
+/// invert of the profile in use apply, whether or not the axis is also fed.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxisButtonConfig {
+    pub axis: Axis,
+    /// Button number from 1
+    pub button: u32,
+    pub direction: Direction,
+    /// Share of the output range, from center to the end on that side
+    pub threshold: f32,
+    /// Share of the range the output falls back below the threshold before the button is released
+    pub hysteresis: f32,
+}
+
+impl Default for AxisButtonConfig {
+    fn default() -> Self {
+        Self {
+            axis: Axis::Z,
+            button: 0,
+            direction: Direction::Positive,
+            threshold: 0.5,
+            hysteresis: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    /// Output above center
+    #[default]
+    Positive,
+    /// Output below center
+    Negative,
+}
+
+/// Keyboard key sent by a button, for hotkeys a game doesn't bind to joystick buttons
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct KeyConfig {
+    /// Button number from 1, not fed to vJoy
+    pub button: u32,
+    /// Key name, such as "f5", "space" or "page_up"
+    pub key: String,
+    #[serde(default)]
+    pub action: KeyAction,
+}
+
+/// When a button sends its key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyAction {
+    /// Key down on press, up on release
+    #[default]
+    Hold,
+    /// Key down then up on press
+    Tap,
+    /// Key down then up on release
+    Release,
+}
+
+/// Actions of a button told apart by how it is pressed, the button itself isn't fed to vJoy.
+///
+/// A tap is only known once the button is released, and once the double press time passed
+/// without a second press if there is a double press action.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PressConfig {
+    /// Button number from 1
+    pub button: u32,
+    pub tap: Option<PressAction>,
+    pub long: Option<PressAction>,
+    pub double: Option<PressAction>,
+    /// How long the button is held for a long press
+    pub long_ms: u64,
+    /// Longest wait after a release for the second press of a double press
+    pub double_ms: u64,
+}
+
+impl Default for PressConfig {
+    fn default() -> Self {
+        Self {
+            button: 0,
+            tap: None,
+            long: None,
+            double: None,
+            long_ms: 500,
+            double_ms: 300,
+        }
+    }
+}
+
+/// Buttons pressed together within a short window, fed as one vJoy button or action instead.
+///
+/// The buttons are held back for the window while the chord may still form, and stay off
+/// until all of them are released once it did.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ChordConfig {
+    /// Buttons numbered from 1, at least two fed to vJoy
+    pub buttons: Vec<u32>,
+    /// vJoy button held while the chord lasts, or a feeder function
+    pub action: Option<PressAction>,
+    /// Longest time between the first and the last button pressed
+    pub window_ms: u64,
+}
+
+impl Default for ChordConfig {
+    fn default() -> Self {
+        Self {
+            buttons: Vec::new(),
+            action: None,
+            window_ms: 50,
+        }
+    }
+}
+
+/// What a press does, a vJoy button number or a feeder function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PressAction {
+    /// vJoy button from 1, held while a long press lasts and pulsed otherwise
+    Button(u32),
+    Function(PressFunction),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PressFunction {
+    /// Switch to the next profile
+    NextProfile,
+}
+
+/// Profiles selected when applications get the focus.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppsConfig {
+    /// Profile of each executable, such as "game.exe", matched ignoring case
+    pub profiles: BTreeMap<String, String>,
+    /// Profile of the applications not listed, unset keeps the profile in use
+    pub fallback: Option<String>,
+    /// How long an application stays in focus before its profile is selected
+    pub debounce_ms: u64,
+}
+
+impl Default for AppsConfig {
+    fn default() -> Self {
+        Self {
+            profiles: BTreeMap::new(),
+            fallback: None,
+            debounce_ms: 500,
+        }
+    }
+}
+
+/// OSC messages sent alongside feeding vJoy
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OscConfig {
+    /// UDP destination, `host:port`
+    pub host: String,
+    /// Address of each axis, `{axis}` is replaced by its lowercase name
+    pub axis_address: String,
+    /// Address of each button, `{button}` is replaced by its number from 1
+    pub button_address: String,
+    /// Most frames of changed values sent per second
+    pub rate: f64,
+}
+
+impl Default for OscConfig {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1:9000".to_string(),
+            axis_address: "/vjoy/axis/{axis}".to_string(),
+            button_address: "/vjoy/button/{button}".to_string(),
+            rate: 30.0,
+        }
+    }
+}
+
+/// Named set of axis settings, switched to at runtime
+///
+/// Only the conversion of each axis is taken from it, whether an axis is fed, its field and
+/// the swaps stay those of `[axes]`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub axes: AxesConfig,
+    /// Start the filters afresh when switching to this profile instead of from the current output
+    pub reset_filters: bool,
+}
+
+/// Button bitmask layout in the buttons report, unset values depend on the device model
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ButtonsConfig {
+    pub count: Option<u32>,
+    /// Index of the first bitmask byte, after the report ID
+    pub offset: Option<usize>,
+    /// Buttons, numbered from 1, that latch on and off on each press
+    pub toggle: Vec<u32>,
+    /// Pairs of device and vJoy button numbers, for buttons fed to another vJoy button
+    pub remap: Vec<(u32, u32)>,
+    /// Let several device buttons land on the same vJoy button
+    pub remap_shared: bool,
+    /// Button that trims each axis to its current deflection, not fed to vJoy
+    pub trim: Option<u32>,
+    /// Button that clears every trim, not fed to vJoy
+    pub trim_reset: Option<u32>,
+    /// Button lowering the sensitivity while held, not fed to vJoy
+    pub precision: Option<u32>,
+    /// Multiplier of every gain in precision mode
+    pub precision_gain: f32,
+    /// Added to every deadzone in precision mode
+    pub precision_deadzone: u16,
+    /// Button raising the sensitivity while held, not fed to vJoy, precision mode wins over it
+    pub boost: Option<u32>,
+    /// Multiplier of every gain in boost mode
+    pub boost_gain: f32,
+    /// Button switching to the next profile, not fed to vJoy
+    pub profile: Option<u32>,
+    /// Button holding every axis where it is while held, not fed to vJoy
+    pub freeze: Option<u32>,
+    /// Button switching the axes between their coarse and fine gains on each press, not fed to vJoy
+    pub fine: Option<u32>,
+}
+
+impl Default for ButtonsConfig {
+    fn default() -> Self {
+        Self {
+            count: None,
+            offset: None,
+            toggle: Vec::new(),
+            remap: Vec::new(),
+            remap_shared: false,
+            trim: None,
+            trim_reset: None,
+            precision: None,
+            precision_gain: 0.25,
+            precision_deadzone: 0,
+            boost: None,
+            boost_gain: 2.0,
+            profile: None,
+            freeze: None,
+            fine: None,
+        }
+    }
+}
+
+impl ButtonsConfig {
+    /// Buttons used by the feeder itself rather than fed to vJoy.
+    pub fn functions(&self) -> Vec<u32> {
+        [
+            self.trim,
+            self.trim_reset,
+            self.precision,
+            self.boost,
+            self.profile,
+            self.freeze,
+            self.fine,
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxesConfig {
+    pub x: AxisConfig,
+    pub y: AxisConfig,
+    pub z: AxisConfig,
+    pub rx: AxisConfig,
+    pub ry: AxisConfig,
+    pub rz: AxisConfig,
+    /// Pairs of axes exchanging their raw values, each axis in one pair at most
+    pub swap: Vec<(Axis, Axis)>,
+}
+
+impl Default for AxisConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            field: None,
+            gain: None,
+            negative_gain: None,
+            asymmetric: false,
+            center: None,
+            trim: 0,
+            deadzone: 0,
+            invert: false,
+            expo: 0.0,
+            curve: None,
+            anti_deadzone: 0.0,
+            fine_gain: 1.0,
+            filter: FilterKind::None,
+            alpha: 0.5,
+            window: 4,
+            gate: 0,
+            slew: 0,
+            interval_ms: 0,
+            mode: AxisMode::Absolute,
+            rate: 1.0,
+            decay: 1.0,
+        }
+    }
+}
+
+impl AxesConfig {
+    pub fn get(&self, axis: Axis) -> &AxisConfig {
+        match axis {
+            Axis::X => &self.x,
+            Axis::Y => &self.y,
+            Axis::Z => &self.z,
+            Axis::RX => &self.rx,
+            Axis::RY => &self.ry,
+            Axis::RZ => &self.rz,
+        }
+    }
+
+    pub fn get_mut(&mut self, axis: Axis) -> &mut AxisConfig {
+        match axis {
+            Axis::X => &mut self.x,
+            Axis::Y => &mut self.y,
+            Axis::Z => &mut self.z,
+            Axis::RX => &mut self.rx,
+            Axis::RY => &mut self.ry,
+            Axis::RZ => &mut self.rz,
+        }
+    }
+
+    /// Device axis whose raw values `axis` reads.
+    pub fn source(&self, axis: Axis) -> Axis {
+        self.swap
+            .iter()
+            .find_map(|&(a, b)| {
+                if axis == a {
+                    Some(b)
+                } else if axis == b {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or(axis)
+    }
+}
+
+/// Raw 16 bit device value to vJoy axis value conversion, `raw * gain + center`
+///
+/// Unset values are derived from the vJoy axis range.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AxisConfig {
+    /// Feed this axis to vJoy, a vJoy device without it is then accepted
+    pub enabled: bool,
+    /// `JoystickPosition` field fed instead of the axis of the same name
+    pub field: Option<Field>,
+    pub gain: Option<f32>,
+    /// Gain of the values below rest, `gain` if unset
+    pub negative_gain: Option<f32>,
+    /// Scale each side of a calibrated axis to its own observed travel
+    pub asymmetric: bool,
+    pub center: Option<i32>,
+    /// Shift of the center in vJoy units, limited so both ends of the range stay reachable
+    pub trim: i32,
+    /// Raw values up to this magnitude read as center
+    pub deadzone: u16,
+    /// Reverse the axis direction
+    pub invert: bool,
+    /// Response curve from 0 (linear) to 1 (cubic)
+    pub expo: f32,
+    /// Custom response curve, applied after expo
+    pub curve: Option<Curve>,
+    /// Share of full deflection any output off center starts from, for games with their own deadzone
+    pub anti_deadzone: f32,
+    /// Multiplier of the gain while the fine stage is toggled on, 1 leaves the axis alone
+    pub fine_gain: f32,
+    pub filter: FilterKind,
+    /// Weight of each new sample for the EMA filter, from 0 to 1
+    pub alpha: f32,
+    /// Number of samples averaged by the window filter
+    pub window: usize,
+    /// Smallest change in vJoy units passed on to the output
+    pub gate: u32,
+    /// Most vJoy units the output moves per update, 0 for no limit
+    pub slew: u32,
+    /// Least time between two changes of the output, 0 for none
+    pub interval_ms: u64,
+    pub mode: AxisMode,
+    /// Share of the range travelled per second at full deflection in relative mode
+    pub rate: f32,
+    /// Share of the distance to center recovered per second once released in relative mode, 0 holds
+    pub decay: f32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    #[default]
+    None,
+    /// Exponential moving average
+    Ema,
+    /// Moving average over a fixed number of samples
+    Window,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AxisMode {
+    /// Deflection sets the output
+    #[default]
+    Absolute,
+    /// Deflection moves the output, at a rate proportional to it
+    Relative,
+}
+
+/// Load the config file at `path`, or the defaults if there is no such file.
+pub fn load(path: &Path) -> Result<Config, ConfigError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            info!("No config file at {}, using defaults", path.display());
+            return Ok(Config::default());
+        }
+        Err(error) => return Err(ConfigError::Io(error)),
+    };
+
+    let config = toml::from_str(&text).map_err(ConfigError::Parse)?;
+
+    info!("Config loaded from {}", path.display());
+
+    Ok(config)
+}
+
+/// Write `STARTER_CONFIG` to `path`, replacing an existing file only if `force`.
+pub fn write_starter(path: &Path, force: bool) -> Result<(), ConfigError> {
+    let file = if force {
+        fs::File::create(path)
+    } else {
+        fs::File::options().write(true).create_new(true).open(path)
+    };
+
+    let mut file = match file {
+        Ok(file) => file,
+        Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+            return Err(ConfigError::Exists(path.to_path_buf()))
+        }
+        Err(error) => return Err(ConfigError::Io(error)),
+    };
+
+    file.write_all(STARTER_CONFIG.as_bytes())
+        .map_err(ConfigError::Io)?;
+
+    info!("Config written to {}", path.display());
+
+    Ok(())
+}
+
+/// Write the trim of each axis, in `Axis::ALL` order, into the config file at `path`.
+///
+/// The other settings are kept but not the comments and formatting.
+pub fn save_trims(path: &Path, trims: &[i32; 6]) -> Result<(), ConfigError> {
+    let mut root = read_table(path)?;
+
+    let axes = table_entry(&mut root, "axes");
+
+    for axis in Axis::ALL {
+        let name = axis.name().to_lowercase();
+
+        table_entry(axes, &name).insert(
+            "trim".to_owned(),
+            Value::Integer(trims[axis as usize] as i64),
+        );
+    }
+
+    let text = toml::to_string(&root).map_err(ConfigError::Write)?;
+
+    fs::write(path, text).map_err(ConfigError::Io)?;
+
+    info!("Trims saved to {}", path.display());
+
+    Ok(())
+}
+
+/// Write `axes` as the `[axes]` settings, or those of `profile`, into the config file at `path`.
+///
+/// Like `save_trims`, the other settings are kept but not the comments and formatting.
+pub fn save_axes(path: &Path, profile: Option<&str>, axes: &AxesConfig) -> Result<(), ConfigError> {
+    let mut root = read_table(path)?;
+
+    let table = match profile {
+        Some(name) => table_entry(table_entry(&mut root, "profiles"), name),
+        None => &mut root,
+    };
+
+    table.insert(
+        "axes".to_owned(),
+        Value::try_from(axes).map_err(ConfigError::Write)?,
+    );
+
+    let text = toml::to_string(&root).map_err(ConfigError::Write)?;
+
+    fs::write(path, text).map_err(ConfigError::Io)?;
+
+    info!("Axis settings saved to {}", path.display());
+
+    Ok(())
+}
+
+/// Config file at `path` as a table, empty if there is none yet.
+fn read_table(path: &Path) -> Result<Table, ConfigError> {
+    match fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map_err(ConfigError::Parse),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Table::new()),
+        Err(error) => Err(ConfigError::Io(error)),
+    }
+}
+
+/// Sub-table `key` of `table`, replacing any other value there.
+fn table_entry<'a>(table: &'a mut Table, key: &str) -> &'a mut Table {
+    let value = table
+        .entry(key.to_owned())
+        .or_insert_with(|| Value::Table(Table::new()));
+
+    if !value.is_table() {
+        *value = Value::Table(Table::new());
+    }
+
+    value.as_table_mut().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_starter_config_parses_into_the_defaults() {
+        let parsed: Config = toml::from_str(STARTER_CONFIG).unwrap();
+
+        assert_eq!(
+            toml::Value::try_from(&parsed).unwrap(),
+            toml::Value::try_from(Config::default()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::f64::consts::PI;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::axis::{Axis, MAX_DEFLECTION};
+use crate::report::{
+    ByteOrder, AXES_REPORT_LEN, REPORT_BUTTONS, REPORT_ROTATION, REPORT_TRANSLATION,
+};
+
+//Axes in the order of their values in each axes report
+const TRANSLATION_AXES: [Axis; 3] = [Axis::X, Axis::Z, Axis::Y];
+const ROTATION_AXES: [Axis; 3] = [Axis::RX, Axis::RZ, Axis::RY];
+
+//SpaceNavigator pace, one report every 8 ms
+const REPORT_INTERVAL: Duration = Duration::from_millis(8);
+
+//Reports sent in turn
+const CYCLE: [u8; 3] = [REPORT_TRANSLATION, REPORT_ROTATION, REPORT_BUTTONS];
+
+/// Shape of the synthetic motion.
+#[derive(Debug, Clone, Copy)]
+pub struct Waveform {
+    /// Sine periods per second
+    pub frequency: f64,
+    /// Share of the full deflection, from 0 to 1
+    pub amplitude: f64,
+}
+
+/// Generates SpaceNavigator reports without the device.
+///
+/// Each axis follows the same sine, shifted by a sixth of a period from the previous one.
+/// Buttons 1 and 2 are pressed in turn during the first quarter of each period.
+pub struct Demo {
+    waveform: Waveform,
+    byte_order: ByteOrder,
+    start: Instant,
+    due: Instant,
+    next: usize,
+}
+
+impl Demo {
+    /// Axis values are written in `byte_order`, to decode as the device reports would.
+    pub fn new(waveform: Waveform, byte_order: ByteOrder) -> Self {
+        let now = Instant::now();
+
+        Self {
+            waveform,
+            byte_order,
+            start: now,
+            due: now,
+            next: 0,
+        }
+    }
+
+    /// Write the next report into `buf` once it is due, like `HidDevice::read_timeout`.
+    pub fn read_timeout(&mut self, buf: &mut [u8], timeout_ms: i32) -> usize {
+        let wait = self.due.saturating_duration_since(Instant::now());
+        let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+
+        if wait > timeout {
+            thread::sleep(timeout);
+            return 0;
+        }
+
+        thread::sleep(wait);
+
+        self.due += REPORT_INTERVAL;
+
+        let report_id = CYCLE[self.next];
+        self.next = (self.next + 1) % CYCLE.len();
+
+        let cycles = (Instant::now() - self.start).as_secs_f64() * self.waveform.frequency;
+
+        buf.fill(0);
+
+        match report_id {
+            REPORT_TRANSLATION | REPORT_ROTATION => {
+                let axes = if report_id == REPORT_TRANSLATION {
+                    TRANSLATION_AXES
+                } else {
+                    ROTATION_AXES
+                };
+
+                let report = axes_report(
+                    report_id,
+                    axes.map(|axis| self.value(axis, cycles)),
+                    self.byte_order,
+                );
+
+                buf[..AXES_REPORT_LEN].copy_from_slice(&report);
+                AXES_REPORT_LEN
+            }
+            _ => {
+                if cycles.fract() < 0.25 {
+                    buf[1] = 1 << (cycles as u64 % 2);
+                }
+
+                buf[0] = report_id;
+                buf.len()
+            }
+        }
+    }
+
+    fn value(&self, axis: Axis, cycles: f64) -> i16 {
+        let phase = 2.0 * PI * (cycles + axis as usize as f64 / 6.0);
+        let amplitude = self.waveform.amplitude.clamp(0.0, 1.0) * MAX_DEFLECTION as f64;
+
+        (amplitude * phase.sin()).round() as i16
+    }
+}
+
+/// Translation and rotation reports holding each axis at its share of the full deflection,
+/// in `Axis::ALL` order, for a fixed pose.
+pub fn pose_reports(shares: [f64; 6], byte_order: ByteOrder) -> Vec<Vec<u8>> {
+    let value = |axis: Axis| {
+        (shares[axis as usize].clamp(-1.0, 1.0) * MAX_DEFLECTION as f64).round() as i16
+    };
+
+    [
+        (REPORT_TRANSLATION, TRANSLATION_AXES),
+        (REPORT_ROTATION, ROTATION_AXES),
+    ]
+    .into_iter()
+    .map(|(report_id, axes)| axes_report(report_id, axes.map(value), byte_order).to_vec())
+    .collect()
+}
+
+fn axes_report(report_id: u8, values: [i16; 3], byte_order: ByteOrder) -> [u8; AXES_REPORT_LEN] {
+    let mut report = [0u8; AXES_REPORT_LEN];
+    report[0] = report_id;
+
+    for (i, value) in values.into_iter().enumerate() {
+        report[1 + 2 * i..3 + 2 * i].copy_from_slice(&byte_order.encode(value));
+    }
+
+    report
+}
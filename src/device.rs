@@ -0,0 +1,352 @@
+use hidapi::{DeviceInfo, HidApi, HidDevice, HidError, HidResult};
+
+use log::{info, warn};
+
+use serde_json::json;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::error::FeederError;
+use crate::report::{self, REPORT_LED};
+use crate::status;
+
+//How long a read waits for a report before checking for Ctrl-C
+pub const READ_TIMEOUT_MS: i32 = 100;
+
+//Largest full speed HID report, enough for any report of an unknown model
+const DUMP_BUFFER_LEN: usize = 64;
+
+//Delay between attempts to find an unplugged device, doubling up to the max
+const RECONNECT_MIN_DELAY: Duration = Duration::from_millis(250);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(2);
+
+//3Dconnexion, maker of the SpaceNavigator and its siblings
+const VENDOR_3DCONNEXION: u16 = 1133;
+
+//3Dconnexion models found without giving a product ID
+pub const KNOWN_MODELS: [(u16, &str); 14] = [
+    (50723, "SpaceTraveler"),
+    (50725, "SpacePilot"),
+    (50726, "SpaceNavigator"),
+    (50727, "SpaceExplorer"),
+    (50728, "SpaceNavigator for Notebooks"),
+    (50729, "SpacePilot Pro"),
+    (50731, "SpaceMouse Pro"),
+    (50734, "SpaceMouse Wireless (cabled)"),
+    (50735, "SpaceMouse Wireless"),
+    (50737, "SpaceMouse Pro Wireless (cabled)"),
+    (50738, "SpaceMouse Pro Wireless"),
+    (50739, "SpaceMouse Enterprise"),
+    (50741, "SpaceMouse Compact"),
+    (50770, "3Dconnexion Universal Receiver"),
+];
+
+//How often waiting for the device looks for it
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+//Parts of the hidapi messages of an unplugged device, on Windows, Linux and macOS
+const DISCONNECT_MESSAGES: [&str; 4] = [
+    "not connected",
+    "no such device",
+    "device not configured",
+    "disconnected",
+];
+
+/// How the read loop reacts to a HID error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HidErrorKind {
+    /// The device is gone, wait for it to be plugged back in
+    Disconnected,
+    /// Worth retrying the read after a pause
+    Transient,
+    /// A bug or a broken setup, retrying cannot help
+    Fatal,
+}
+
+pub fn classify_error(error: &HidError) -> HidErrorKind {
+    match error {
+        HidError::HidApiError { message } => {
+            let message = message.to_lowercase();
+
+            if DISCONNECT_MESSAGES
+                .iter()
+                .any(|part| message.contains(part))
+            {
+                HidErrorKind::Disconnected
+            } else {
+                HidErrorKind::Transient
+            }
+        }
+        HidError::HidApiErrorEmpty | HidError::FromWideCharError { .. } => HidErrorKind::Transient,
+        _ => HidErrorKind::Fatal,
+    }
+}
+
+/// HID device model matched, one of `KNOWN_MODELS` or the one asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Model {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub name: &'static str,
+}
+
+impl Model {
+    pub fn new(vendor_id: u16, product_id: u16) -> Self {
+        Self {
+            vendor_id,
+            product_id,
+            name: model_name(vendor_id, product_id).unwrap_or("HID device"),
+        }
+    }
+}
+
+/// Name of a known 3Dconnexion model.
+pub fn model_name(vendor_id: u16, product_id: u16) -> Option<&'static str> {
+    KNOWN_MODELS
+        .iter()
+        .find(|&&(known, _)| vendor_id == VENDOR_3DCONNEXION && known == product_id)
+        .map(|&(_, name)| name)
+}
+
+/// Whether the IDs are those asked for, any known model of the vendor without `product_id`,
+/// and the serial too if given.
+fn is_match(
+    vendor_id: u16,
+    product_id: Option<u16>,
+    serial: Option<&str>,
+    info: &DeviceInfo,
+) -> bool {
+    info.vendor_id() == vendor_id
+        && match product_id {
+            Some(product_id) => info.product_id() == product_id,
+            None => model_name(info.vendor_id(), info.product_id()).is_some(),
+        }
+        && serial.is_none_or(|serial| info.serial_number() == Some(serial))
+}
+
+pub fn is_space_navigator_plugged(
+    api: &HidApi,
+    vendor_id: u16,
+    product_id: Option<u16>,
+    serial: Option<&str>,
+) -> bool {
+    api.device_list()
+        .any(|info| is_match(vendor_id, product_id, serial, info))
+}
+
+/// Open the device with the IDs, or of a known model if `product_id` is `None`.
+///
+/// Several units matching is an error unless `serial` picks one.
+pub fn find_space_navigator(
+    api: &HidApi,
+    vendor_id: u16,
+    product_id: Option<u16>,
+    serial: Option<&str>,
+) -> Result<(HidDevice, Model), FeederError> {
+    let mut candidates: Vec<&DeviceInfo> = api
+        .device_list()
+        .filter(|info| is_match(vendor_id, product_id, serial, info))
+        .collect();
+
+    //Each interface of a unit is listed, with the same serial
+    candidates.sort_by(|a, b| {
+        (a.product_id(), a.serial_number()).cmp(&(b.product_id(), b.serial_number()))
+    });
+    candidates.dedup_by(|a, b| {
+        (a.product_id(), a.serial_number()) == (b.product_id(), b.serial_number())
+    });
+
+    let device_info = match candidates.as_slice() {
+        [] => return Err(not_found(vendor_id, product_id, serial)),
+        [device_info] => device_info,
+        _ => {
+            return Err(FeederError::AmbiguousDevice(
+                candidates
+                    .iter()
+                    .map(|info| {
+                        format!(
+                            "{} serial {}",
+                            Model::new(info.vendor_id(), info.product_id()).name,
+                            info.serial_number().unwrap_or("<unknown>")
+                        )
+                    })
+                    .collect(),
+            ))
+        }
+    };
+
+    let dev = device_info.open_device(api)?;
+    let model = Model::new(device_info.vendor_id(), device_info.product_id());
+
+    info!(
+        "{} found: {} {}, serial {}",
+        model.name,
+        device_string(dev.get_manufacturer_string()),
+        device_string(dev.get_product_string()),
+        device_string(dev.get_serial_number_string()),
+    );
+
+    status::emit(
+        "device_found",
+        json!({
+            "model": model.name,
+            "vendor_id": model.vendor_id,
+            "product_id": model.product_id,
+            "serial": dev.get_serial_number_string().ok().flatten(),
+        }),
+    );
+
+    Ok((dev, model))
+}
+
+fn not_found(vendor_id: u16, product_id: Option<u16>, serial: Option<&str>) -> FeederError {
+    match (product_id, serial) {
+        (_, Some(serial)) => FeederError::SerialNotFound(serial.to_string()),
+        (Some(product_id), None) => FeederError::DeviceNotFound {
+            vendor_id,
+            product_id,
+        },
+        (None, None) => FeederError::NoKnownDevice { vendor_id },
+    }
+}
+
+/// A string read from the device, `<unknown>` if it has none or could not be read.
+fn device_string(string: HidResult<Option<String>>) -> String {
+    match string {
+        Ok(Some(string)) if !string.is_empty() => string,
+        _ => "<unknown>".to_string(),
+    }
+}
+
+/// Poll for the device with backoff until it is found or `running` is cleared.
+pub fn reconnect_space_navigator(
+    api: &mut HidApi,
+    vendor_id: u16,
+    product_id: u16,
+    serial: Option<&str>,
+    running: &AtomicBool,
+) -> Option<HidDevice> {
+    let product_id = Some(product_id);
+
+    let mut delay = RECONNECT_MIN_DELAY;
+
+    while running.load(Ordering::Relaxed) {
+        thread::sleep(delay);
+
+        match api.refresh_devices() {
+            Ok(_) if is_space_navigator_plugged(api, vendor_id, product_id, serial) => {
+                if let Ok((device, _)) = find_space_navigator(api, vendor_id, product_id, serial) {
+                    return Some(device);
+                }
+            }
+            Ok(_) => {}
+            Err(error) => warn!("Could not refresh HID devices: {}", error),
+        }
+
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+    }
+
+    None
+}
+
+/// Poll until the device is plugged in, or the optional timeout expires.
+pub fn wait_for_space_navigator(
+    api: &mut HidApi,
+    vendor_id: u16,
+    product_id: Option<u16>,
+    serial: Option<&str>,
+    timeout: Option<Duration>,
+) -> Result<(HidDevice, Model), FeederError> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+    match product_id {
+        Some(product_id) => info!(
+            "Waiting for SpaceNavigator (vendor {:#06x}, product {:#06x})...",
+            vendor_id, product_id
+        ),
+        None => info!("Waiting for a 3Dconnexion device..."),
+    }
+
+    loop {
+        api.refresh_devices()?;
+
+        if is_space_navigator_plugged(api, vendor_id, product_id, serial) {
+            return find_space_navigator(api, vendor_id, product_id, serial);
+        }
+
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            info!("Timed out waiting for SpaceNavigator");
+            return Err(not_found(vendor_id, product_id, serial));
+        }
+
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+}
+
+/// Print the IDs and strings of every HID device, marking 3Dconnexion ones.
+pub fn list_devices(api: &HidApi) {
+    for info in api.device_list() {
+        println!(
+            "{} vendor {:#06x} ({}), product {:#06x} ({}): {} {}, serial {}",
+            if info.vendor_id() == VENDOR_3DCONNEXION {
+                "*"
+            } else {
+                " "
+            },
+            info.vendor_id(),
+            info.vendor_id(),
+            info.product_id(),
+            info.product_id(),
+            info.manufacturer_string().unwrap_or("?"),
+            info.product_string().unwrap_or("?"),
+            info.serial_number().unwrap_or("?"),
+        );
+    }
+
+    println!("* 3Dconnexion device");
+}
+
+/// Print each report read from `device` as hex until `running` is cleared.
+pub fn dump_reports(device: &HidDevice, running: &AtomicBool) -> HidResult<()> {
+    let mut buffer = [0u8; DUMP_BUFFER_LEN];
+    let mut last = Instant::now();
+
+    while running.load(Ordering::Relaxed) {
+        let size = device.read_timeout(&mut buffer, READ_TIMEOUT_MS)?;
+
+        if size == 0 {
+            continue;
+        }
+
+        let now = Instant::now();
+
+        println!(
+            "+{:>9.3} ms {:<11} {}",
+            (now - last).as_secs_f64() * 1000.0,
+            report::report_name(buffer[0]),
+            report::hex(&buffer[..size])
+        );
+
+        last = now;
+    }
+
+    Ok(())
+}
+
+pub fn set_led(device: &HidDevice, on: bool) -> HidResult<()> {
+    device.write(&[REPORT_LED, on as u8])?;
+
+    Ok(())
+}
+
+pub fn update_led(device: &HidDevice, on: bool) {
+    if let Err(error) = set_led(device, on) {
+        warn!(
+            "Could not turn SpaceNavigator LED {}: {}",
+            if on { "on" } else { "off" },
+            error
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use hidapi::HidError;
+
+use std::io;
+use std::time::Duration;
+
+use thiserror::Error;
+
+use crate::axis::Field;
+use crate::config::ConfigError;
+
+/// Failure of one of the feeder startup stages, or of the HID device while feeding.
+#[derive(Debug, Error)]
+pub enum FeederError {
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error("{0}")]
+    InvalidSettings(String),
+    #[error("vJoy driver is not installed or disabled")]
+    VJoyDisabled,
+    #[error("vJoy is not installed, {0} could not be loaded, get vJoy from https://github.com/njz3/vJoy/releases")]
+    VJoyDllMissing(&'static str),
+    #[error("vJoy driver version {driver:#06x} does not match DLL version {dll:#06x}")]
+    VersionMismatch { dll: u16, driver: u16 },
+    #[error("vJoy device {0} is already owned by another feeder")]
+    VJoyBusy(u32),
+    #[error("vJoy device {0} is not installed or disabled")]
+    VJoyMissing(u32),
+    #[error("vJoy device {0} general error")]
+    VJoyUnknown(u32),
+    #[error("no free vJoy device has the mapped axes and enough buttons")]
+    NoFreeVJoy,
+    #[error("vJoy device {id} lacks the mapped axes {axes:?}")]
+    CapabilityMismatch { id: u32, axes: Vec<Field> },
+    #[error("could not acquire vJoy device number {id} in {attempts} attempts")]
+    AcquireFailed { id: u32, attempts: u32 },
+    #[error("cannot create the uinput joystick, is /dev/uinput writable? {0}")]
+    Uinput(io::Error),
+    #[error("cannot initialize hidapi: {0}")]
+    HidInit(HidError),
+    #[error("could not find SpaceNavigator (vendor {vendor_id:#06x}, product {product_id:#06x})")]
+    DeviceNotFound { vendor_id: u16, product_id: u16 },
+    #[error("could not find any known 3Dconnexion device (vendor {vendor_id:#06x}), give its product ID")]
+    NoKnownDevice { vendor_id: u16 },
+    #[error("could not find SpaceNavigator with serial {0}")]
+    SerialNotFound(String),
+    #[error("several devices match, pick one with --serial: {}", .0.join(", "))]
+    AmbiguousDevice(Vec<String>),
+    #[error("SpaceNavigator error: {0}")]
+    Hid(#[from] HidError),
+    #[error("SpaceNavigator read failed {count} times in a row, last with: {error}")]
+    ReadFailed { count: u32, error: HidError },
+    #[error("SpaceNavigator sent no report for {} ms", .0.as_millis())]
+    Stalled(Duration),
+    #[error("cannot write recording: {0}")]
+    Record(io::Error),
+    #[error("cannot replay recording: {0}")]
+    Replay(io::Error),
+    #[error("cannot open the OSC output: {0}")]
+    Osc(io::Error),
+    #[error("cannot open the WebSocket server: {0}")]
+    WebSocket(io::Error),
+    #[error("cannot open the metrics endpoint: {0}")]
+    Metrics(io::Error),
+    #[error("cannot open the keyboard output: {0}")]
+    Keyboard(io::Error),
+}
+
+impl FeederError {
+    /// Process exit code, distinct for each stage.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            FeederError::Config(_) | FeederError::InvalidSettings(_) => 2,
+            FeederError::VJoyDisabled => 3,
+            FeederError::VersionMismatch { .. } => 4,
+            FeederError::VJoyBusy(_)
+            | FeederError::VJoyMissing(_)
+            | FeederError::VJoyUnknown(_)
+            | FeederError::NoFreeVJoy => 5,
+            FeederError::CapabilityMismatch { .. } => 6,
+            FeederError::AcquireFailed { .. } | FeederError::Uinput(_) => 7,
+            FeederError::HidInit(_) => 8,
+            FeederError::DeviceNotFound { .. }
+            | FeederError::NoKnownDevice { .. }
+            | FeederError::SerialNotFound(_)
+            | FeederError::AmbiguousDevice(_) => 9,
+            FeederError::Hid(_) | FeederError::ReadFailed { .. } | FeederError::Stalled(_) => 10,
+            FeederError::Record(_) => 11,
+            FeederError::Replay(_) => 12,
+            FeederError::Osc(_) => 13,
+            FeederError::WebSocket(_) => 14,
+            FeederError::Metrics(_) => 15,
+            FeederError::Keyboard(_) => 16,
+            FeederError::VJoyDllMissing(_) => 17,
+        }
+    }
+}
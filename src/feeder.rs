@@ -0,0 +1,2546 @@
+use hidapi::HidApi;
+
+use log::{debug, info, trace, warn};
+
+use serde_json::json;
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::axis::{self, Axis, AxisChannel, AxisRange, Field};
+use crate::axis_button::{self, AxisButton};
+use crate::buttons;
+use crate::calibrate::{self, AxisCalibration, Calibrations};
+use crate::chord::{self, Chord};
+use crate::config::{
+    AxesConfig, AxisButtonConfig, AxisConfig, ButtonsConfig, ChordConfig, FilterKind,
+    GestureConfig, HatConfig, KeyConfig, OscConfig, PressAction, PressConfig, PressFunction,
+    ProfileConfig, SplitButtons, SplitConfig, VJoyConfig,
+};
+use crate::demo::{self, Demo, Waveform};
+use crate::device::{self, Model};
+use crate::error::FeederError;
+use crate::gesture::{self, Gesture};
+use crate::hat::{self, Hat};
+use crate::joystick::PositionSink;
+use crate::keys::{self, KeyOutput};
+use crate::led::Led;
+use crate::metrics::Metrics;
+use crate::osc::OscOutput;
+use crate::press::{self, PressButton};
+use crate::recenter::IdleRecenter;
+use crate::record::{self, Player, Recorder};
+use crate::reload::ConfigWatcher;
+use crate::repl::Tuning;
+use crate::report::{self, ByteOrder, Report};
+use crate::source::{DeviceSource, InputSource, MockSource};
+use crate::status;
+use crate::vjoy::{self, VJoyOutput};
+use crate::watchdog::Watchdog;
+use crate::{PRODUCT_ID, VENDOR_ID};
+
+//How often relative axes move on while the device sends nothing
+const RELATIVE_TICK_MS: i32 = 10;
+
+//Both the translation and the rotation of a frame were read
+const FULL_FRAME: u8 = 0b11;
+
+//How often a held frame checks for Ctrl-C
+const HOLD_POLL: Duration = Duration::from_millis(100);
+
+/// Name of the profile made of the `[axes]` settings.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Latest feed state, shared with front-ends such as the dashboard.
+#[derive(Debug, Default, Clone)]
+pub struct Snapshot {
+    /// Last raw device value of each axis, in `Axis::ALL` order
+    pub raw: [i16; 6],
+    /// Value fed to vJoy for each axis
+    pub axes: [i32; 6],
+    pub ranges: [AxisRange; 6],
+    /// Buttons fed to vJoy, after latching
+    pub buttons: u64,
+    pub button_count: u32,
+    /// Whether the device is being read, false while reconnecting
+    pub connected: bool,
+}
+
+/// Trim of each axis in vJoy units, shared with front-ends to adjust it while feeding.
+#[derive(Debug, Default)]
+pub struct Trims {
+    values: [AtomicI32; 6],
+}
+
+impl Trims {
+    pub fn new(values: [i32; 6]) -> Self {
+        Self {
+            values: values.map(AtomicI32::new),
+        }
+    }
+
+    pub fn get(&self, axis: Axis) -> i32 {
+        self.values[axis as usize].load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, axis: Axis, trim: i32) {
+        self.values[axis as usize].store(trim, Ordering::Relaxed);
+    }
+
+    pub fn nudge(&self, axis: Axis, delta: i32) {
+        self.set(axis, self.get(axis).saturating_add(delta));
+    }
+
+    /// Trims in `Axis::ALL` order.
+    pub fn values(&self) -> [i32; 6] {
+        Axis::ALL.map(|axis| self.get(axis))
+    }
+}
+
+/// Profile in use, shared with front-ends to switch it while feeding.
+#[derive(Debug)]
+pub struct ProfileSwitch {
+    names: Vec<String>,
+    active: AtomicUsize,
+}
+
+impl ProfileSwitch {
+    /// `names` in switching order, starting with `DEFAULT_PROFILE`.
+    pub fn new(names: Vec<String>, active: usize) -> Self {
+        Self {
+            names,
+            active: AtomicUsize::new(active),
+        }
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Index of the profile in use in `names`.
+    pub fn active(&self) -> usize {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    /// Name of the profile in use.
+    pub fn name(&self) -> &str {
+        &self.names[self.active()]
+    }
+
+    pub fn select(&self, index: usize) {
+        self.active
+            .store(index % self.names.len(), Ordering::Relaxed);
+    }
+
+    /// Switch to the following profile, after the last one back to the first.
+    pub fn next(&self) {
+        self.select(self.active() + 1);
+    }
+}
+
+/// Recording fed instead of the HID device.
+#[derive(Debug, Clone)]
+pub struct Replay {
+    pub path: PathBuf,
+    /// Multiplier of the recorded pace
+    pub speed: f64,
+    /// Start over at the end instead of stopping
+    pub looping: bool,
+}
+
+/// Output index and field fed by each axis, the axis config overriding the split.
+fn targets(axes: &AxesConfig, split: Option<&SplitConfig>) -> [(usize, Field); 6] {
+    Axis::ALL.map(|axis| {
+        let (output, field) = match split.and_then(|split| split.target(axis)) {
+            Some(target) => (1, target),
+            None => (0, Field::from(axis)),
+        };
+
+        (output, axes.get(axis).field.unwrap_or(field))
+    })
+}
+
+/// What a `FeederBuilder` collects and a `Feeder` runs with.
+#[derive(Debug, Clone)]
+struct Settings {
+    vjoy_ids: Vec<u32>,
+    acquire_attempts: u32,
+    vendor_id: u16,
+    /// Any known model if unset
+    product_id: Option<u16>,
+    /// Unit picked among several matching
+    serial: Option<String>,
+    byte_order: ByteOrder,
+    axes: AxesConfig,
+    buttons: ButtonsConfig,
+    wait: bool,
+    blocking: bool,
+    wait_timeout: Option<Duration>,
+    rate: Option<f64>,
+    coalesce: Option<Duration>,
+    auto_zero: Option<Duration>,
+    /// Idle time and band of the auto-recenter
+    recenter: Option<(Duration, u16)>,
+    watchdog: Option<Duration>,
+    record: Option<PathBuf>,
+    replay: Option<Replay>,
+    demo: Option<Waveform>,
+    /// Share of the full deflection each axis is held at instead of reading the HID device
+    pose: Option<[f64; 6]>,
+    /// How long the first full frame is held before stopping
+    once: Option<Duration>,
+    split: Option<SplitConfig>,
+    hat: Option<HatConfig>,
+    osc: Option<OscConfig>,
+    gestures: Vec<GestureConfig>,
+    keys: Vec<KeyConfig>,
+    presses: Vec<PressConfig>,
+    chords: Vec<ChordConfig>,
+    axis_buttons: Vec<AxisButtonConfig>,
+    calibrations: Calibrations,
+    snapshot: Option<Arc<Mutex<Snapshot>>>,
+    metrics: Option<Arc<Metrics>>,
+    console: bool,
+    /// Config file whose axis settings are reloaded when it changes
+    watch_config: Option<PathBuf>,
+    tuning: Option<Arc<Tuning>>,
+    /// Where the positions go instead of the vJoy devices
+    sink: Option<PositionSink>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            vjoy_ids: VJoyConfig::default().ids,
+            acquire_attempts: VJoyConfig::default().acquire_attempts,
+            vendor_id: VENDOR_ID,
+            product_id: None,
+            serial: None,
+            byte_order: ByteOrder::default(),
+            axes: AxesConfig::default(),
+            buttons: ButtonsConfig::default(),
+            wait: false,
+            blocking: true,
+            wait_timeout: None,
+            rate: None,
+            coalesce: None,
+            auto_zero: None,
+            recenter: None,
+            watchdog: None,
+            record: None,
+            replay: None,
+            demo: None,
+            pose: None,
+            once: None,
+            split: None,
+            hat: None,
+            osc: None,
+            gestures: Vec::new(),
+            keys: Vec::new(),
+            presses: Vec::new(),
+            chords: Vec::new(),
+            axis_buttons: Vec::new(),
+            calibrations: Calibrations::default(),
+            snapshot: None,
+            metrics: None,
+            console: true,
+            watch_config: None,
+            tuning: None,
+            sink: None,
+        }
+    }
+}
+
+/// Reads a HID device and feeds its axes and buttons to a vJoy device.
+#[derive(Debug)]
+pub struct Feeder {
+    settings: Settings,
+    trims: Arc<Trims>,
+    /// Profiles after the default one, in `profile` order
+    profiles: Vec<ProfileConfig>,
+    profile: Arc<ProfileSwitch>,
+}
+
+/// Configures a `Feeder`, unset values are the same as the app defaults.
+#[derive(Debug, Clone, Default)]
+pub struct FeederBuilder {
+    settings: Settings,
+    profiles: BTreeMap<String, ProfileConfig>,
+    profile: Option<String>,
+}
+
+impl FeederBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn vjoy_id(mut self, id: u32) -> Self {
+        self.settings.vjoy_ids = vec![id];
+        self
+    }
+
+    /// Feed the same state to each of these vJoy devices.
+    pub fn vjoy_ids(mut self, ids: impl IntoIterator<Item = u32>) -> Self {
+        self.settings.vjoy_ids = ids.into_iter().collect();
+        self
+    }
+
+    /// Try acquiring each vJoy device up to `attempts` times, in case it is busy.
+    pub fn acquire_attempts(mut self, attempts: u32) -> Self {
+        self.settings.acquire_attempts = attempts;
+        self
+    }
+
+    /// USB vendor and product ID of the HID device, any known 3Dconnexion model without the latter.
+    pub fn device(mut self, vendor_id: u16, product_id: Option<u16>) -> Self {
+        self.settings.vendor_id = vendor_id;
+        self.settings.product_id = product_id;
+        self
+    }
+
+    /// Serial number of the unit to read, needed when several match the IDs.
+    pub fn serial(mut self, serial: impl Into<String>) -> Self {
+        self.settings.serial = Some(serial.into());
+        self
+    }
+
+    /// Byte order of the device axis values, little endian by default.
+    pub fn byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.settings.byte_order = byte_order;
+        self
+    }
+
+    /// Mapping, filter and deadzone of every axis at once.
+    pub fn axes(mut self, axes: AxesConfig) -> Self {
+        self.settings.axes = axes;
+        self
+    }
+
+    pub fn axis(mut self, axis: Axis, config: AxisConfig) -> Self {
+        *self.settings.axes.get_mut(axis) = config;
+        self
+    }
+
+    /// Raw gain and center of `axis`, instead of values derived from the vJoy range.
+    pub fn mapping(mut self, axis: Axis, gain: f32, center: i32) -> Self {
+        let config = self.settings.axes.get_mut(axis);
+        config.gain = Some(gain);
+        config.center = Some(center);
+        self
+    }
+
+    pub fn deadzone(mut self, axis: Axis, deadzone: u16) -> Self {
+        self.settings.axes.get_mut(axis).deadzone = deadzone;
+        self
+    }
+
+    pub fn filter(mut self, axis: Axis, filter: FilterKind) -> Self {
+        self.settings.axes.get_mut(axis).filter = filter;
+        self
+    }
+
+    pub fn buttons(mut self, buttons: ButtonsConfig) -> Self {
+        self.settings.buttons = buttons;
+        self
+    }
+
+    /// Wait for the HID device to be plugged in instead of failing.
+    pub fn wait(mut self, wait: bool) -> Self {
+        self.settings.wait = wait;
+        self
+    }
+
+    /// Wait for each HID report, the default, or poll the device instead.
+    pub fn blocking(mut self, blocking: bool) -> Self {
+        self.settings.blocking = blocking;
+        self
+    }
+
+    /// Give up waiting for the HID device after `timeout`.
+    pub fn wait_timeout(mut self, timeout: Duration) -> Self {
+        self.settings.wait_timeout = Some(timeout);
+        self
+    }
+
+    /// Send at most `rate` vJoy updates per second.
+    pub fn rate(mut self, rate: f64) -> Self {
+        self.settings.rate = Some(rate);
+        self
+    }
+
+    /// Hold a vJoy update for up to `timeout` after a translation or rotation report,
+    /// until the other one arrives, so each update carries all six axes of the same frame.
+    pub fn coalesce(mut self, timeout: Duration) -> Self {
+        self.settings.coalesce = Some(timeout).filter(|timeout| !timeout.is_zero());
+        self
+    }
+
+    /// Average the rest position over `window` at startup and subtract it from raw values.
+    pub fn auto_zero(mut self, window: Duration) -> Self {
+        self.settings.auto_zero = Some(window).filter(|window| !window.is_zero());
+        self
+    }
+
+    /// Zero the axes again each time every raw value stayed within `band` of rest for `idle`,
+    /// against a slow drift while the puck is untouched.
+    pub fn recenter(mut self, idle: Duration, band: u16) -> Self {
+        self.settings.recenter = Some((idle, band)).filter(|(idle, _)| !idle.is_zero());
+        self
+    }
+
+    /// Reopen the HID device when it sends no report for `timeout`, in case it wedged.
+    pub fn watchdog(mut self, timeout: Duration) -> Self {
+        self.settings.watchdog = Some(timeout).filter(|timeout| !timeout.is_zero());
+        self
+    }
+
+    /// Also write every raw report to a recording at `path`.
+    pub fn record(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings.record = Some(path.into());
+        self
+    }
+
+    /// Feed a recording instead of the HID device.
+    pub fn replay(mut self, replay: Replay) -> Self {
+        self.settings.replay = Some(replay);
+        self
+    }
+
+    /// Feed the rotation axes to a second vJoy device instead.
+    pub fn split(mut self, split: SplitConfig) -> Self {
+        self.settings.split = Some(split);
+        self
+    }
+
+    /// Point the vJoy hat with a pair of axes.
+    pub fn hat(mut self, hat: HatConfig) -> Self {
+        self.settings.hat = Some(hat);
+        self
+    }
+
+    /// Send keyboard keys instead of feeding vJoy some buttons.
+    pub fn keys(mut self, keys: Vec<KeyConfig>) -> Self {
+        self.settings.keys = keys;
+        self
+    }
+
+    /// Tell taps, long presses and double presses of some buttons apart, each with its own action.
+    pub fn presses(mut self, presses: Vec<PressConfig>) -> Self {
+        self.settings.presses = presses;
+        self
+    }
+
+    /// Feed buttons pressed together as a vJoy button or action of their own.
+    pub fn chords(mut self, chords: Vec<ChordConfig>) -> Self {
+        self.settings.chords = chords;
+        self
+    }
+
+    /// Press vJoy buttons or switch profiles when axes are held past thresholds.
+    pub fn gestures(mut self, gestures: Vec<GestureConfig>) -> Self {
+        self.settings.gestures = gestures;
+        self
+    }
+
+    /// Hold vJoy buttons while the converted outputs of axes are past thresholds.
+    pub fn axis_buttons(mut self, axis_buttons: Vec<AxisButtonConfig>) -> Self {
+        self.settings.axis_buttons = axis_buttons;
+        self
+    }
+
+    /// Also send the fed state as OSC messages.
+    pub fn osc(mut self, osc: OscConfig) -> Self {
+        self.settings.osc = Some(osc);
+        self
+    }
+
+    /// Feed synthetic motion instead of the HID device.
+    pub fn demo(mut self, waveform: Waveform) -> Self {
+        self.settings.demo = Some(waveform);
+        self
+    }
+
+    /// Hold each axis at its share of the full deflection, in `Axis::ALL` order, instead of
+    /// reading the HID device. The axis settings still apply.
+    pub fn pose(mut self, shares: [f64; 6]) -> Self {
+        self.settings.pose = Some(shares);
+        self
+    }
+
+    /// Stop once the first full frame, translation and rotation, was fed, holding it for `hold` first.
+    pub fn once(mut self, hold: Duration) -> Self {
+        self.settings.once = Some(hold);
+        self
+    }
+
+    /// Stored calibrations, the one of the unit found is used for unset gains and centers.
+    pub fn calibrations(mut self, calibrations: Calibrations) -> Self {
+        self.settings.calibrations = calibrations;
+        self
+    }
+
+    /// Apply the axis settings of the config file at `path`, and of its profiles, each time it is saved.
+    pub fn watch_config(mut self, path: impl Into<PathBuf>) -> Self {
+        self.settings.watch_config = Some(path.into());
+        self
+    }
+
+    /// Keep `snapshot` up to date with every report.
+    pub fn snapshot(mut self, snapshot: Arc<Mutex<Snapshot>>) -> Self {
+        self.settings.snapshot = Some(snapshot);
+        self
+    }
+
+    /// Send the positions to `sink` instead of acquiring the vJoy devices, to check them in tests.
+    ///
+    /// The axes then have the default vJoy range, like uinput joysticks.
+    pub fn sink(mut self, sink: PositionSink) -> Self {
+        self.settings.sink = Some(sink);
+        self
+    }
+
+    /// Apply the axis settings edited in `tuning`, e.g. at the `--repl` console.
+    pub fn tuning(mut self, tuning: Arc<Tuning>) -> Self {
+        self.settings.tuning = Some(tuning);
+        self
+    }
+
+    /// Count the reports, updates and errors in `metrics`, and keep the fed axis values there.
+    pub fn metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.settings.metrics = Some(metrics);
+        self
+    }
+
+    /// Print the feed status, unless a dashboard owns the terminal.
+    pub fn console(mut self, console: bool) -> Self {
+        self.settings.console = console;
+        self
+    }
+
+    /// Axis settings to switch to at runtime, in name order after the default one.
+    pub fn profiles(mut self, profiles: BTreeMap<String, ProfileConfig>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Start with the profile called `name` instead of the default one.
+    pub fn profile(mut self, name: impl Into<String>) -> Self {
+        self.profile = Some(name.into());
+        self
+    }
+
+    pub fn build(self) -> Result<Feeder, FeederError> {
+        let settings = &self.settings;
+
+        if settings.vjoy_ids.is_empty() {
+            return Err(FeederError::InvalidSettings(
+                "at least one vJoy device is needed".to_string(),
+            ));
+        }
+
+        for (index, &id) in settings.vjoy_ids.iter().enumerate() {
+            vjoy::check_vjoy_id(id).map_err(FeederError::InvalidSettings)?;
+
+            if settings.vjoy_ids[..index].contains(&id) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "vJoy device {} is listed twice",
+                    id
+                )));
+            }
+        }
+
+        if let Some(replay) = &settings.replay {
+            if !(replay.speed.is_finite() && replay.speed >= record::MIN_SPEED) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "replay speed must be a number from {}, got {}",
+                    record::MIN_SPEED,
+                    replay.speed
+                )));
+            }
+        }
+
+        let swapped: Vec<Axis> = self
+            .settings
+            .axes
+            .swap
+            .iter()
+            .flat_map(|&(a, b)| [a, b])
+            .collect();
+
+        for (index, axis) in swapped.iter().enumerate() {
+            if swapped[..index].contains(axis) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "axis {} appears more than once in the swaps",
+                    axis.name()
+                )));
+            }
+        }
+
+        let targets = targets(&settings.axes, settings.split.as_ref());
+        let enabled: Vec<Axis> = Axis::ALL
+            .into_iter()
+            .filter(|&axis| settings.axes.get(axis).enabled)
+            .collect();
+
+        for (index, &axis) in enabled.iter().enumerate() {
+            let target = targets[axis as usize];
+
+            if let Some(other) = enabled[..index]
+                .iter()
+                .find(|&&other| targets[other as usize] == target)
+            {
+                return Err(FeederError::InvalidSettings(format!(
+                    "axes {} and {} both feed {}",
+                    other.name(),
+                    axis.name(),
+                    target.1.name()
+                )));
+            }
+        }
+
+        if let Some(split) = &settings.split {
+            vjoy::check_vjoy_id(split.vjoy_id).map_err(FeederError::InvalidSettings)?;
+
+            if settings.vjoy_ids.contains(&split.vjoy_id) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "vJoy device {} cannot be fed both halves of a split",
+                    split.vjoy_id
+                )));
+            }
+        }
+
+        if let Some(hat) = &settings.hat {
+            if !(hat.threshold > 0.0 && hat.threshold <= 1.0) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "hat threshold must be above 0 and at most 1, got {}",
+                    hat.threshold
+                )));
+            }
+
+            if !(hat.hysteresis >= 0.0 && hat.hysteresis < hat.threshold.min(0.5)) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "hat hysteresis must be at least 0 and below both the threshold and 0.5, got {}",
+                    hat.hysteresis
+                )));
+            }
+        }
+
+        if let Some(osc) = &settings.osc {
+            if !(osc.rate > 0.0 && Duration::try_from_secs_f64(1.0 / osc.rate).is_ok()) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "OSC rate must be a positive number with a representable interval, got {}",
+                    osc.rate
+                )));
+            }
+
+            for address in [&osc.axis_address, &osc.button_address] {
+                if !address.starts_with('/') {
+                    return Err(FeederError::InvalidSettings(format!(
+                        "OSC address {} must start with /",
+                        address
+                    )));
+                }
+            }
+        }
+
+        if let Some(shares) = &settings.pose {
+            if settings.demo.is_some() || settings.replay.is_some() {
+                return Err(FeederError::InvalidSettings(
+                    "a pose cannot be combined with the demo or a replay".to_string(),
+                ));
+            }
+
+            if settings.once.is_none() {
+                return Err(FeederError::InvalidSettings(
+                    "a pose is only fed once".to_string(),
+                ));
+            }
+
+            if let Some(share) = shares.iter().find(|share| !(-1.0..=1.0).contains(*share)) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "pose values must be between -1 and 1, got {}",
+                    share
+                )));
+            }
+        }
+
+        if let Some(waveform) = &settings.demo {
+            if settings.replay.is_some() {
+                return Err(FeederError::InvalidSettings(
+                    "demo and replay cannot be combined".to_string(),
+                ));
+            }
+
+            if !(waveform.frequency.is_finite() && waveform.frequency > 0.0) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "demo frequency must be a positive number, got {}",
+                    waveform.frequency
+                )));
+            }
+
+            if !(0.0..=1.0).contains(&waveform.amplitude) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "demo amplitude must be between 0 and 1, got {}",
+                    waveform.amplitude
+                )));
+            }
+        }
+
+        if let Some(rate) = settings.rate {
+            if !(rate > 0.0 && Duration::try_from_secs_f64(1.0 / rate).is_ok()) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "update rate must be a positive number with a representable interval, got {}",
+                    rate
+                )));
+            }
+        }
+
+        for press in &settings.presses {
+            let actions = [press.tap, press.long, press.double];
+            let buttons = actions.iter().filter_map(|action| match action {
+                Some(PressAction::Button(button)) => Some(*button),
+                _ => None,
+            });
+
+            for button in [press.button].into_iter().chain(buttons) {
+                if !(1..=report::MAX_BUTTONS).contains(&button) {
+                    return Err(FeederError::InvalidSettings(format!(
+                        "press button must be between 1 and {}, got {}",
+                        report::MAX_BUTTONS,
+                        button
+                    )));
+                }
+            }
+        }
+
+        for axis_button in &settings.axis_buttons {
+            if !(axis_button.threshold > 0.0 && axis_button.threshold <= 1.0) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "axis button threshold must be above 0 and at most 1, got {}",
+                    axis_button.threshold
+                )));
+            }
+
+            if !(axis_button.hysteresis >= 0.0 && axis_button.hysteresis < axis_button.threshold) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "axis button hysteresis must be at least 0 and below the threshold, got {}",
+                    axis_button.hysteresis
+                )));
+            }
+
+            if !(1..=report::MAX_BUTTONS).contains(&axis_button.button) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "axis button must be between 1 and {}, got {}",
+                    report::MAX_BUTTONS,
+                    axis_button.button
+                )));
+            }
+        }
+
+        for chord in &settings.chords {
+            //A button listed twice is a single one
+            if buttons::button_mask(&chord.buttons).count_ones() < 2 {
+                return Err(FeederError::InvalidSettings(format!(
+                    "a chord needs at least 2 distinct buttons, got {:?}",
+                    chord.buttons
+                )));
+            }
+
+            let button = match chord.action {
+                Some(PressAction::Button(button)) => Some(button),
+                Some(PressAction::Function(_)) => None,
+                None => {
+                    return Err(FeederError::InvalidSettings(format!(
+                        "chord {:?} has no action",
+                        chord.buttons
+                    )))
+                }
+            };
+
+            for &button in chord.buttons.iter().chain(button.as_ref()) {
+                if !(1..=report::MAX_BUTTONS).contains(&button) {
+                    return Err(FeederError::InvalidSettings(format!(
+                        "chord button must be between 1 and {}, got {}",
+                        report::MAX_BUTTONS,
+                        button
+                    )));
+                }
+            }
+        }
+
+        //Clashes are checked once the device, and so its buttons, is known
+        buttons::ButtonRemap::new(&settings.buttons.remap, 0, 0, true)
+            .map_err(FeederError::InvalidSettings)?;
+
+        for gesture in &settings.gestures {
+            if !(gesture.threshold.abs() > 0.0 && gesture.threshold.abs() <= 1.0) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "gesture threshold must be between -1 and 1 and not 0, got {}",
+                    gesture.threshold
+                )));
+            }
+
+            if !(gesture.hysteresis >= 0.0 && gesture.hysteresis < gesture.threshold.abs()) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "gesture hysteresis must be at least 0 and below the threshold, got {}",
+                    gesture.hysteresis
+                )));
+            }
+
+            if let Some(button) = gesture.button {
+                if !(1..=report::MAX_BUTTONS).contains(&button) {
+                    return Err(FeederError::InvalidSettings(format!(
+                        "gesture button must be between 1 and {}, got {}",
+                        report::MAX_BUTTONS,
+                        button
+                    )));
+                }
+            }
+        }
+
+        for key in &settings.keys {
+            if keys::lookup(&key.key).is_none() {
+                return Err(FeederError::InvalidSettings(format!(
+                    "unknown key {}, known keys are {}",
+                    key.key,
+                    keys::KEYS.map(|key| key.name).join(", ")
+                )));
+            }
+
+            if !(1..=report::MAX_BUTTONS).contains(&key.button) {
+                return Err(FeederError::InvalidSettings(format!(
+                    "key button must be between 1 and {}, got {}",
+                    report::MAX_BUTTONS,
+                    key.button
+                )));
+            }
+        }
+
+        if self.profiles.contains_key(DEFAULT_PROFILE) {
+            return Err(FeederError::InvalidSettings(format!(
+                "profile name {} is taken by the [axes] settings",
+                DEFAULT_PROFILE
+            )));
+        }
+
+        let names: Vec<String> = std::iter::once(DEFAULT_PROFILE.to_string())
+            .chain(self.profiles.keys().cloned())
+            .collect();
+
+        let active = match &self.profile {
+            Some(name) => names
+                .iter()
+                .position(|other| other == name)
+                .ok_or_else(|| {
+                    FeederError::InvalidSettings(format!("no profile called {}", name))
+                })?,
+            None => 0,
+        };
+
+        let trims = Trims::new(Axis::ALL.map(|axis| settings.axes.get(axis).trim));
+
+        Ok(Feeder {
+            settings: self.settings,
+            trims: Arc::new(trims),
+            profiles: self.profiles.into_values().collect(),
+            profile: Arc::new(ProfileSwitch::new(names, active)),
+        })
+    }
+}
+
+impl Feeder {
+    pub fn builder() -> FeederBuilder {
+        FeederBuilder::new()
+    }
+
+    /// vJoy devices fed, the first one sets the axis ranges.
+    pub fn vjoy_ids(&self) -> &[u32] {
+        &self.settings.vjoy_ids
+    }
+
+    /// Tries at acquiring each vJoy device.
+    pub fn acquire_attempts(&self) -> u32 {
+        self.settings.acquire_attempts
+    }
+
+    /// Axes fed to vJoy, the vJoy device must have all of them.
+    pub fn mapped_axes(&self) -> Vec<Axis> {
+        Axis::ALL
+            .into_iter()
+            .filter(|&axis| self.settings.axes.get(axis).enabled)
+            .collect()
+    }
+
+    /// Each vJoy device fed with the axes it must have and the buttons it gets.
+    pub fn vjoy_requirements(&self) -> Vec<(u32, Vec<Field>, u32)> {
+        let targets = self.targets();
+        let button_outputs = self.button_outputs();
+
+        self.outputs()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, ids)| {
+                let axes: Vec<Field> = self
+                    .mapped_axes()
+                    .into_iter()
+                    .filter(|&axis| targets[axis as usize].0 == index)
+                    .map(|axis| targets[axis as usize].1)
+                    .collect();
+
+                let buttons = if button_outputs.contains(&index) {
+                    self.button_count()
+                        .max(gesture::button_count(&self.settings.gestures))
+                        .max(buttons::remap_count(&self.settings.buttons.remap))
+                        .max(press::button_count(&self.settings.presses))
+                        .max(chord::button_count(&self.settings.chords))
+                        .max(axis_button::button_count(&self.settings.axis_buttons))
+                } else {
+                    0
+                };
+
+                ids.into_iter().map(move |id| (id, axes.clone(), buttons))
+            })
+            .collect()
+    }
+
+    /// vJoy devices fed the same position, the first ones and those fed the rotation when split.
+    fn outputs(&self) -> Vec<Vec<u32>> {
+        let mut outputs = vec![self.settings.vjoy_ids.clone()];
+
+        if let Some(split) = &self.settings.split {
+            outputs.push(vec![split.vjoy_id]);
+        }
+
+        outputs
+    }
+
+    fn targets(&self) -> [(usize, Field); 6] {
+        targets(&self.settings.axes, self.settings.split.as_ref())
+    }
+
+    /// Range of `field` on vJoy device `id`, or the default one when feeding a sink.
+    fn axis_range(&self, id: u32, field: Field) -> AxisRange {
+        match self.settings.sink {
+            Some(_) => AxisRange::default(),
+            None => vjoy::get_vjoy_axis_range(id, field),
+        }
+    }
+
+    /// Outputs fed the buttons.
+    fn button_outputs(&self) -> Vec<usize> {
+        match self.settings.split.as_ref().map(|split| split.buttons) {
+            None | Some(SplitButtons::First) => vec![0],
+            Some(SplitButtons::Second) => vec![1],
+            Some(SplitButtons::Both) => vec![0, 1],
+        }
+    }
+
+    /// Trims applied while feeding, starting from the configured ones.
+    pub fn trims(&self) -> &Arc<Trims> {
+        &self.trims
+    }
+
+    /// Profile in use, switched by the profile button or front-ends.
+    pub fn profile(&self) -> &Arc<ProfileSwitch> {
+        &self.profile
+    }
+
+    /// Axis settings of profile `index`, the conversion part only.
+    pub fn profile_axes(&self, index: usize) -> &AxesConfig {
+        match index {
+            0 => &self.settings.axes,
+            _ => &self.profiles[index - 1].axes,
+        }
+    }
+
+    /// Whether switching to profile `index` starts its filters afresh.
+    fn resets_filters(&self, index: usize) -> bool {
+        index > 0 && self.profiles[index - 1].reset_filters
+    }
+
+    /// Buttons decoded from the HID device, the most of the models it may be until it is found.
+    pub fn button_count(&self) -> u32 {
+        match self.settings.product_id {
+            Some(product_id) => report::button_count(product_id, &self.settings.buttons),
+            None => device::KNOWN_MODELS
+                .iter()
+                .map(|&(product_id, _)| report::button_count(product_id, &self.settings.buttons))
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Model assumed without a HID device, for the demo and replays.
+    fn default_model(&self) -> Model {
+        Model::new(
+            self.settings.vendor_id,
+            self.settings.product_id.unwrap_or(PRODUCT_ID),
+        )
+    }
+
+    /// Feed vJoy until `running` is cleared.
+    ///
+    /// The vJoy device must have passed the `vjoy::check_vjoy_*` checks.
+    pub fn run(&self, running: Arc<AtomicBool>) -> Result<(), FeederError> {
+        if let Some(waveform) = self.settings.demo {
+            info!("Feeding synthetic motion");
+
+            return self.feed(
+                &mut Demo::new(waveform, self.settings.byte_order),
+                self.default_model(),
+                None,
+                None,
+                None,
+                &running,
+            );
+        }
+
+        if let Some(shares) = self.settings.pose {
+            info!("Feeding a fixed pose");
+
+            return self.feed(
+                &mut MockSource::new(demo::pose_reports(shares, self.settings.byte_order)),
+                self.default_model(),
+                None,
+                None,
+                None,
+                &running,
+            );
+        }
+
+        if let Some(replay) = &self.settings.replay {
+            let mut player = Player::open(&replay.path, replay.speed, replay.looping)
+                .map_err(FeederError::Replay)?;
+
+            info!("Replaying {}", replay.path.display());
+
+            return self.feed(
+                &mut player,
+                self.default_model(),
+                None,
+                None,
+                None,
+                &running,
+            );
+        }
+
+        let mut api = HidApi::new().map_err(FeederError::HidInit)?;
+
+        let (device, model) = if self.settings.wait {
+            device::wait_for_space_navigator(
+                &mut api,
+                self.settings.vendor_id,
+                self.settings.product_id,
+                self.settings.serial.as_deref(),
+                self.settings.wait_timeout,
+            )?
+        } else {
+            device::find_space_navigator(
+                &api,
+                self.settings.vendor_id,
+                self.settings.product_id,
+                self.settings.serial.as_deref(),
+            )?
+        };
+
+        let serial = calibrate::serial_of(&device);
+        let calibration =
+            self.settings
+                .calibrations
+                .get(&serial, model.vendor_id, model.product_id);
+
+        match calibration {
+            Some(_) => info!("Using the calibration of SpaceNavigator {}", serial),
+            None => debug!("No calibration for SpaceNavigator {:?}", serial),
+        }
+
+        device.set_blocking_mode(self.settings.blocking)?;
+
+        debug!("SpaceNavigator blocking mode = {}", self.settings.blocking);
+
+        let mut source = DeviceSource::new(
+            api,
+            device,
+            model.vendor_id,
+            model.product_id,
+            //The same unit comes back, not another one plugged meanwhile
+            (!serial.is_empty()).then(|| serial.clone()),
+            self.settings.blocking,
+        );
+
+        self.feed(
+            &mut source,
+            model,
+            calibration,
+            self.settings.auto_zero,
+            self.settings.watchdog,
+            &running,
+        )
+    }
+
+    /// Feed the reports of `source`, e.g. a `MockSource`, until it runs dry or `running` is cleared.
+    ///
+    /// No stored calibration applies, the auto-zero and watchdog do.
+    pub fn run_with(
+        &self,
+        source: &mut dyn InputSource,
+        running: &AtomicBool,
+    ) -> Result<(), FeederError> {
+        self.feed(
+            source,
+            self.default_model(),
+            None,
+            self.settings.auto_zero,
+            self.settings.watchdog,
+            running,
+        )
+    }
+
+    fn feed(
+        &self,
+        source: &mut dyn InputSource,
+        model: Model,
+        calibration: Option<&[AxisCalibration; 6]>,
+        auto_zero: Option<Duration>,
+        watchdog: Option<Duration>,
+        running: &AtomicBool,
+    ) -> Result<(), FeederError> {
+        let settings = &self.settings;
+        let mut outputs = Outputs::new(self);
+        let mut axes = AxisPipeline::new(self, &mut outputs, calibration);
+
+        let parser = report::parser_for(
+            model.vendor_id,
+            model.product_id,
+            &settings.buttons,
+            settings.byte_order,
+        );
+        let button_count = report::button_count(model.product_id, &settings.buttons)
+            .max(gesture::button_count(&settings.gestures))
+            .max(buttons::remap_count(&settings.buttons.remap))
+            .max(press::button_count(&settings.presses))
+            .max(chord::button_count(&settings.chords))
+            .max(axis_button::button_count(&settings.axis_buttons));
+
+        if let Some(window) = auto_zero {
+            info!(
+                "Hold the puck still, zeroing over {} ms...",
+                window.as_millis()
+            );
+
+            match calibrate::sample_rest(source, parser.as_ref(), window, running)? {
+                Some(rest) => axes.zero(settings, rest, calibration),
+                None => return Ok(()),
+            }
+        }
+
+        let mut recorder = match &settings.record {
+            Some(path) => {
+                let recorder = Recorder::create(path).map_err(FeederError::Record)?;
+                info!("Recording reports to {}", path.display());
+                Some(recorder)
+            }
+            None => None,
+        };
+
+        let mut osc = match &settings.osc {
+            Some(config) => {
+                let osc = OscOutput::new(config, button_count).map_err(FeederError::Osc)?;
+                info!("Sending OSC messages to {}", config.host);
+                Some(osc)
+            }
+            None => None,
+        };
+
+        let mut buttons = ButtonPipeline::new(settings, &model)?;
+
+        outputs.acquire(self, button_count)?;
+        source.set_led(true);
+
+        let mut read_buffer = vec![0u8; parser.report_len()];
+        let watchdog = watchdog.map(|timeout| Watchdog::start(timeout, source.stall_signal()));
+        let mut led = Led::new(true);
+
+        //Times each report ID no parser handles was read
+        let mut unknown_reports: BTreeMap<u8, u64> = BTreeMap::new();
+
+        let mut conversions = Conversions::new(self);
+
+        if conversions.profile > 0 {
+            info!("Profile {}", self.profile.name());
+        }
+
+        if let Some(snapshot) = &settings.snapshot {
+            let mut snapshot = snapshot.lock().unwrap();
+            snapshot.ranges = axes
+                .channels
+                .each_ref()
+                .map(|channel| channel.mapping.range);
+            snapshot.button_count = button_count;
+            snapshot.connected = true;
+        }
+
+        if settings.console && !status::is_json() {
+            println!("Program status nominal\nCtrl-c to exit");
+        }
+
+        status::emit(
+            "feeding",
+            json!({
+                "model": model.name,
+                "vjoy_ids": settings.vjoy_ids,
+                "profile": self.profile.name(),
+            }),
+        );
+
+        while running.load(Ordering::Relaxed) {
+            if let Some(on) = led.update(Instant::now()) {
+                source.set_led(on);
+            }
+
+            if let Some((conversion, reset)) = conversions.update(self) {
+                axes.switch(settings, conversion, reset, calibration, outputs.fed());
+            }
+
+            let timeout = outputs.timeout(axes.is_settled());
+
+            //Wake up in time for a gesture or press held while the device is silent
+            let timeout = match buttons.deadline() {
+                Some(deadline) => timeout.min(
+                    deadline
+                        .saturating_duration_since(Instant::now())
+                        .as_millis() as i32,
+                ),
+                None => timeout,
+            };
+
+            let mut read = source.read(&mut read_buffer[..], timeout);
+
+            if let Some(watchdog) = &watchdog {
+                if matches!(read, Ok(Some(size)) if size > 0) {
+                    watchdog.feed();
+                } else if watchdog.take_tripped() {
+                    read = Err(FeederError::Stalled(watchdog.timeout()));
+                }
+            }
+
+            let size = match read {
+                Ok(Some(size)) => size,
+                Ok(None) => {
+                    info!("No more reports to feed");
+
+                    //Held back by the rate cap, the rest of the frame would never go out
+                    if outputs.pending {
+                        outputs.send(settings);
+                    }
+
+                    if let Some(hold) = settings.once.filter(|_| outputs.seen_full_frame()) {
+                        hold_frame(hold, running);
+                    }
+
+                    break;
+                }
+                Err(error) => {
+                    if !self.reconnect(
+                        source,
+                        error,
+                        running,
+                        &mut outputs,
+                        &mut axes,
+                        &mut buttons,
+                    )? {
+                        return Ok(());
+                    }
+
+                    if let Some(watchdog) = &watchdog {
+                        watchdog.feed();
+                    }
+
+                    led = Led::new(true);
+
+                    continue;
+                }
+            };
+
+            //Timed out, nothing new but a capped update may still be due
+            if size > 0 {
+                let data = &read_buffer[..size];
+
+                if let Some(metrics) = &settings.metrics {
+                    metrics.reports.fetch_add(1, Ordering::Relaxed);
+                }
+
+                if let Some(writer) = &mut recorder {
+                    if let Err(error) = writer.record(data) {
+                        warn!("Recording stopped: {}", error);
+                        recorder = None;
+                    }
+                }
+
+                let mut report = Report::default();
+                parser.parse(data, &mut report);
+
+                trace!("Report {:?}", report);
+                log_ignored(&report, data, &mut unknown_reports);
+
+                outputs.read_halves(&report, settings.coalesce);
+                axes.sync_trims(&self.trims);
+                axes.convert(settings, &report, &mut outputs);
+                buttons.read_axes(settings, &report, &axes);
+
+                if let Some(bits) = report.buttons {
+                    axes.press(settings, &self.trims, bits, buttons.last, &outputs);
+
+                    if buttons.press(bits, &self.profile) != 0 {
+                        led.blink(Instant::now());
+                    }
+                }
+
+                if let Some(snapshot) = &settings.snapshot {
+                    let mut snapshot = snapshot.lock().unwrap();
+
+                    for axis in Axis::ALL {
+                        if let Some(raw) = report.axes[settings.axes.source(axis) as usize] {
+                            snapshot.raw[axis as usize] = raw;
+                        }
+                    }
+
+                    snapshot.axes = outputs.fed();
+                    snapshot.buttons = outputs.buttons();
+                }
+
+                outputs.pending = true;
+            }
+
+            let now = Instant::now();
+
+            if let Some(bits) = buttons.update(&axes.zeroed, now, &self.profile) {
+                outputs.write_buttons(bits);
+
+                if let Some(snapshot) = &settings.snapshot {
+                    snapshot.lock().unwrap().buttons = bits;
+                }
+            }
+
+            axes.recenter(settings, &mut outputs, now);
+
+            //Relative, slew limited and held axes keep moving while the device is silent
+            if size == 0 {
+                axes.tick(settings, &mut outputs);
+            }
+
+            if let Some(osc) = &mut osc {
+                osc.update(axes.shares(settings, &outputs), outputs.buttons(), now);
+            }
+
+            if let Some(failed) = outputs.send_due(settings, now) {
+                led.set_error(failed, now);
+
+                if let Some(hold) = settings.once.filter(|_| outputs.seen_full_frame()) {
+                    hold_frame(hold, running);
+                    break;
+                }
+            }
+        }
+
+        debug!(
+            "{} vJoy updates sent, {} skipped as unchanged",
+            outputs.sent, outputs.skipped
+        );
+
+        if !unknown_reports.is_empty() {
+            let counts: Vec<String> = unknown_reports
+                .iter()
+                .map(|(id, count)| format!("{:#04x} x{}", id, count))
+                .collect();
+
+            debug!("Unknown reports ignored: {}", counts.join(", "));
+        }
+
+        source.set_led(false);
+        outputs.relinquish();
+
+        Ok(())
+    }
+
+    /// Center the outputs while `source` recovers from `error`, then start every stage afresh.
+    ///
+    /// Returns whether feeding goes on, the outputs are relinquished otherwise.
+    fn reconnect(
+        &self,
+        source: &mut dyn InputSource,
+        error: FeederError,
+        running: &AtomicBool,
+        outputs: &mut Outputs,
+        axes: &mut AxisPipeline,
+        buttons: &mut ButtonPipeline,
+    ) -> Result<bool, FeederError> {
+        let settings = &self.settings;
+
+        outputs.center();
+
+        if let Some(snapshot) = &settings.snapshot {
+            snapshot.lock().unwrap().connected = false;
+        }
+
+        status::emit("device_lost", json!({ "error": error.to_string() }));
+
+        buttons.release_keys();
+
+        if let Some(metrics) = &settings.metrics {
+            metrics.errors.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let recovered = source.recover(error, running);
+
+        if !matches!(recovered, Ok(true)) {
+            outputs.relinquish();
+            return recovered;
+        }
+
+        if let Some(metrics) = &settings.metrics {
+            metrics.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+
+        axes.reset();
+        buttons.reset();
+
+        if let Some(snapshot) = &settings.snapshot {
+            snapshot.lock().unwrap().connected = true;
+        }
+
+        status::emit("device_reconnected", json!({}));
+
+        source.set_led(true);
+        outputs.restart();
+
+        Ok(true)
+    }
+}
+
+/// Log the reports, or the part of them, no parser could read.
+///
+/// Only the first of each unknown ID is shown, a device may send them all the time.
+fn log_ignored(report: &Report, data: &[u8], unknown_reports: &mut BTreeMap<u8, u64>) {
+    //A partial read or a malformed device
+    if let Some(id) = report.truncated {
+        debug!(
+            "Truncated report {:#04x} of {} bytes ignored: {}",
+            id,
+            data.len(),
+            report::hex(data)
+        );
+    }
+
+    if let Some(id) = report.unknown {
+        let count = unknown_reports.entry(id).or_insert(0);
+
+        if *count == 0 {
+            debug!("Unknown report {:#04x} ignored: {}", id, report::hex(data));
+        }
+
+        *count += 1;
+    }
+}
+
+/// Conversion settings of each profile, as reloaded from the config file or tuned at the console.
+struct Conversions {
+    /// Profile in use
+    profile: usize,
+    axes: Vec<AxesConfig>,
+    watcher: Option<ConfigWatcher>,
+}
+
+impl Conversions {
+    fn new(feeder: &Feeder) -> Self {
+        //Taken from the config file again when it changes
+        let axes: Vec<AxesConfig> = (0..feeder.profile.names().len())
+            .map(|index| feeder.profile_axes(index).clone())
+            .collect();
+
+        //The console edits what is in use here
+        if let Some(tuning) = &feeder.settings.tuning {
+            tuning.publish(&axes);
+        }
+
+        let watcher = feeder.settings.watch_config.clone().map(|path| {
+            info!(
+                "Reloading the axis settings when {} changes",
+                path.display()
+            );
+            ConfigWatcher::start(path)
+        });
+
+        Self {
+            profile: feeder.profile.active(),
+            axes,
+            watcher,
+        }
+    }
+
+    /// Settings to rebuild the axes with, and whether their filters start afresh, once another
+    /// profile was picked or the one in use was reloaded or tuned.
+    fn update(&mut self, feeder: &Feeder) -> Option<(&AxesConfig, bool)> {
+        let wanted = feeder.profile.active();
+        let reloaded = self.watcher.as_ref().and_then(ConfigWatcher::take);
+        let reload = reloaded.is_some();
+        let mut tuned = false;
+
+        if let Some(mut config) = reloaded {
+            self.axes[0] = config.axes;
+
+            for (conversion, name) in self.axes[1..].iter_mut().zip(&feeder.profile.names()[1..]) {
+                match config.profiles.remove(name) {
+                    Some(reloaded) => *conversion = reloaded.axes,
+                    None => warn!("Profile {} left the config file, it stays as it was", name),
+                }
+            }
+
+            if let Some(tuning) = &feeder.settings.tuning {
+                tuning.publish(&self.axes);
+            }
+        }
+
+        if let Some((index, tuned_conversions)) = feeder
+            .settings
+            .tuning
+            .as_ref()
+            .and_then(|tuning| tuning.take())
+        {
+            self.axes = tuned_conversions;
+            tuned = index == wanted;
+        }
+
+        if wanted == self.profile && !reload && !tuned {
+            return None;
+        }
+
+        let reset = wanted != self.profile && feeder.resets_filters(wanted);
+        self.profile = wanted;
+
+        if reload {
+            info!("Axis settings reloaded, profile {}", feeder.profile.name());
+        } else if tuned {
+            debug!("Axis settings tuned, profile {}", feeder.profile.name());
+        } else {
+            info!("Profile {}", feeder.profile.name());
+        }
+
+        status::emit(
+            "profile",
+            json!({ "name": feeder.profile.name(), "reloaded": reload }),
+        );
+
+        Some((&self.axes[self.profile], reset))
+    }
+}
+
+/// vJoy devices fed and the frames sent to them, at most at the update rate.
+struct Outputs {
+    /// vJoy devices fed the same position, see `Feeder::outputs`
+    devices: Vec<VJoyOutput>,
+    /// Device and field fed by each axis
+    targets: [(usize, Field); 6],
+    /// Devices fed the buttons and the hat
+    button_outputs: Vec<usize>,
+    interval: Option<Duration>,
+    next_update: Instant,
+    /// Whether the position changed since the last frame sent
+    pending: bool,
+    /// Halves of the frame read since the last update, translation in bit 0 and rotation in bit 1
+    frame_halves: u8,
+    /// Halves read since the start, for `once`
+    seen_halves: u8,
+    frame_deadline: Instant,
+    sent: u64,
+    skipped: u64,
+}
+
+impl Outputs {
+    fn new(feeder: &Feeder) -> Self {
+        Self {
+            devices: feeder.outputs().into_iter().map(VJoyOutput::new).collect(),
+            targets: feeder.targets(),
+            button_outputs: feeder.button_outputs(),
+            interval: feeder
+                .settings
+                .rate
+                .map(|rate| Duration::from_secs_f64(1.0 / rate)),
+            next_update: Instant::now(),
+            pending: false,
+            frame_halves: 0,
+            seen_halves: 0,
+            frame_deadline: Instant::now(),
+            sent: 0,
+            skipped: 0,
+        }
+    }
+
+    /// Acquire each device, or attach it to the sink, with the buttons and hat it is fed.
+    fn acquire(&mut self, feeder: &Feeder, button_count: u32) -> Result<(), FeederError> {
+        for index in 0..self.devices.len() {
+            let fed_buttons = self.button_outputs.contains(&index);
+            let buttons = if fed_buttons { button_count } else { 0 };
+            let hat = feeder.settings.hat.is_some() && fed_buttons;
+
+            if let Some(sink) = &feeder.settings.sink {
+                self.devices[index].attach(sink);
+                continue;
+            }
+
+            if let Err(error) =
+                self.devices[index].acquire(feeder.settings.acquire_attempts, buttons, hat)
+            {
+                for device in self.devices[..index].iter_mut() {
+                    device.relinquish();
+                }
+
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Value fed to `axis`.
+    fn read(&self, axis: Axis) -> i32 {
+        let (output, target) = self.targets[axis as usize];
+        axis::read_field(&self.devices[output].position, target)
+    }
+
+    /// Values fed to every axis, in `Axis::ALL` order.
+    fn fed(&self) -> [i32; 6] {
+        Axis::ALL.map(|axis| self.read(axis))
+    }
+
+    fn write(&mut self, axis: Axis, value: i32) {
+        let (output, target) = self.targets[axis as usize];
+        axis::write_field(&mut self.devices[output].position, target, value);
+        self.pending = true;
+    }
+
+    /// Buttons fed.
+    fn buttons(&self) -> u64 {
+        buttons::read_buttons(&self.devices[self.button_outputs[0]].position)
+    }
+
+    fn write_buttons(&mut self, bits: u64) {
+        for &output in &self.button_outputs {
+            buttons::write_buttons(&mut self.devices[output].position, bits);
+        }
+
+        self.pending = true;
+    }
+
+    fn write_hat(&mut self, value: u32) {
+        for &output in &self.button_outputs {
+            hat::write_hat(&mut self.devices[output].position, value);
+        }
+
+        self.pending = true;
+    }
+
+    /// Note the halves of the frame in `report`, the first one of a frame holds it for `coalesce`.
+    fn read_halves(&mut self, report: &Report, coalesce: Option<Duration>) {
+        let halves = report.axes[..3].iter().any(Option::is_some) as u8
+            | (report.axes[3..].iter().any(Option::is_some) as u8) << 1;
+
+        self.seen_halves |= halves;
+
+        if let Some(timeout) = coalesce {
+            if halves != 0 && self.frame_halves == 0 {
+                self.frame_deadline = Instant::now() + timeout;
+            }
+
+            self.frame_halves |= halves;
+        }
+    }
+
+    /// Whether both the translation and the rotation were read since the start.
+    fn seen_full_frame(&self) -> bool {
+        self.seen_halves == FULL_FRAME
+    }
+
+    /// Longest wait for a report, in ms, until a pending frame is due or `settled` axes move on.
+    fn timeout(&self, settled: bool) -> i32 {
+        if self.pending {
+            update_due(self.next_update, self.frame_halves, self.frame_deadline)
+                .saturating_duration_since(Instant::now())
+                .as_millis()
+                .min(device::READ_TIMEOUT_MS as u128) as i32
+        } else if settled {
+            device::READ_TIMEOUT_MS
+        } else {
+            RELATIVE_TICK_MS
+        }
+    }
+
+    /// Send the pending frame once the rate cap and coalescing allow it.
+    ///
+    /// Returns whether an update failed, if a frame was sent.
+    fn send_due(&mut self, settings: &Settings, now: Instant) -> Option<bool> {
+        //With a rate cap, reports are coalesced until the next frame
+        if !self.pending
+            || now < update_due(self.next_update, self.frame_halves, self.frame_deadline)
+        {
+            return None;
+        }
+
+        self.pending = false;
+        self.frame_halves = 0;
+
+        if let Some(interval) = self.interval {
+            self.next_update = now + interval;
+        }
+
+        Some(self.send(settings))
+    }
+
+    /// Send every device, counting the updates, then publish the fed axes.
+    ///
+    /// The metrics and the snapshot are left as vJoy was fed. Returns whether an update failed.
+    fn send(&mut self, settings: &Settings) -> bool {
+        let mut failed = false;
+        let (sent_before, skipped_before) = (self.sent, self.skipped);
+
+        //Translation and rotation come in separate reports, either may leave the state unchanged
+        for device in self.devices.iter_mut() {
+            match device.send() {
+                Some(updated) => {
+                    failed |= !updated;
+                    self.sent += 1;
+                }
+                None => self.skipped += 1,
+            }
+        }
+
+        let fed = self.fed();
+
+        if let Some(metrics) = &settings.metrics {
+            metrics
+                .updates_sent
+                .fetch_add(self.sent - sent_before, Ordering::Relaxed);
+            metrics
+                .updates_skipped
+                .fetch_add(self.skipped - skipped_before, Ordering::Relaxed);
+
+            for (gauge, value) in metrics.axes.iter().zip(fed) {
+                gauge.store(value, Ordering::Relaxed);
+            }
+        }
+
+        if let Some(snapshot) = &settings.snapshot {
+            let mut snapshot = snapshot.lock().unwrap();
+            snapshot.axes = fed;
+            snapshot.buttons = self.buttons();
+        }
+
+        failed
+    }
+
+    fn center(&mut self) {
+        for device in self.devices.iter_mut() {
+            device.center();
+        }
+    }
+
+    /// Send the whole position again after a reconnection, dropping the frame in progress.
+    fn restart(&mut self) {
+        for device in self.devices.iter_mut() {
+            device.resend();
+        }
+
+        self.pending = false;
+        self.frame_halves = 0;
+    }
+
+    fn relinquish(&mut self) {
+        for device in self.devices.iter_mut() {
+            device.relinquish();
+        }
+    }
+}
+
+/// Raw device axes converted into vJoy values, and the buttons acting on the conversion.
+struct AxisPipeline {
+    channels: [AxisChannel; 6],
+    /// Last raw value of each axis less its zero offset
+    zeroed: [i32; 6],
+    /// Deflection reading as rest in `zeroed`, a calibrated center stays off the zero offset
+    rest: [i32; 6],
+    recenter: Option<IdleRecenter>,
+    hat: Option<Hat>,
+    frozen: bool,
+    fine: bool,
+    trim_button: u64,
+    trim_reset: u64,
+    precision: u64,
+    boost: u64,
+    freeze: u64,
+    fine_button: u64,
+}
+
+impl AxisPipeline {
+    /// Channels of the profile in use scaled to the ranges of `outputs`, whose centers they set.
+    fn new(
+        feeder: &Feeder,
+        outputs: &mut Outputs,
+        calibration: Option<&[AxisCalibration; 6]>,
+    ) -> Self {
+        let settings = &feeder.settings;
+        let profile_axes = feeder.profile_axes(feeder.profile.active());
+
+        let channels = Axis::ALL.map(|axis| {
+            let (output, target) = outputs.targets[axis as usize];
+
+            AxisChannel::new(
+                profile_axes.get(axis),
+                feeder.axis_range(outputs.devices[output].ids[0], target),
+                calibration.map(|axes| &axes[settings.axes.source(axis) as usize]),
+            )
+        });
+
+        for (axis, AxisChannel { mapping, .. }) in Axis::ALL.iter().zip(&channels) {
+            let (output, target) = outputs.targets[*axis as usize];
+
+            debug!(
+                "Axis {}: vJoy device {} axis {}, range {}..={}, gain {}, center {}",
+                axis.name(),
+                outputs.devices[output].ids[0],
+                target.name(),
+                mapping.range.min,
+                mapping.range.max,
+                mapping.gain,
+                mapping.center
+            );
+
+            //Rest would then be fed as the nearest end of the range
+            if profile_axes.get(*axis).enabled
+                && !(mapping.range.min..=mapping.range.max).contains(&mapping.center)
+            {
+                warn!(
+                    "Axis {} center {} is outside the vJoy range {}..={}",
+                    axis.name(),
+                    mapping.center,
+                    mapping.range.min,
+                    mapping.range.max
+                );
+            }
+
+            outputs.devices[output]
+                .centers
+                .push((target, mapping.center));
+        }
+
+        for (index, output) in outputs.devices.iter().enumerate() {
+            for &other in &output.ids[1..] {
+                for (channel, &(_, target)) in channels
+                    .iter()
+                    .zip(&outputs.targets)
+                    .filter(|(_, (output, _))| *output == index)
+                {
+                    if feeder.axis_range(other, target) != channel.mapping.range {
+                        warn!(
+                            "vJoy device {} axis {} range differs from device {}, scaled for the latter",
+                            other,
+                            target.name(),
+                            output.ids[0]
+                        );
+                    }
+                }
+            }
+        }
+
+        let rest = Axis::ALL.map(|axis| {
+            calibration.map_or(0, |axes| {
+                axes[settings.axes.source(axis) as usize].center as i32
+            })
+        });
+
+        Self {
+            channels,
+            zeroed: [0; 6],
+            rest,
+            recenter: settings
+                .recenter
+                .map(|(idle, band)| IdleRecenter::new(band, idle)),
+            hat: settings.hat.as_ref().map(Hat::new),
+            frozen: false,
+            fine: false,
+            trim_button: buttons::button_mask(settings.buttons.trim.as_slice()),
+            trim_reset: buttons::button_mask(settings.buttons.trim_reset.as_slice()),
+            precision: buttons::button_mask(settings.buttons.precision.as_slice()),
+            boost: buttons::button_mask(settings.buttons.boost.as_slice()),
+            freeze: buttons::button_mask(settings.buttons.freeze.as_slice()),
+            fine_button: buttons::button_mask(settings.buttons.fine.as_slice()),
+        }
+    }
+
+    /// Subtract the raw `rest` sampled at startup from every axis.
+    fn zero(
+        &mut self,
+        settings: &Settings,
+        rest: [i16; 6],
+        calibration: Option<&[AxisCalibration; 6]>,
+    ) {
+        for (axis, channel) in Axis::ALL.iter().zip(self.channels.iter_mut()) {
+            let source = settings.axes.source(*axis) as usize;
+
+            //A calibrated center already accounts for the calibrated rest
+            let calibrated = calibration.map_or(0, |axes| axes[source].center);
+
+            channel.mapping.offset = rest[source].saturating_sub(calibrated);
+
+            debug!(
+                "Axis {}: zero offset {}",
+                axis.name(),
+                channel.mapping.offset
+            );
+        }
+    }
+
+    /// Rebuild the channels with the conversion settings `axes`.
+    ///
+    /// Zero offsets, trims, precision mode and the fine stage carry over, and so does the output unless `reset`.
+    fn switch(
+        &mut self,
+        settings: &Settings,
+        axes: &AxesConfig,
+        reset: bool,
+        calibration: Option<&[AxisCalibration; 6]>,
+        current: [i32; 6],
+    ) {
+        for axis in Axis::ALL {
+            let old = &self.channels[axis as usize].mapping;
+
+            let mut channel = AxisChannel::new(
+                axes.get(axis),
+                old.range,
+                calibration.map(|axes| &axes[settings.axes.source(axis) as usize]),
+            );
+
+            channel.mapping.offset = old.offset;
+            channel.mapping.set_trim(old.trim);
+            channel.mapping.sensitivity = old.sensitivity;
+            channel.mapping.extra_deadzone = old.extra_deadzone;
+            channel.mapping.fine = old.fine;
+
+            if !reset {
+                channel.resume(current[axis as usize]);
+            }
+
+            self.channels[axis as usize] = channel;
+        }
+    }
+
+    /// Take up the trims set by front-ends.
+    fn sync_trims(&mut self, trims: &Trims) {
+        for (axis, channel) in Axis::ALL.iter().zip(self.channels.iter_mut()) {
+            let trim = trims.get(*axis);
+
+            //Front-ends may ask for more than the range allows
+            if trim != channel.mapping.trim && channel.mapping.set_trim(trim) != trim {
+                trims.set(*axis, channel.mapping.trim);
+            }
+        }
+    }
+
+    /// Convert the axes in `report` onto `outputs`, and point the hat with them.
+    fn convert(&mut self, settings: &Settings, report: &Report, outputs: &mut Outputs) {
+        for axis in Axis::ALL {
+            //Nor are the filters fed, so they don't drift meanwhile
+            if !settings.axes.get(axis).enabled || self.frozen {
+                continue;
+            }
+
+            if let Some(raw) = report.axes[settings.axes.source(axis) as usize] {
+                outputs.write(axis, self.channels[axis as usize].convert(raw));
+            }
+        }
+
+        for axis in Axis::ALL {
+            if let Some(raw) = report.axes[settings.axes.source(axis) as usize] {
+                self.zeroed[axis as usize] =
+                    raw as i32 - self.channels[axis as usize].mapping.offset as i32;
+            }
+        }
+
+        if let (Some(hat), Some(config)) = (&mut self.hat, &settings.hat) {
+            let value = hat.apply(
+                self.zeroed[config.x as usize],
+                self.zeroed[config.y as usize],
+            );
+
+            outputs.write_hat(value);
+        }
+    }
+
+    /// Share of the range from the center reached by `raw` on `axis`, from -1 to 1.
+    fn deflection(&self, axis: Axis, raw: i16) -> f64 {
+        let mapping = &self.channels[axis as usize].mapping;
+        mapping.deflection(mapping.apply(raw))
+    }
+
+    /// Apply the trim, freeze, fine stage, precision and boost buttons among `bits`.
+    fn press(
+        &mut self,
+        settings: &Settings,
+        trims: &Trims,
+        bits: u64,
+        last: u64,
+        outputs: &Outputs,
+    ) {
+        let pressed = bits & !last;
+
+        if pressed & self.trim_button != 0 {
+            for (axis, channel) in Axis::ALL.iter().zip(self.channels.iter_mut()) {
+                //The current deflection becomes the new rest
+                let trim = channel
+                    .mapping
+                    .set_trim(outputs.read(*axis) - channel.mapping.center);
+                trims.set(*axis, trim);
+            }
+
+            info!("Trimmed to {:?}", trims.values());
+        }
+
+        if pressed & self.trim_reset != 0 {
+            for (axis, channel) in Axis::ALL.iter().zip(self.channels.iter_mut()) {
+                channel.mapping.set_trim(0);
+                trims.set(*axis, 0);
+            }
+
+            info!("Trims cleared");
+        }
+
+        if (bits & self.freeze != 0) != self.frozen {
+            self.frozen = !self.frozen;
+
+            //Carry on from where the axes were held
+            if !self.frozen {
+                for (axis, channel) in Axis::ALL.iter().zip(self.channels.iter_mut()) {
+                    channel.resume(outputs.read(*axis));
+                }
+            }
+
+            debug!("Axes frozen = {}", self.frozen);
+        }
+
+        if pressed & self.fine_button != 0 {
+            self.fine = !self.fine;
+
+            for channel in self.channels.iter_mut() {
+                channel.mapping.fine = self.fine;
+            }
+
+            info!("{} stage", if self.fine { "Fine" } else { "Coarse" });
+        }
+
+        let precise = bits & self.precision != 0;
+        let boosted = bits & self.boost != 0;
+
+        if precise != (last & self.precision != 0) || boosted != (last & self.boost != 0) {
+            //Precision wins over boost, holding both is the safer of the two
+            let sensitivity = if precise {
+                settings.buttons.precision_gain.max(0.0)
+            } else if boosted {
+                settings.buttons.boost_gain.max(0.0)
+            } else {
+                1.0
+            };
+
+            for channel in self.channels.iter_mut() {
+                channel.mapping.sensitivity = sensitivity;
+                channel.mapping.extra_deadzone = if precise {
+                    settings.buttons.precision_deadzone as i32
+                } else {
+                    0
+                };
+            }
+
+            debug!("Precision mode = {}, boost = {}", precise, boosted);
+        }
+    }
+
+    /// Zero the axes again once they sat idle near rest, writing the recentered values.
+    fn recenter(&mut self, settings: &Settings, outputs: &mut Outputs, now: Instant) {
+        let deflections =
+            Axis::ALL.map(|axis| self.zeroed[axis as usize] - self.rest[axis as usize]);
+
+        let drift = match self
+            .recenter
+            .as_mut()
+            .filter(|_| !self.frozen)
+            .and_then(|recenter| recenter.update(deflections, now))
+            .filter(|drift| drift.iter().any(|&drift| drift != 0))
+        {
+            Some(drift) => drift,
+            None => return,
+        };
+
+        for axis in Axis::ALL {
+            let index = axis as usize;
+            let channel = &mut self.channels[index];
+
+            channel.mapping.offset = channel.mapping.offset.saturating_add(drift[index] as i16);
+            self.zeroed[index] -= drift[index];
+
+            if !settings.axes.get(axis).enabled {
+                continue;
+            }
+
+            //Back to the exact center without waiting for the next report
+            let raw = self.zeroed[index] + channel.mapping.offset as i32;
+            let value = channel.convert(raw.clamp(i16::MIN as i32, i16::MAX as i32) as i16);
+
+            outputs.write(axis, value);
+
+            if let Some(snapshot) = &settings.snapshot {
+                snapshot.lock().unwrap().axes[index] = value;
+            }
+        }
+
+        info!("Idle, recentered by {:?}", drift);
+        outputs.pending = true;
+    }
+
+    /// Move on the relative, slew limited and held axes without a report.
+    fn tick(&mut self, settings: &Settings, outputs: &mut Outputs) {
+        if self.frozen {
+            return;
+        }
+
+        for axis in Axis::ALL {
+            if !settings.axes.get(axis).enabled {
+                continue;
+            }
+
+            if let Some(value) = self.channels[axis as usize].tick() {
+                outputs.write(axis, value);
+
+                if let Some(snapshot) = &settings.snapshot {
+                    snapshot.lock().unwrap().axes[axis as usize] = value;
+                }
+            }
+        }
+    }
+
+    fn is_settled(&self) -> bool {
+        self.channels.iter().all(AxisChannel::is_settled)
+    }
+
+    /// Share of its range each enabled axis is fed at, from -1 to 1.
+    fn shares(&self, settings: &Settings, outputs: &Outputs) -> [Option<f32>; 6] {
+        Axis::ALL.map(|axis| {
+            let range = self.channels[axis as usize].mapping.range;
+            let span = (range.max - range.min).max(1) as f32;
+            let value = outputs.read(axis);
+
+            settings
+                .axes
+                .get(axis)
+                .enabled
+                .then(|| 2.0 * (value - range.min) as f32 / span - 1.0)
+        })
+    }
+
+    /// Start afresh after a reconnection, the trims and the fine stage carry over.
+    fn reset(&mut self) {
+        if let Some(hat) = &mut self.hat {
+            hat.reset();
+        }
+
+        if let Some(recenter) = &mut self.recenter {
+            recenter.reset();
+        }
+
+        self.zeroed = [0; 6];
+        self.frozen = false;
+
+        for channel in self.channels.iter_mut() {
+            channel.reset();
+            channel.mapping.sensitivity = 1.0;
+            channel.mapping.extra_deadzone = 0;
+        }
+    }
+}
+
+/// Device buttons turned into the vJoy buttons fed: latch, remap, chords, presses, gestures and
+/// axis buttons, plus the keys and profile switches some of them trigger.
+struct ButtonPipeline {
+    latch: buttons::ButtonLatch,
+    remap: buttons::ButtonRemap,
+    /// Buttons with a function of their own, not fed to vJoy
+    functions: u64,
+    profile_button: u64,
+    keys: Option<KeyOutput>,
+    gestures: Vec<Gesture>,
+    presses: Vec<PressButton>,
+    chords: Vec<Chord>,
+    axis_buttons: Vec<AxisButton>,
+    /// Buttons of the last report read
+    last: u64,
+    /// Device buttons fed, after the chords, latch and remap
+    device: u64,
+    /// Buttons held by the gestures, presses, chords and axes
+    held: u64,
+}
+
+impl ButtonPipeline {
+    fn new(settings: &Settings, model: &Model) -> Result<Self, FeederError> {
+        let key_buttons: Vec<u32> = settings.keys.iter().map(|key| key.button).collect();
+        let press_buttons: Vec<u32> = settings.presses.iter().map(|press| press.button).collect();
+        let functions = buttons::button_mask(&settings.buttons.functions())
+            | buttons::button_mask(&key_buttons)
+            | buttons::button_mask(&press_buttons);
+
+        let keys = if settings.keys.is_empty() {
+            None
+        } else {
+            Some(KeyOutput::new(&settings.keys).map_err(FeederError::Keyboard)?)
+        };
+
+        let remap = buttons::ButtonRemap::new(
+            &settings.buttons.remap,
+            report::button_count(model.product_id, &settings.buttons),
+            functions,
+            settings.buttons.remap_shared,
+        )
+        .map_err(FeederError::InvalidSettings)?;
+
+        Ok(Self {
+            latch: buttons::ButtonLatch::new(buttons::button_mask(&settings.buttons.toggle)),
+            remap,
+            functions,
+            profile_button: buttons::button_mask(settings.buttons.profile.as_slice()),
+            keys,
+            gestures: settings.gestures.iter().map(Gesture::new).collect(),
+            presses: settings.presses.iter().map(PressButton::new).collect(),
+            chords: settings.chords.iter().map(Chord::new).collect(),
+            axis_buttons: settings.axis_buttons.iter().map(AxisButton::new).collect(),
+            last: 0,
+            device: 0,
+            held: 0,
+        })
+    }
+
+    /// When a gesture, press or chord held while the device is silent next changes.
+    fn deadline(&self) -> Option<Instant> {
+        self.gestures
+            .iter()
+            .filter_map(Gesture::deadline)
+            .chain(self.presses.iter().filter_map(PressButton::deadline))
+            .chain(self.chords.iter().filter_map(Chord::deadline))
+            .min()
+    }
+
+    /// Update the axis buttons with the axes in `report`, held as they are while `axes` are frozen.
+    fn read_axes(&mut self, settings: &Settings, report: &Report, axes: &AxisPipeline) {
+        if axes.frozen {
+            return;
+        }
+
+        for axis_button in self.axis_buttons.iter_mut() {
+            let axis = axis_button.axis;
+
+            if let Some(raw) = report.axes[settings.axes.source(axis) as usize] {
+                axis_button.update(axes.deflection(axis, raw));
+            }
+        }
+    }
+
+    /// Take the device buttons `bits` of a report, sending their keys.
+    ///
+    /// Returns the buttons newly pressed.
+    fn press(&mut self, bits: u64, profile: &ProfileSwitch) -> u64 {
+        let pressed = bits & !self.last;
+
+        if let Some(keys) = &mut self.keys {
+            keys.update(bits, self.last);
+        }
+
+        if pressed & self.profile_button != 0 {
+            profile.next();
+        }
+
+        self.last = bits;
+        pressed
+    }
+
+    /// Run the gestures, presses and chords up to `now`.
+    ///
+    /// Chords may hold buttons back while the device is silent, so the buttons fed are only
+    /// worked out here. Returns them when they changed.
+    fn update(&mut self, zeroed: &[i32; 6], now: Instant, profile: &ProfileSwitch) -> Option<u64> {
+        let mut held = 0;
+
+        for gesture in self.gestures.iter_mut() {
+            if gesture.update(zeroed[gesture.axis as usize], now) {
+                debug!("Gesture on {} fired", gesture.axis.name());
+
+                if gesture.next_profile {
+                    profile.next();
+                }
+            }
+
+            if gesture.is_active() {
+                held |= gesture.button;
+            }
+        }
+
+        for press in self.presses.iter_mut() {
+            if let Some(kind) = press.update(self.last & press.button != 0, now) {
+                debug!(
+                    "{:?} press of button {}",
+                    kind,
+                    press.button.trailing_zeros() + 1
+                );
+
+                if press.action(kind) == Some(PressAction::Function(PressFunction::NextProfile)) {
+                    profile.next();
+                }
+            }
+
+            held |= press.buttons(now);
+        }
+
+        let mut fed = self.last & !self.functions;
+
+        for chord in self.chords.iter_mut() {
+            if chord.update(fed, now) {
+                debug!("Chord of buttons {:#x} formed", chord.buttons);
+
+                if chord.action == Some(PressAction::Function(PressFunction::NextProfile)) {
+                    profile.next();
+                }
+            }
+
+            fed = chord.filter(fed);
+            held |= chord.output();
+        }
+
+        for axis_button in &self.axis_buttons {
+            held |= axis_button.output();
+        }
+
+        let fed = self.remap.apply(self.latch.apply(fed));
+
+        if fed == self.device && held == self.held {
+            return None;
+        }
+
+        self.device = fed;
+        self.held = held;
+
+        Some(fed | held)
+    }
+
+    fn release_keys(&mut self) {
+        if let Some(keys) = &mut self.keys {
+            keys.release_all();
+        }
+    }
+
+    /// Start afresh after a reconnection, nothing held.
+    fn reset(&mut self) {
+        for gesture in self.gestures.iter_mut() {
+            gesture.reset();
+        }
+
+        for press in self.presses.iter_mut() {
+            press.reset();
+        }
+
+        for chord in self.chords.iter_mut() {
+            chord.reset();
+        }
+
+        for axis_button in self.axis_buttons.iter_mut() {
+            axis_button.reset();
+        }
+
+        self.last = 0;
+        self.device = 0;
+        self.held = 0;
+    }
+}
+
+/// Keep the frame fed with `once` on vJoy for `hold`, or until `running` is cleared.
+fn hold_frame(hold: Duration, running: &AtomicBool) {
+    info!("Frame fed, holding it for {:?}", hold);
+
+    let end = Instant::now() + hold;
+
+    while running.load(Ordering::Relaxed) && Instant::now() < end {
+        thread::sleep(HOLD_POLL.min(end.saturating_duration_since(Instant::now())));
+    }
+}
+
+/// When the next vJoy update may be sent, a partial frame holds it until the rest arrives or `frame_deadline`.
+fn update_due(next_update: Instant, frame_halves: u8, frame_deadline: Instant) -> Instant {
+    if frame_halves == 0 || frame_halves == FULL_FRAME {
+        next_update
+    } else {
+        next_update.max(frame_deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::axis::scale_axis;
+    use crate::report::REPORT_BUTTONS;
+
+    #[test]
+    fn scripted_reports_are_written_to_the_position() {
+        let sink = PositionSink::default();
+        let feeder = FeederBuilder::new()
+            .vjoy_id(1)
+            .sink(sink.clone())
+            .build()
+            .unwrap();
+
+        let mut reports =
+            demo::pose_reports([0.5, -0.25, 1.0, 0.0, 0.1, -1.0], ByteOrder::default());
+        reports.push(vec![REPORT_BUTTONS, 0b10, 0]);
+
+        feeder
+            .run_with(&mut MockSource::new(reports), &AtomicBool::new(true))
+            .unwrap();
+
+        //Centered again once the source ran dry, the last position sent before is the fed one
+        let positions = sink.positions();
+        let (id, fed) = positions[positions.len() - 2];
+        let center = AxisRange::default().center();
+        let gain = AxisRange::default().gain();
+
+        assert_eq!(id, 1);
+        assert_eq!(fed.wAxisX, scale_axis(175, gain, center));
+        assert_eq!(fed.wAxisY, scale_axis(-88, gain, center));
+        assert_eq!(fed.wAxisZ, AxisRange::default().max);
+        assert_eq!(fed.wAxisXRot, center);
+        assert_eq!(fed.wAxisYRot, scale_axis(35, gain, center));
+        assert_eq!(fed.wAxisZRot, AxisRange::default().min);
+        assert_eq!(fed.lButtons, 0b10);
+
+        let last = sink.last(1).unwrap();
+        assert_eq!(last.wAxisX, center);
+        assert_eq!(last.lButtons, 0);
+    }
+
+    #[test]
+    fn a_frame_held_back_by_the_rate_cap_is_still_fed_whole() {
+        let sink = PositionSink::default();
+        let feeder = FeederBuilder::new()
+            .vjoy_id(1)
+            .sink(sink.clone())
+            .rate(60.0)
+            .once(Duration::ZERO)
+            .build()
+            .unwrap();
+
+        let reports = demo::pose_reports([0.5, 0.0, 0.0, 0.5, 0.0, 0.0], ByteOrder::default());
+
+        feeder
+            .run_with(&mut MockSource::new(reports), &AtomicBool::new(true))
+            .unwrap();
+
+        let positions = sink.positions();
+        let (_, fed) = positions[positions.len() - 2];
+        let center = AxisRange::default().center();
+        let gain = AxisRange::default().gain();
+
+        assert_eq!(fed.wAxisX, scale_axis(175, gain, center));
+        assert_eq!(fed.wAxisXRot, scale_axis(175, gain, center));
+    }
+
+    #[test]
+    fn a_rate_too_low_for_an_interval_is_rejected() {
+        for rate in [0.0, -1.0, 1e-300, f64::NAN] {
+            let built = FeederBuilder::new().vjoy_id(1).rate(rate).build();
+
+            assert!(matches!(built, Err(FeederError::InvalidSettings(_))));
+        }
+    }
+
+    #[test]
+    fn an_osc_rate_too_low_for_an_interval_is_rejected() {
+        let osc = OscConfig {
+            rate: 1e-300,
+            ..OscConfig::default()
+        };
+        let built = FeederBuilder::new().vjoy_id(1).osc(osc).build();
+
+        assert!(matches!(built, Err(FeederError::InvalidSettings(_))));
+    }
+
+    #[test]
+    fn a_chord_of_one_repeated_button_is_rejected() {
+        for numbers in [vec![1, 1], vec![2, 2, 2]] {
+            let chord = ChordConfig {
+                buttons: numbers,
+                action: Some(PressAction::Button(3)),
+                ..ChordConfig::default()
+            };
+            let built = FeederBuilder::new().vjoy_id(1).chords(vec![chord]).build();
+
+            assert!(matches!(built, Err(FeederError::InvalidSettings(_))));
+        }
+
+        let chord = ChordConfig {
+            buttons: vec![1, 2],
+            action: Some(PressAction::Button(3)),
+            ..ChordConfig::default()
+        };
+        assert!(FeederBuilder::new()
+            .vjoy_id(1)
+            .chords(vec![chord])
+            .build()
+            .is_ok());
+    }
+
+    /// Source whose reads block until woken, as on a wedged device.
+    struct WedgedSource {
+        wake: std::sync::mpsc::Sender<()>,
+        woken: std::sync::mpsc::Receiver<()>,
+    }
+
+    impl InputSource for WedgedSource {
+        fn read(
+            &mut self,
+            _buf: &mut [u8],
+            _timeout_ms: i32,
+        ) -> Result<Option<usize>, FeederError> {
+            let _ = self.woken.recv();
+            Ok(Some(0))
+        }
+
+        fn stall_signal(&self) -> Option<crate::source::StallSignal> {
+            let wake = self.wake.clone();
+
+            Some(Box::new(move || {
+                let _ = wake.send(());
+            }))
+        }
+    }
+
+    #[test]
+    fn the_watchdog_wakes_a_read_blocked_on_the_device() {
+        let (wake, woken) = std::sync::mpsc::channel();
+        let feeder = FeederBuilder::new()
+            .vjoy_id(1)
+            .sink(PositionSink::default())
+            .watchdog(Duration::from_millis(50))
+            .build()
+            .unwrap();
+
+        let result = feeder.run_with(&mut WedgedSource { wake, woken }, &AtomicBool::new(true));
+
+        assert!(matches!(result, Err(FeederError::Stalled(_))));
+    }
+}
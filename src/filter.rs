@@ -0,0 +1,374 @@
+use crate::axis::AxisMapping;
+use crate::config::{AxisConfig, FilterKind};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Smoothing applied to a scaled axis value before it is sent to vJoy.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    None,
+    Ema(AxisFilter),
+    Window(WindowFilter),
+}
+
+impl Filter {
+    pub fn new(config: &AxisConfig) -> Self {
+        match config.filter {
+            FilterKind::None => Filter::None,
+            FilterKind::Ema => Filter::Ema(AxisFilter::new(config.alpha)),
+            FilterKind::Window => Filter::Window(WindowFilter::new(config.window)),
+        }
+    }
+
+    pub fn apply(&mut self, value: i32) -> i32 {
+        match self {
+            Filter::None => value,
+            Filter::Ema(filter) => filter.apply(value),
+            Filter::Window(filter) => filter.apply(value),
+        }
+    }
+
+    /// Forget the history, as if `value` was the only sample so far.
+    pub fn reset(&mut self, value: i32) {
+        match self {
+            Filter::None => {}
+            Filter::Ema(filter) => filter.reset(value),
+            Filter::Window(filter) => filter.reset(value),
+        }
+    }
+}
+
+/// Exponential moving average low-pass filter.
+///
+/// `alpha` is the weight of each new sample, 1.0 disables smoothing.
+/// The first sample seeds the average so the output starts at the current value.
+#[derive(Debug, Clone)]
+pub struct AxisFilter {
+    alpha: f32,
+    value: Option<f32>,
+}
+
+impl AxisFilter {
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            alpha: alpha.clamp(f32::EPSILON, 1.0),
+            value: None,
+        }
+    }
+
+    pub fn apply(&mut self, input: i32) -> i32 {
+        let value = match self.value {
+            Some(value) => value + self.alpha * (input as f32 - value),
+            None => input as f32,
+        };
+
+        self.value = Some(value);
+
+        value.round() as i32
+    }
+
+    pub fn reset(&mut self, value: i32) {
+        self.value = Some(value as f32);
+    }
+}
+
+/// Mean of the last `size` samples.
+///
+/// The output lags the input by (size - 1) / 2 reports on average
+/// and takes `size` reports to fully settle after a step.
+#[derive(Debug, Clone)]
+pub struct WindowFilter {
+    size: usize,
+    samples: VecDeque<i32>,
+}
+
+impl WindowFilter {
+    pub fn new(size: usize) -> Self {
+        let size = size.max(1);
+
+        Self {
+            size,
+            samples: VecDeque::with_capacity(size),
+        }
+    }
+
+    pub fn apply(&mut self, input: i32) -> i32 {
+        if self.samples.len() == self.size {
+            self.samples.pop_front();
+        }
+
+        self.samples.push_back(input);
+
+        let sum: i64 = self.samples.iter().map(|&sample| sample as i64).sum();
+
+        (sum / self.samples.len() as i64) as i32
+    }
+
+    pub fn reset(&mut self, value: i32) {
+        self.samples.clear();
+        self.samples.push_back(value);
+    }
+}
+
+/// Hold the output until the input moves more than `threshold` away from it,
+/// so a deflected axis held still does not flicker.
+#[derive(Debug, Clone)]
+pub struct NoiseGate {
+    threshold: i32,
+    last: Option<i32>,
+}
+
+impl NoiseGate {
+    pub fn new(threshold: u32) -> Self {
+        Self {
+            threshold: threshold.min(i32::MAX as u32) as i32,
+            last: None,
+        }
+    }
+
+    pub fn apply(&mut self, input: i32) -> i32 {
+        match self.last {
+            Some(last) if (input - last).abs() <= self.threshold => last,
+            _ => {
+                self.last = Some(input);
+                input
+            }
+        }
+    }
+
+    pub fn reset(&mut self, value: i32) {
+        self.last = Some(value);
+    }
+}
+
+/// Move the output at most `max_delta` per update toward the input, so jumps ramp in.
+///
+/// The input is kept, `advance` carries on toward it without new reports.
+#[derive(Debug, Clone)]
+pub struct SlewLimiter {
+    max_delta: i32,
+    last: Option<i32>,
+    target: Option<i32>,
+}
+
+impl SlewLimiter {
+    /// 0 lets every change through at once.
+    pub fn new(max_delta: u32) -> Self {
+        Self {
+            max_delta: max_delta.min(i32::MAX as u32) as i32,
+            last: None,
+            target: None,
+        }
+    }
+
+    pub fn apply(&mut self, input: i32) -> i32 {
+        self.target = Some(input);
+        self.advance()
+    }
+
+    /// Next step toward the last input.
+    pub fn advance(&mut self) -> i32 {
+        let target = self.target.unwrap_or_default();
+
+        let value = match self.last {
+            Some(last) if self.max_delta > 0 => {
+                last + (target - last).clamp(-self.max_delta, self.max_delta)
+            }
+            _ => target,
+        };
+
+        self.last = Some(value);
+        value
+    }
+
+    /// Whether the output reached the last input.
+    pub fn is_settled(&self) -> bool {
+        self.last == self.target
+    }
+
+    pub fn reset(&mut self, value: i32) {
+        self.last = Some(value);
+        self.target = Some(value);
+    }
+}
+
+/// Pass the output on at most once per `interval`, holding the last one passed meanwhile.
+///
+/// The latest input is kept, `advance` passes it on once the interval is over without new reports.
+#[derive(Debug, Clone)]
+pub struct UpdateHold {
+    interval: Duration,
+    /// When the output last changed
+    since: Option<Instant>,
+    last: Option<i32>,
+    pending: Option<i32>,
+}
+
+impl UpdateHold {
+    /// A zero interval lets every change through.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            since: None,
+            last: None,
+            pending: None,
+        }
+    }
+
+    pub fn apply(&mut self, input: i32, now: Instant) -> i32 {
+        let last = match self.last {
+            Some(last) if last != input => last,
+            _ => {
+                self.pending = None;
+                self.last = Some(input);
+                return input;
+            }
+        };
+
+        if self.since.is_some_and(|since| now < since + self.interval) {
+            self.pending = Some(input);
+            return last;
+        }
+
+        self.since = Some(now);
+        self.pending = None;
+        self.last = Some(input);
+        input
+    }
+
+    /// The held input once the interval is over, `None` until then.
+    pub fn advance(&mut self, now: Instant) -> Option<i32> {
+        let pending = self.pending?;
+
+        if self.since.is_some_and(|since| now < since + self.interval) {
+            return None;
+        }
+
+        Some(self.apply(pending, now))
+    }
+
+    /// Whether the last input was passed on.
+    pub fn is_settled(&self) -> bool {
+        self.pending.is_none()
+    }
+
+    pub fn reset(&mut self, value: i32) {
+        self.since = None;
+        self.last = Some(value);
+        self.pending = None;
+    }
+}
+
+/// Rate control for relative axes, deflection moves the output instead of setting it.
+///
+/// `rate` is the share of the range travelled per second at full deflection and
+/// `decay` the share of the distance to center recovered per second once released.
+#[derive(Debug, Clone)]
+pub struct Integrator {
+    rate: f64,
+    decay: f64,
+    position: Option<f64>,
+    deflection: f64,
+    last: Option<Instant>,
+}
+
+impl Integrator {
+    pub fn new(rate: f32, decay: f32) -> Self {
+        Self {
+            rate: rate.max(0.0) as f64,
+            decay: decay.max(0.0) as f64,
+            position: None,
+            deflection: 0.0,
+            last: None,
+        }
+    }
+
+    /// Take `value`, as mapped in absolute mode, as the new deflection and move on.
+    pub fn apply(&mut self, value: i32, mapping: &AxisMapping, now: Instant) -> i32 {
+        let center = mapping.center.saturating_add(mapping.trim) as f64;
+        let half_span = ((mapping.range.max - mapping.range.min) as f64 / 2.0).max(1.0);
+
+        self.deflection = ((value as f64 - center) / half_span).clamp(-1.0, 1.0);
+
+        self.advance(mapping, now)
+    }
+
+    /// Keep moving with the last deflection, also when no report came.
+    pub fn advance(&mut self, mapping: &AxisMapping, now: Instant) -> i32 {
+        let center = mapping.center.saturating_add(mapping.trim) as f64;
+        let (min, max) = (mapping.range.min as f64, mapping.range.max as f64);
+
+        let elapsed = self.last.map_or(0.0, |last| (now - last).as_secs_f64());
+        self.last = Some(now);
+
+        let position = self.position.unwrap_or(center);
+
+        let position = if self.deflection != 0.0 {
+            position + self.deflection * self.rate * (max - min) * elapsed
+        } else {
+            center + (position - center) * (-self.decay * elapsed).exp()
+        };
+
+        let position = position.clamp(min, max);
+        self.position = Some(position);
+
+        position.round() as i32
+    }
+
+    /// Whether the output stays put until the next report, released and back on center or not decaying.
+    pub fn is_settled(&self, mapping: &AxisMapping) -> bool {
+        let center = mapping.center.saturating_add(mapping.trim) as f64;
+
+        self.deflection == 0.0
+            && (self.decay == 0.0
+                || self
+                    .position
+                    .is_none_or(|position| (position - center).abs() < 0.5))
+    }
+
+    pub fn reset(&mut self) {
+        self.position = None;
+        self.deflection = 0.0;
+        self.last = None;
+    }
+
+    /// Carry on from `position` with no deflection, e.g. after a profile switch.
+    pub fn resume(&mut self, position: i32) {
+        self.reset();
+        self.position = Some(position as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_ema_converges_to_a_step() {
+        let mut filter = AxisFilter::new(0.3);
+        filter.reset(0);
+
+        let outputs: Vec<i32> = (0..40).map(|_| filter.apply(10000)).collect();
+
+        assert!(outputs.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(outputs[0] > 0 && outputs[0] < 10000);
+        assert_eq!(*outputs.last().unwrap(), 10000);
+    }
+
+    #[test]
+    fn an_alpha_of_1_passes_the_values_through() {
+        let mut filter = AxisFilter::new(1.0);
+
+        for input in [0, 16383, -5, 32767, 12] {
+            assert_eq!(filter.apply(input), input);
+        }
+    }
+
+    #[test]
+    fn the_first_sample_seeds_the_ema() {
+        let mut filter = AxisFilter::new(0.1);
+
+        assert_eq!(filter.apply(5000), 5000);
+    }
+}
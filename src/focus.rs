@@ -0,0 +1,120 @@
+use log::{debug, info, warn};
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use winapi::shared::minwindef::{DWORD, FALSE};
+use winapi::um::handleapi::CloseHandle;
+use winapi::um::processthreadsapi::OpenProcess;
+use winapi::um::winbase::QueryFullProcessImageNameW;
+use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+use winapi::um::winuser::{GetForegroundWindow, GetWindowThreadProcessId};
+
+use crate::config::AppsConfig;
+use crate::feeder::ProfileSwitch;
+
+//How often the window in focus is checked
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Switch `profile` to the one of the application in focus until `running` is cleared.
+///
+/// An application must stay in focus for the debounce time before its profile is selected,
+/// and a profile picked by hand is kept until another application gets the focus.
+pub fn watch(
+    config: AppsConfig,
+    profile: Arc<ProfileSwitch>,
+    running: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    //Executable names are matched ignoring case, as Windows does
+    let apps: BTreeMap<String, String> = config
+        .profiles
+        .into_iter()
+        .map(|(app, name)| (app.to_lowercase(), name))
+        .collect();
+    let debounce = Duration::from_millis(config.debounce_ms);
+
+    thread::spawn(move || {
+        //Application in focus and since when, then the last one acted upon
+        let mut candidate: Option<(String, Instant)> = None;
+        let mut settled: Option<String> = None;
+
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(POLL_INTERVAL);
+
+            //Nothing in focus, e.g. while switching desktops
+            let app = match foreground_app() {
+                Some(app) => app,
+                None => continue,
+            };
+
+            let since = match &candidate {
+                Some((name, since)) if *name == app => *since,
+                _ => {
+                    debug!("{} in focus", app);
+                    candidate = Some((app.clone(), Instant::now()));
+                    continue;
+                }
+            };
+
+            if since.elapsed() < debounce || settled.as_ref() == Some(&app) {
+                continue;
+            }
+
+            settled = Some(app.clone());
+
+            let name = match apps.get(&app).or(config.fallback.as_ref()) {
+                Some(name) => name,
+                None => continue,
+            };
+
+            match profile.names().iter().position(|known| known == name) {
+                Some(index) if index != profile.active() => {
+                    profile.select(index);
+                    info!("{} in focus, profile {}", app, name);
+                }
+                Some(_) => {}
+                None => warn!("{} in focus but there is no profile called {}", app, name),
+            }
+        }
+    })
+}
+
+/// Lowercase executable file name of the foreground window, such as "game.exe".
+fn foreground_app() -> Option<String> {
+    unsafe {
+        let window = GetForegroundWindow();
+
+        if window.is_null() {
+            return None;
+        }
+
+        let mut process_id: DWORD = 0;
+        GetWindowThreadProcessId(window, &mut process_id);
+
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, process_id);
+
+        if process.is_null() {
+            return None;
+        }
+
+        let mut path = [0u16; 1024];
+        let mut len = path.len() as DWORD;
+        let found = QueryFullProcessImageNameW(process, 0, path.as_mut_ptr(), &mut len) != 0;
+
+        CloseHandle(process);
+
+        if !found {
+            return None;
+        }
+
+        let path = String::from_utf16_lossy(&path[..len as usize]);
+
+        Path::new(&path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+    }
+}
@@ -0,0 +1,91 @@
+use std::time::{Duration, Instant};
+
+use crate::axis::{Axis, MAX_DEFLECTION};
+use crate::buttons;
+use crate::config::GestureConfig;
+use crate::hysteresis::Hysteresis;
+
+/// Fires when an axis stays past a threshold, e.g. a hard push down or a twist.
+///
+/// It ends once the deflection falls `hysteresis` below the threshold, so it doesn't chatter,
+/// and must end before it fires again.
+#[derive(Debug, Clone)]
+pub struct Gesture {
+    pub axis: Axis,
+    /// Bit of the vJoy button held while active, 0 for none
+    pub button: u64,
+    pub next_profile: bool,
+    /// 1 or -1, the direction the axis is pushed
+    direction: f64,
+    /// Share of the full deflection past the threshold
+    past: Hysteresis,
+    hold: Duration,
+    /// When the axis went past the threshold, while it stays there
+    since: Option<Instant>,
+    active: bool,
+}
+
+impl Gesture {
+    pub fn new(config: &GestureConfig) -> Self {
+        Self {
+            axis: config.axis,
+            button: config
+                .button
+                .map_or(0, |button| buttons::button_mask(&[button])),
+            next_profile: config.next_profile,
+            direction: if config.threshold < 0.0 { -1.0 } else { 1.0 },
+            past: Hysteresis::below(config.threshold.abs() as f64, config.hysteresis as f64),
+            hold: Duration::from_millis(config.hold_ms),
+            since: None,
+            active: false,
+        }
+    }
+
+    /// Follow the raw deflection of the axis at `now`, true when the gesture just fired.
+    pub fn update(&mut self, deflection: i32, now: Instant) -> bool {
+        let share = self.direction * deflection as f64 / MAX_DEFLECTION as f64;
+
+        if !self.past.update(share) {
+            self.since = None;
+            self.active = false;
+            return false;
+        }
+
+        let since = *self.since.get_or_insert(now);
+
+        if self.active || now < since + self.hold {
+            return false;
+        }
+
+        self.active = true;
+
+        true
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// When the gesture fires if the axis stays put, to check on it while the device is silent.
+    pub fn deadline(&self) -> Option<Instant> {
+        match self.since {
+            Some(since) if !self.active => Some(since + self.hold),
+            _ => None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.past.reset();
+        self.since = None;
+        self.active = false;
+    }
+}
+
+/// Highest vJoy button pressed by `gestures`, 0 if none.
+pub fn button_count(gestures: &[GestureConfig]) -> u32 {
+    gestures
+        .iter()
+        .filter_map(|gesture| gesture.button)
+        .max()
+        .unwrap_or(0)
+}
@@ -0,0 +1,172 @@
+use rusty_vjoy::JoystickPosition;
+
+use std::f64::consts::FRAC_PI_4;
+
+use crate::axis::MAX_DEFLECTION;
+use crate::config::HatConfig;
+use crate::hysteresis::Hysteresis;
+
+/// `bHats` value of a centered hat, for continuous and discrete POVs alike.
+pub const HAT_CENTERED: u32 = u32::MAX;
+
+//Hundredths of a degree between two of the eight directions
+const DIRECTION_STEP: u32 = 4500;
+
+/// Turns a pair of axes into the eight directions of a continuous POV hat.
+///
+/// Once pointed, the hat holds on until the deflection falls `hysteresis` below the threshold
+/// or strays `hysteresis` of a sector past the edge of its direction, so it doesn't chatter.
+#[derive(Debug, Clone)]
+pub struct Hat {
+    /// Deflection pointing the hat
+    deflection: Hysteresis,
+    margin: f64,
+    invert_x: bool,
+    invert_y: bool,
+    /// Eighths of a turn clockwise from north, `None` when centered
+    direction: Option<u32>,
+}
+
+impl Hat {
+    pub fn new(config: &HatConfig) -> Self {
+        Self {
+            deflection: Hysteresis::below(config.threshold as f64, config.hysteresis as f64),
+            margin: config.hysteresis as f64,
+            invert_x: config.invert_x,
+            invert_y: config.invert_y,
+            direction: None,
+        }
+    }
+
+    /// `bHats` value for raw deflections `x`, toward east, and `y`, toward south.
+    pub fn apply(&mut self, x: i32, y: i32) -> u32 {
+        let sign = |invert: bool| if invert { -1.0 } else { 1.0 };
+        let x = sign(self.invert_x) * x as f64 / MAX_DEFLECTION as f64;
+        let y = sign(self.invert_y) * y as f64 / MAX_DEFLECTION as f64;
+
+        if !self.deflection.update(x.hypot(y)) {
+            self.direction = None;
+            return HAT_CENTERED;
+        }
+
+        let angle = (x.atan2(-y) / FRAC_PI_4).rem_euclid(8.0);
+
+        let direction = match self.direction {
+            Some(current) if sector_distance(angle, current) <= 0.5 + self.margin => current,
+            _ => angle.round() as u32 % 8,
+        };
+
+        self.direction = Some(direction);
+
+        direction * DIRECTION_STEP
+    }
+
+    pub fn reset(&mut self) {
+        self.deflection.reset();
+        self.direction = None;
+    }
+}
+
+/// Eighths of a turn between `angle` and `direction`, either way round.
+fn sector_distance(angle: f64, direction: u32) -> f64 {
+    let distance = (angle - direction as f64).rem_euclid(8.0);
+
+    distance.min(8.0 - distance)
+}
+
+pub fn write_hat(position: &mut JoystickPosition, value: u32) {
+    position.bHats = value;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //Raw deflection at `share` of the full deflection, `eighths` of a turn clockwise from north
+    fn toward(eighths: f64, share: f64) -> (i32, i32) {
+        let angle = eighths * FRAC_PI_4;
+        let length = share * MAX_DEFLECTION as f64;
+
+        (
+            (length * angle.sin()).round() as i32,
+            (-length * angle.cos()).round() as i32,
+        )
+    }
+
+    #[test]
+    fn the_eight_directions_go_clockwise_from_north() {
+        let pushes = [
+            (0, -350),
+            (250, -250),
+            (350, 0),
+            (250, 250),
+            (0, 350),
+            (-250, 250),
+            (-350, 0),
+            (-250, -250),
+        ];
+
+        for (direction, (x, y)) in pushes.into_iter().enumerate() {
+            let mut hat = Hat::new(&HatConfig::default());
+
+            assert_eq!(hat.apply(x, y), direction as u32 * DIRECTION_STEP);
+        }
+    }
+
+    #[test]
+    fn an_inverted_axis_flips_its_directions() {
+        let mut hat = Hat::new(&HatConfig {
+            invert_y: true,
+            ..HatConfig::default()
+        });
+
+        assert_eq!(hat.apply(0, -350), 18000);
+    }
+
+    #[test]
+    fn the_hat_is_centered_below_the_threshold() {
+        let mut hat = Hat::new(&HatConfig::default());
+
+        assert_eq!(hat.apply(0, 0), HAT_CENTERED);
+        assert_eq!(hat.apply(170, 0), HAT_CENTERED);
+        assert_eq!(hat.apply(175, 0), 9000);
+
+        //Pointed, it holds down to the threshold less the hysteresis
+        assert_eq!(hat.apply(150, 0), 9000);
+        assert_eq!(hat.apply(135, 0), HAT_CENTERED);
+    }
+
+    #[test]
+    fn a_direction_holds_a_margin_past_its_sector_edge() {
+        let mut hat = Hat::new(&HatConfig::default());
+        let (x, y) = toward(0.0, 1.0);
+        assert_eq!(hat.apply(x, y), 0);
+
+        //Past the edge between north and north-east, within the margin
+        let (x, y) = toward(0.55, 1.0);
+        assert_eq!(hat.apply(x, y), 0);
+
+        let (x, y) = toward(0.65, 1.0);
+        assert_eq!(hat.apply(x, y), 4500);
+
+        //Back within the margin of north-east, it stays there
+        let (x, y) = toward(0.45, 1.0);
+        assert_eq!(hat.apply(x, y), 4500);
+
+        //A fresh hat takes the nearest direction
+        let mut hat = Hat::new(&HatConfig::default());
+        let (x, y) = toward(0.55, 1.0);
+        assert_eq!(hat.apply(x, y), 4500);
+    }
+
+    #[test]
+    fn north_west_wraps_around_to_north() {
+        let mut hat = Hat::new(&HatConfig::default());
+
+        let (x, y) = toward(7.6, 1.0);
+        assert_eq!(hat.apply(x, y), 0);
+
+        let (x, y) = toward(7.45, 1.0);
+        assert_eq!(hat.apply(x, y), 0);
+    }
+}
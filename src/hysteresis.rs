@@ -0,0 +1,84 @@
+/// On or off output of a value crossing a threshold, such as an axis pushed into a button.
+///
+/// It turns on once the value reaches `enter` and back off only once it falls below `exit`,
+/// so a value wavering between the two holds the output instead of making it flicker.
+#[derive(Debug, Clone, Copy)]
+pub struct Hysteresis {
+    enter: f64,
+    exit: f64,
+    on: bool,
+}
+
+impl Hysteresis {
+    /// `exit` is at most `enter`, the band between them holds the output.
+    pub fn new(enter: f64, exit: f64) -> Self {
+        Self {
+            enter,
+            exit: exit.min(enter),
+            on: false,
+        }
+    }
+
+    /// Threshold `enter` with a band of `width` below it, as the configs set them.
+    pub fn below(enter: f64, width: f64) -> Self {
+        Self::new(enter, enter - width)
+    }
+
+    /// Follow `value`, true while the output is on.
+    pub fn update(&mut self, value: f64) -> bool {
+        self.on = if self.on {
+            value >= self.exit
+        } else {
+            value >= self.enter
+        };
+
+        self.on
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+
+    pub fn reset(&mut self) {
+        self.on = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_value_wavering_inside_the_band_holds_the_output() {
+        let mut hysteresis = Hysteresis::below(0.6, 0.2);
+
+        for value in [0.45, 0.55, 0.41, 0.59] {
+            assert!(!hysteresis.update(value));
+        }
+
+        assert!(hysteresis.update(0.6));
+
+        for value in [0.45, 0.55, 0.41, 0.59, 0.4] {
+            assert!(hysteresis.update(value));
+        }
+    }
+
+    #[test]
+    fn the_output_turns_off_below_the_exit() {
+        let mut hysteresis = Hysteresis::new(0.6, 0.4);
+
+        assert!(hysteresis.update(0.7));
+        assert!(!hysteresis.update(0.39));
+        assert!(!hysteresis.is_on());
+        assert!(!hysteresis.update(0.5));
+    }
+
+    #[test]
+    fn an_exit_above_the_enter_is_lowered_to_it() {
+        let mut hysteresis = Hysteresis::new(0.5, 0.8);
+
+        assert!(hysteresis.update(0.5));
+        assert!(hysteresis.update(0.5));
+        assert!(!hysteresis.update(0.49));
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::Write as _;
+
+use crate::axis::{Axis, Field};
+use crate::vjoy;
+
+/// Layout of a vJoy device, as set by a `vJoyConfig` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VJoyLayout {
+    pub id: u32,
+    pub fields: Vec<Field>,
+    pub buttons: u32,
+    /// Continuous POV hats
+    pub povs: u32,
+    /// Four direction POV hats
+    pub discrete_povs: u32,
+    /// The `vJoyConfig` line read
+    pub command: String,
+}
+
+/// Read the first `vJoyConfig` command of `text`, such as `vJoyConfig 1 -f -a x y z rx ry rz -b 8 -p 1`.
+///
+/// Comment lines starting with `#`, `;` or `rem`, as in a batch file, are skipped.
+pub fn parse_vjoyconfig(text: &str) -> Result<VJoyLayout, String> {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| {
+            let lower = line.to_lowercase();
+
+            !line.is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with(';')
+                && !lower.starts_with("rem ")
+                && lower.contains("vjoyconfig")
+        })
+        .ok_or("no vJoyConfig command found")?;
+
+    //The program may be quoted with its whole path
+    let mut tokens = line.split_whitespace().skip_while(|token| {
+        !token
+            .trim_matches('"')
+            .to_lowercase()
+            .trim_end_matches(".exe")
+            .ends_with("vjoyconfig")
+    });
+    tokens.next();
+
+    let id = tokens
+        .next()
+        .ok_or("the vJoyConfig command has no device ID")?;
+    let id = id
+        .parse()
+        .map_err(|_| format!("`{}` is not a vJoy device ID", id))
+        .and_then(vjoy::check_vjoy_id)?;
+
+    let mut layout = VJoyLayout {
+        id,
+        command: line.to_string(),
+        ..VJoyLayout::default()
+    };
+
+    let mut flag = String::new();
+
+    for token in tokens {
+        if token.starts_with('-') {
+            flag = token.to_lowercase();
+
+            if !["-f", "-a", "-b", "-p", "-s", "-e"].contains(&flag.as_str()) {
+                return Err(format!("unknown vJoyConfig option {}", token));
+            }
+
+            continue;
+        }
+
+        let count = || {
+            token
+                .parse::<u32>()
+                .map_err(|_| format!("`{}` is not a count for {}", token, flag))
+        };
+
+        match flag.as_str() {
+            "-a" => layout.fields.push(parse_field(token)?),
+            "-b" => layout.buttons = count()?,
+            "-p" => layout.povs = count()?,
+            "-s" => layout.discrete_povs = count()?,
+            //Force feedback effects don't matter to a feeder
+            "-e" => continue,
+            _ => return Err(format!("unexpected `{}` in the vJoyConfig command", token)),
+        }
+
+        //Counts take a single value
+        if flag != "-a" {
+            flag.clear();
+        }
+    }
+
+    Ok(layout)
+}
+
+/// vJoyConfig axis name, the two sliders being fed as slider and dial.
+fn parse_field(name: &str) -> Result<Field, String> {
+    let field = match name.to_lowercase().as_str() {
+        "x" => Field::X,
+        "y" => Field::Y,
+        "z" => Field::Z,
+        "rx" => Field::RX,
+        "ry" => Field::RY,
+        "rz" => Field::RZ,
+        "sl0" => Field::Slider,
+        "sl1" => Field::Dial,
+        _ => return Err(format!("unknown vJoyConfig axis {}", name)),
+    };
+
+    Ok(field)
+}
+
+/// Config file skeleton feeding `layout`, to refine by hand.
+///
+/// Puck axes without their field on the device feed a slider left over, or are disabled.
+pub fn skeleton(layout: &VJoyLayout) -> String {
+    let mut sliders = [Field::Slider, Field::Dial]
+        .into_iter()
+        .filter(|field| layout.fields.contains(field));
+
+    let mut text = String::new();
+
+    let _ = writeln!(text, "# imported from: {}", layout.command);
+    let _ = writeln!(
+        text,
+        "# vJoy device {} has {} axes, {} buttons, {} continuous and {} discrete POV hats",
+        layout.id,
+        layout.fields.len(),
+        layout.buttons,
+        layout.povs,
+        layout.discrete_povs
+    );
+    let _ = writeln!(text, "\n[vjoy]\nid = {}", layout.id);
+
+    if layout.buttons == 0 {
+        let _ = writeln!(
+            text,
+            "\n# the vJoy device has no buttons, move them to others with remap or feed none\n[buttons]\ncount = 0"
+        );
+    }
+
+    for axis in Axis::ALL {
+        let _ = writeln!(text, "\n[axes.{}]", axis.name().to_lowercase());
+
+        if layout.fields.contains(&Field::from(axis)) {
+            let _ = writeln!(text, "enabled = true");
+        } else if let Some(field) = sliders.next() {
+            let _ = writeln!(text, "field = \"{}\"", field.name().to_lowercase());
+        } else {
+            let _ = writeln!(
+                text,
+                "# no {} on the vJoy device\nenabled = false",
+                axis.name()
+            );
+        }
+    }
+
+    if layout.povs > 0 {
+        let _ = writeln!(
+            text,
+            "\n# point the POV hat with a pair of axes, disabled above if they should only do that\n# [hat]\n# x = \"rx\"\n# y = \"ry\""
+        );
+    }
+
+    text
+}
@@ -0,0 +1,63 @@
+use rusty_vjoy::JoystickPosition;
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Virtual game controller fed the position, a vJoy device on Windows or uinput on Linux.
+pub trait VirtualJoystick {
+    /// Send `position`, false if the device did not take it.
+    fn update(&mut self, position: &JoystickPosition) -> bool;
+}
+
+/// Positions kept in memory instead of being sent to a device, to check what a feeder wrote.
+#[derive(Clone, Default)]
+pub struct PositionSink {
+    sent: Arc<Mutex<Vec<(u32, JoystickPosition)>>>,
+}
+
+impl PositionSink {
+    /// Joystick appending what it is sent to the sink, as device `id`.
+    pub fn joystick(&self, id: u32) -> SinkJoystick {
+        SinkJoystick {
+            id,
+            sent: self.sent.clone(),
+        }
+    }
+
+    /// Every position sent so far with the ID of its device, oldest first.
+    pub fn positions(&self) -> Vec<(u32, JoystickPosition)> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// Last position sent to device `id`.
+    pub fn last(&self, id: u32) -> Option<JoystickPosition> {
+        self.sent
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|(sent_id, _)| *sent_id == id)
+            .map(|&(_, position)| position)
+    }
+}
+
+impl fmt::Debug for PositionSink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PositionSink")
+            .field("sent", &self.sent.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// Device of a `PositionSink`, which always takes the position.
+pub struct SinkJoystick {
+    id: u32,
+    sent: Arc<Mutex<Vec<(u32, JoystickPosition)>>>,
+}
+
+impl VirtualJoystick for SinkJoystick {
+    fn update(&mut self, position: &JoystickPosition) -> bool {
+        self.sent.lock().unwrap().push((self.id, *position));
+        true
+    }
+}
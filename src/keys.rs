@@ -0,0 +1,331 @@
+use log::{debug, warn};
+
+use std::io;
+
+use crate::buttons;
+use crate::config::{KeyAction, KeyConfig};
+
+/// Key by its config name, with the codes each platform sends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub name: &'static str,
+    /// Windows virtual-key code
+    pub vk: u16,
+    /// Linux input event code
+    pub code: u16,
+    /// Sent with the extended flag on Windows, e.g. the arrows
+    pub extended: bool,
+}
+
+const fn key(name: &'static str, vk: u16, code: u16) -> Key {
+    Key {
+        name,
+        vk,
+        code,
+        extended: false,
+    }
+}
+
+const fn extended(name: &'static str, vk: u16, code: u16) -> Key {
+    Key {
+        name,
+        vk,
+        code,
+        extended: true,
+    }
+}
+
+/// Keys buttons may send, named as in the config file.
+pub const KEYS: [Key; 88] = [
+    key("a", 0x41, 30),
+    key("b", 0x42, 48),
+    key("c", 0x43, 46),
+    key("d", 0x44, 32),
+    key("e", 0x45, 18),
+    key("f", 0x46, 33),
+    key("g", 0x47, 34),
+    key("h", 0x48, 35),
+    key("i", 0x49, 23),
+    key("j", 0x4a, 36),
+    key("k", 0x4b, 37),
+    key("l", 0x4c, 38),
+    key("m", 0x4d, 50),
+    key("n", 0x4e, 49),
+    key("o", 0x4f, 24),
+    key("p", 0x50, 25),
+    key("q", 0x51, 16),
+    key("r", 0x52, 19),
+    key("s", 0x53, 31),
+    key("t", 0x54, 20),
+    key("u", 0x55, 22),
+    key("v", 0x56, 47),
+    key("w", 0x57, 17),
+    key("x", 0x58, 45),
+    key("y", 0x59, 21),
+    key("z", 0x5a, 44),
+    key("0", 0x30, 11),
+    key("1", 0x31, 2),
+    key("2", 0x32, 3),
+    key("3", 0x33, 4),
+    key("4", 0x34, 5),
+    key("5", 0x35, 6),
+    key("6", 0x36, 7),
+    key("7", 0x37, 8),
+    key("8", 0x38, 9),
+    key("9", 0x39, 10),
+    key("f1", 0x70, 59),
+    key("f2", 0x71, 60),
+    key("f3", 0x72, 61),
+    key("f4", 0x73, 62),
+    key("f5", 0x74, 63),
+    key("f6", 0x75, 64),
+    key("f7", 0x76, 65),
+    key("f8", 0x77, 66),
+    key("f9", 0x78, 67),
+    key("f10", 0x79, 68),
+    key("f11", 0x7a, 87),
+    key("f12", 0x7b, 88),
+    key("f13", 0x7c, 183),
+    key("f14", 0x7d, 184),
+    key("f15", 0x7e, 185),
+    key("f16", 0x7f, 186),
+    key("f17", 0x80, 187),
+    key("f18", 0x81, 188),
+    key("f19", 0x82, 189),
+    key("f20", 0x83, 190),
+    key("f21", 0x84, 191),
+    key("f22", 0x85, 192),
+    key("f23", 0x86, 193),
+    key("f24", 0x87, 194),
+    key("escape", 0x1b, 1),
+    key("tab", 0x09, 15),
+    key("backspace", 0x08, 14),
+    key("enter", 0x0d, 28),
+    key("space", 0x20, 57),
+    key("minus", 0xbd, 12),
+    key("equal", 0xbb, 13),
+    key("comma", 0xbc, 51),
+    key("period", 0xbe, 52),
+    key("slash", 0xbf, 53),
+    key("shift", 0xa0, 42),
+    key("ctrl", 0xa2, 29),
+    key("alt", 0xa4, 56),
+    key("right_shift", 0xa1, 54),
+    extended("right_ctrl", 0xa3, 97),
+    extended("right_alt", 0xa5, 100),
+    extended("up", 0x26, 103),
+    extended("down", 0x28, 108),
+    extended("left", 0x25, 105),
+    extended("right", 0x27, 106),
+    extended("home", 0x24, 102),
+    extended("end", 0x23, 107),
+    extended("page_up", 0x21, 104),
+    extended("page_down", 0x22, 109),
+    extended("insert", 0x2d, 110),
+    extended("delete", 0x2e, 111),
+    key("pause", 0x13, 119),
+    key("caps_lock", 0x14, 58),
+];
+
+/// Key called `name`, ignoring case.
+pub fn lookup(name: &str) -> Option<Key> {
+    KEYS.iter()
+        .find(|key| key.name.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+struct Binding {
+    button: u64,
+    key: Key,
+    action: KeyAction,
+}
+
+/// Keyboard keys sent on button presses and releases, alongside vJoy.
+///
+/// Keys still down are released when it is dropped, also when the feed stops on an error.
+pub struct KeyOutput {
+    bindings: Vec<Binding>,
+    keyboard: Keyboard,
+    held: Vec<Key>,
+}
+
+impl KeyOutput {
+    /// `configs` must name known keys, as checked when building the feeder.
+    pub fn new(configs: &[KeyConfig]) -> io::Result<Self> {
+        let bindings: Vec<Binding> = configs
+            .iter()
+            .filter_map(|config| {
+                Some(Binding {
+                    button: buttons::button_mask(&[config.button]),
+                    key: lookup(&config.key)?,
+                    action: config.action,
+                })
+            })
+            .collect();
+
+        let keys: Vec<Key> = bindings.iter().map(|binding| binding.key).collect();
+
+        Ok(Self {
+            bindings,
+            keyboard: Keyboard::create(&keys)?,
+            held: Vec::new(),
+        })
+    }
+
+    /// Send the keys of the buttons pressed or released between `last` and `bits`.
+    pub fn update(&mut self, bits: u64, last: u64) {
+        let pressed = bits & !last;
+        let released = last & !bits;
+
+        for index in 0..self.bindings.len() {
+            let Binding {
+                button,
+                key,
+                action,
+            } = self.bindings[index];
+
+            match action {
+                KeyAction::Hold => {
+                    if pressed & button != 0 {
+                        self.send(key, true);
+                    }
+
+                    if released & button != 0 {
+                        self.send(key, false);
+                    }
+                }
+                KeyAction::Tap if pressed & button != 0 => self.tap(key),
+                KeyAction::Release if released & button != 0 => self.tap(key),
+                _ => {}
+            }
+        }
+    }
+
+    /// Let go of every key still down, e.g. when the device is lost.
+    pub fn release_all(&mut self) {
+        while let Some(key) = self.held.pop() {
+            self.send(key, false);
+        }
+    }
+
+    fn tap(&mut self, key: Key) {
+        self.send(key, true);
+        self.send(key, false);
+    }
+
+    fn send(&mut self, key: Key, down: bool) {
+        debug!("Key {} {}", key.name, if down { "down" } else { "up" });
+
+        if down {
+            self.held.push(key);
+        } else {
+            self.held.retain(|&held| held != key);
+        }
+
+        if let Err(error) = self.keyboard.send(key, down) {
+            warn!("Key {} could not be sent: {}", key.name, error);
+        }
+    }
+}
+
+impl Drop for KeyOutput {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+#[cfg(target_os = "linux")]
+type Keyboard = crate::uinput::UinputKeyboard;
+
+/// Keystrokes injected with `SendInput`, as scan codes since games often read those.
+#[cfg(windows)]
+struct Keyboard;
+
+#[cfg(windows)]
+impl Keyboard {
+    fn create(_keys: &[Key]) -> io::Result<Self> {
+        Ok(Keyboard)
+    }
+
+    fn send(&mut self, key: Key, down: bool) -> io::Result<()> {
+        use std::mem;
+
+        use winapi::um::winuser::{
+            MapVirtualKeyW, SendInput, INPUT, INPUT_KEYBOARD, KEYEVENTF_EXTENDEDKEY,
+            KEYEVENTF_KEYUP, KEYEVENTF_SCANCODE, MAPVK_VK_TO_VSC,
+        };
+
+        unsafe {
+            let mut input: INPUT = mem::zeroed();
+            input.type_ = INPUT_KEYBOARD;
+
+            let keyboard = input.u.ki_mut();
+            keyboard.wVk = key.vk;
+            keyboard.wScan = MapVirtualKeyW(key.vk as u32, MAPVK_VK_TO_VSC) as u16;
+            keyboard.dwFlags = KEYEVENTF_SCANCODE;
+
+            if key.extended {
+                keyboard.dwFlags |= KEYEVENTF_EXTENDEDKEY;
+            }
+
+            if !down {
+                keyboard.dwFlags |= KEYEVENTF_KEYUP;
+            }
+
+            if SendInput(1, &mut input, mem::size_of::<INPUT>() as i32) == 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_names_and_codes_are_unique() {
+        for (index, key) in KEYS.iter().enumerate() {
+            for other in &KEYS[..index] {
+                assert!(
+                    !key.name.eq_ignore_ascii_case(other.name),
+                    "{} listed twice",
+                    key.name
+                );
+                assert_ne!(
+                    key.vk, other.vk,
+                    "{} and {} share a vk",
+                    key.name, other.name
+                );
+                assert_ne!(
+                    key.code, other.code,
+                    "{} and {} share a code",
+                    key.name, other.name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn key_names_are_lowercase_as_in_the_config() {
+        assert!(KEYS
+            .iter()
+            .all(|key| key.name == key.name.to_ascii_lowercase()));
+    }
+
+    #[test]
+    fn lookup_ignores_case() {
+        assert_eq!(lookup("F1"), lookup("f1"));
+        assert_eq!(lookup("Page_Up").unwrap().vk, 0x21);
+        assert!(lookup("SPACE").is_some());
+        assert_eq!(lookup("ESCAPE").unwrap().code, 1);
+    }
+
+    #[test]
+    fn lookup_of_an_unknown_name_is_none() {
+        assert_eq!(lookup("f25"), None);
+        assert_eq!(lookup(""), None);
+    }
+}
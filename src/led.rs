@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+//LED goes dark this long on a button press
+const BLINK_DURATION: Duration = Duration::from_millis(80);
+
+//LED toggles at this interval while in error
+const PULSE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Non-blocking LED state machine, steady on while feeding.
+///
+/// Driven from the read loop: request a state with `blink`/`set_error`
+/// then call `update` to learn when the LED must be switched.
+pub struct Led {
+    on: bool,
+    blink_until: Option<Instant>,
+    error_since: Option<Instant>,
+}
+
+impl Led {
+    /// `on` is the state the LED was last set to.
+    pub fn new(on: bool) -> Self {
+        Self {
+            on,
+            blink_until: None,
+            error_since: None,
+        }
+    }
+
+    pub fn blink(&mut self, now: Instant) {
+        self.blink_until = Some(now + BLINK_DURATION);
+    }
+
+    pub fn set_error(&mut self, error: bool, now: Instant) {
+        match (error, self.error_since) {
+            (true, None) => self.error_since = Some(now),
+            (false, Some(_)) => self.error_since = None,
+            _ => {}
+        }
+    }
+
+    fn wanted(&self, now: Instant) -> bool {
+        if let Some(since) = self.error_since {
+            let phase = now.duration_since(since).as_millis() / PULSE_INTERVAL.as_millis();
+
+            return phase.is_multiple_of(2);
+        }
+
+        match self.blink_until {
+            Some(until) => now >= until,
+            None => true,
+        }
+    }
+
+    /// New LED state if it must change.
+    pub fn update(&mut self, now: Instant) -> Option<bool> {
+        let wanted = self.wanted(now);
+
+        if wanted == self.on {
+            return None;
+        }
+
+        self.on = wanted;
+
+        Some(wanted)
+    }
+}
@@ -0,0 +1,52 @@
+pub mod axis;
+pub mod axis_button;
+pub mod buttons;
+pub mod calibrate;
+pub mod chord;
+pub mod config;
+pub mod demo;
+pub mod device;
+pub mod error;
+pub mod feeder;
+pub mod filter;
+#[cfg(windows)]
+pub mod focus;
+pub mod gesture;
+pub mod hat;
+pub mod hysteresis;
+pub mod import;
+pub mod joystick;
+pub mod keys;
+pub mod led;
+pub mod metrics;
+pub mod osc;
+pub mod press;
+pub mod recenter;
+pub mod record;
+pub mod reload;
+pub mod repl;
+pub mod report;
+pub mod source;
+pub mod status;
+#[cfg(windows)]
+pub mod tray;
+pub mod tui;
+#[cfg(target_os = "linux")]
+pub mod uinput;
+pub mod vjoy;
+#[cfg(windows)]
+pub mod vjoy_dll;
+pub mod watchdog;
+pub mod ws;
+
+pub use axis::AxisMapping;
+pub use error::FeederError;
+pub use feeder::{Feeder, FeederBuilder, Replay};
+pub use source::{InputSource, MockSource};
+
+#[cfg(not(any(windows, target_os = "linux")))]
+compile_error!("vjoy-feeder feeds vJoy on Windows and uinput on Linux only");
+
+//SpaceNavigator
+pub const VENDOR_ID: u16 = 1133;
+pub const PRODUCT_ID: u16 = 50726;
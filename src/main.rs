@@ -1,140 +1,300 @@
-use hidapi::{HidApi, HidDevice, HidError, HidResult};
+use clap::{ArgAction, Parser, ValueEnum};
 
-use rusty_vjoy::{HidUsage, JoystickPosition, VJDStat};
+use log::{debug, error, info, warn};
 
+use std::env;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process::ExitCode;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use hidapi::HidApi;
+
+use serde_json::json;
+
+use vjoy_feeder::axis::Axis;
+use vjoy_feeder::calibrate::{Calibrations, DeviceCalibration};
+use vjoy_feeder::config::{BindingConfig, Config, ConfigError, OscConfig};
+use vjoy_feeder::demo::Waveform;
+use vjoy_feeder::feeder::Snapshot;
+use vjoy_feeder::metrics::Metrics;
+use vjoy_feeder::repl::{Console, Tuning};
+use vjoy_feeder::{
+    calibrate, config, device, import, metrics, record, repl, report, status, tui, vjoy, ws,
+    Feeder, FeederBuilder, FeederError, Replay,
+};
+
+//Settings taken from the environment when the flag is not given
+const ENV_VJOY_ID: &str = "VJOY_FEEDER_ID";
+const ENV_VENDOR_ID: &str = "VJOY_FEEDER_VENDOR_ID";
+const ENV_PRODUCT_ID: &str = "VJOY_FEEDER_PRODUCT_ID";
+const ENV_SERIAL: &str = "VJOY_FEEDER_SERIAL";
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ConfigFormat {
+    Toml,
+    Json,
+}
 
-const HALFMAXI16: i32 = i16::MAX as i32 / 2;
+/// vJoy feeder app for SpaceNavigator 3D mouse
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Config file, defaults are used if it does not exist
+    #[arg(long, default_value = config::DEFAULT_CONFIG_PATH)]
+    config: PathBuf,
+
+    /// vJoy device to feed, repeat or separate with commas to feed several the same state, or VJOY_FEEDER_ID [default: 1]
+    #[arg(long, value_parser = parse_vjoy_id, value_delimiter = ',')]
+    vjoy_id: Vec<u32>,
+
+    /// Feed the first free vJoy device with the mapped axes and enough buttons
+    #[cfg(windows)]
+    #[arg(long, conflicts_with = "vjoy_id")]
+    auto_vjoy: bool,
 
-//SpaceNavigator
-const VENDOR_ID: u16 = 1133;
-const PRODUCT_ID: u16 = 50726;
+    /// USB vendor ID of the HID device, decimal or 0x-prefixed hex, or VJOY_FEEDER_VENDOR_ID [default: 1133]
+    #[arg(long, value_parser = parse_usb_id)]
+    vendor_id: Option<u16>,
 
-//16 bit signed per axis
-const REPORT_TRANSLATION: u8 = 1; //left/right, foward/back, up/down
-const REPORT_ROTATION: u8 = 2; //pitch, roll, yaw
+    /// USB product ID of the HID device, decimal or 0x-prefixed hex, or VJOY_FEEDER_PRODUCT_ID [default: any known 3Dconnexion model]
+    #[arg(long, value_parser = parse_usb_id)]
+    product_id: Option<u16>,
 
-//1 byte
-const REPORT_BUTTONS: u8 = 3; //0 == no buttons, 1 == left button, 2 == right button 3 == both buttons
+    /// Serial number of the HID device, to pick one among several identical units, or VJOY_FEEDER_SERIAL
+    #[arg(long)]
+    serial: Option<String>,
 
-//1 byte
-const _REPORT_LED: u8 = 4; //0 == off, 1+ == on
+    /// Wait for the HID device to be plugged in instead of exiting
+    #[arg(long)]
+    wait: bool,
 
-fn check_vjoy_enabled() -> Result<(), HidError> {
-    let status = rusty_vjoy::vjoy_enabled();
+    /// Give up waiting for the HID device after this many seconds
+    #[arg(long, value_name = "SECONDS", requires = "wait")]
+    wait_timeout: Option<u64>,
 
-    println!("vJoy driver installed & enabled = {}", status);
+    /// Average the rest position for this long at startup and subtract it, 0 to skip
+    #[arg(long, value_name = "MS")]
+    auto_zero: Option<u64>,
 
-    if status {
-        println!(
-            "Driver Info;\n    Vendor: {}\n    Product : {}\n    Version Number: {}\n",
-            rusty_vjoy::get_vjoy_manufacturer_string(),
-            rusty_vjoy::get_vjoy_product_string(),
-            rusty_vjoy::get_vjoy_serial_number_string(),
-        );
-        Ok(())
-    } else {
-        Err(HidError::InitializationError)
-    }
-}
+    /// Zero the axes again once they all stayed this long near rest, against drift, 0 to never
+    #[arg(long, value_name = "MS")]
+    recenter: Option<u64>,
 
-fn check_vjoy_versions() -> Result<(), HidError> {
-    let (matching, dll_ver, driver_ver) = rusty_vjoy::driver_match();
+    /// Poll the HID device instead of waiting for each report
+    #[arg(long)]
+    non_blocking: bool,
 
-    println!("vJoy Driver match DLL version = {}", matching);
+    /// Reopen the HID device after this long without a report, 0 to never
+    #[arg(long, value_name = "MS")]
+    watchdog: Option<u64>,
 
-    if matching {
-        Ok(())
-    } else {
-        println!(
-            "Versions;\n    Driver: {:#04x}\n    DLL: {:#04x}",
-            driver_ver, dll_ver
-        );
-        Err(HidError::InitializationError)
-    }
+    /// Wait this long for both the translation and rotation reports before updating vJoy, 0 to send each
+    #[arg(long, value_name = "MS")]
+    coalesce: Option<u64>,
+
+    /// Also write the raw reports to this file, to replay them later
+    #[arg(long, value_name = "PATH")]
+    record: Option<PathBuf>,
+
+    /// Feed a recording made with --record instead of the HID device
+    #[arg(long, value_name = "PATH")]
+    replay: Option<PathBuf>,
+
+    /// Replay this many times faster than recorded
+    #[arg(long, default_value_t = 1.0, value_parser = parse_speed, requires = "replay")]
+    speed: f64,
+
+    /// Start the replay over once it is done
+    #[arg(long = "loop", requires = "replay")]
+    looping: bool,
+
+    /// Feed synthetic motion instead of the HID device, to check the vJoy side without it
+    #[arg(long, conflicts_with = "replay")]
+    demo: bool,
+
+    /// Feed a single full frame, hold it on vJoy for --hold seconds and exit, e.g. to set a throttle from a script
+    #[arg(long, conflicts_with_all = ["demo", "replay", "tui", "repl"])]
+    once: bool,
+
+    /// Seconds the frame fed with --once is held before vJoy is let go
+    #[arg(long, value_name = "SECONDS", default_value = "0", value_parser = parse_duration, requires = "once")]
+    hold: Duration,
+
+    /// Pose fed with --once instead of reading the HID device, as AXIS=SHARE of the full deflection from -1 to 1, unset axes rest
+    #[arg(long = "value", value_name = "AXIS=SHARE", value_parser = parse_pose_value, requires = "once")]
+    values: Vec<(Axis, f64)>,
+
+    /// Sine periods per second of the demo motion
+    #[arg(long, value_name = "HZ", default_value_t = 0.25, requires = "demo")]
+    frequency: f64,
+
+    /// Demo motion as a share of the full deflection, from 0 to 1
+    #[arg(long, default_value_t = 1.0, requires = "demo")]
+    amplitude: f64,
+
+    /// Also send the axes and buttons as OSC messages to this UDP address, overriding the config
+    #[arg(long, value_name = "HOST:PORT")]
+    osc: Option<String>,
+
+    /// Stream the axes and buttons as JSON to WebSocket clients on ws://localhost:PORT
+    #[arg(long, value_name = "PORT")]
+    ws_port: Option<u16>,
+
+    /// Serve Prometheus metrics of the feed on http://localhost:PORT/metrics
+    #[arg(long, value_name = "PORT")]
+    metrics_port: Option<u16>,
+
+    /// WebSocket frames sent per second
+    #[arg(long, value_name = "HZ", default_value = "30", value_parser = parse_interval, requires = "ws_port")]
+    ws_rate: Duration,
+
+    /// Print the log, the checks and the status events as newline-delimited JSON objects instead of text
+    #[arg(long, conflicts_with = "tui")]
+    json: bool,
+
+    /// State events printed per second with --json
+    #[arg(long, value_name = "HZ", default_value = "10", value_parser = parse_interval, requires = "json")]
+    json_rate: Duration,
+
+    /// Send at most this many vJoy updates per second
+    #[arg(long, value_name = "HZ", value_parser = parse_rate)]
+    rate: Option<f64>,
+
+    /// Stop feeding after this many seconds as if Ctrl-C was pressed, e.g. for scripted runs
+    #[arg(long, value_name = "SECONDS", value_parser = parse_duration)]
+    exit_after: Option<Duration>,
+
+    /// Write a commented config with the defaults to this file, or print it without one, and exit
+    #[arg(long, value_name = "PATH")]
+    generate_config: Option<Option<PathBuf>>,
+
+    /// Overwrite the file given to --generate-config
+    #[arg(long, requires = "generate_config")]
+    force: bool,
+
+    /// Print a config skeleton for the vJoy layout set by the vJoyConfig command in this file and exit
+    #[arg(long, value_name = "PATH")]
+    import_vjoy: Option<PathBuf>,
+
+    /// Print the settings in effect, from the config file and the flags, as toml or json and exit
+    #[arg(long, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "toml")]
+    print_config: Option<ConfigFormat>,
+
+    /// List the connected HID devices and exit
+    #[arg(long)]
+    list_devices: bool,
+
+    /// List the vJoy devices and their capabilities and exit
+    #[arg(long)]
+    list_vjoy: bool,
+
+    /// Measure the rest position and range of each axis of this unit, applied on later runs
+    #[arg(long)]
+    calibrate: bool,
+
+    /// Print the raw reports of the HID device instead of feeding vJoy
+    #[arg(long)]
+    dump: bool,
+
+    /// Apply the axis settings again each time the config file is saved, to tune them live
+    #[arg(long)]
+    watch: bool,
+
+    /// Show a live dashboard of the axes and buttons
+    #[arg(long)]
+    tui: bool,
+
+    /// Tune the axes live with commands typed on the console, e.g. set gain x 60, then save
+    #[arg(long, conflicts_with_all = ["tui", "json", "watch"])]
+    repl: bool,
+
+    /// Run in the background with a tray icon instead of the console window
+    #[cfg(windows)]
+    #[arg(long, conflicts_with_all = ["tui", "repl"])]
+    tray: bool,
+
+    /// Wait for enter before exiting on an error, keeping the console window open
+    #[arg(long)]
+    pause: bool,
+
+    /// Log more details, -v for debug and -vv for trace, RUST_LOG takes precedence
+    #[arg(short, long, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Skip the banner with the version logged at startup
+    #[arg(short, long)]
+    quiet: bool,
 }
 
-fn check_vjoy_axis(id: u32) -> Result<(), HidError> {
-    let has_x = rusty_vjoy::get_vjd_axis_exist(id, HidUsage::X);
-    let has_y = rusty_vjoy::get_vjd_axis_exist(id, HidUsage::Y);
-    let has_z = rusty_vjoy::get_vjd_axis_exist(id, HidUsage::Z);
+fn parse_vjoy_id(arg: &str) -> Result<u32, String> {
+    let id: u32 = arg
+        .parse()
+        .map_err(|_| format!("`{}` is not a valid vJoy device ID", arg))?;
 
-    let has_rx = rusty_vjoy::get_vjd_axis_exist(id, HidUsage::RX);
-    let has_ry = rusty_vjoy::get_vjd_axis_exist(id, HidUsage::RY);
-    let has_rz = rusty_vjoy::get_vjd_axis_exist(id, HidUsage::RZ);
+    vjoy::check_vjoy_id(id)
+}
 
-    let buttons = rusty_vjoy::get_vjd_button_number(id);
+fn parse_rate(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(rate) if rate > 0.0 && Duration::try_from_secs_f64(1.0 / rate).is_ok() => Ok(rate),
+        _ => Err(format!(
+            "`{}` is not a positive rate in Hz with a representable interval",
+            arg
+        )),
+    }
+}
 
-    println!(
-        "vJoy device {} capabilities;\n    Numner of buttons: {},\n    Axis X: {},\n    Axis Y: {},\n    Axis Z: {},\n    Axis RX: {},\n    Axis RY: {},\n    Axis RZ: {}",
-        id, buttons, has_x, has_y, has_z, has_rx, has_ry, has_rz
-    );
+fn parse_interval(arg: &str) -> Result<Duration, String> {
+    parse_rate(arg).map(|rate| Duration::from_secs_f64(1.0 / rate))
+}
 
-    if has_x && has_y && has_z && has_rx && has_ry && has_rz && buttons == 2 {
-        Ok(())
-    } else {
-        println!("vJoy device input do not match hardware output!");
-        Err(HidError::OpenHidDeviceError)
+fn parse_speed(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(speed) if speed.is_finite() && speed >= record::MIN_SPEED => Ok(speed),
+        _ => Err(format!(
+            "`{}` is not a speed multiplier from {}",
+            arg,
+            record::MIN_SPEED
+        )),
     }
 }
 
-fn check_vjoy_status(id: u32) -> Result<(), HidError> {
-    match rusty_vjoy::get_vjd_status(id) {
-        VJDStat::VjdStatOwned => {
-            println!("vJoy device {} is already owned by this feeder", id);
-            Ok(())
-        }
-        VJDStat::VjdStatFree => {
-            println!("vJoy device {} is free", id);
-            Ok(())
-        }
-        VJDStat::VjdStatBusy => {
-            println!(
-                "vJoy device {} is already owned by another feeder\nCannot continue\n",
-                id
-            );
-            Err(HidError::OpenHidDeviceError)
-        }
-        VJDStat::VjdStatMissing => {
-            println!(
-                "vJoy device {} is not installed or disabled\nCannot continue\n",
-                id
-            );
-            Err(HidError::OpenHidDeviceError)
-        }
-        VJDStat::VjdStatUnknown => {
-            println!("vJoy device {} general error\nCannot continue\n", id);
-            Err(HidError::OpenHidDeviceError)
-        }
+fn parse_duration(arg: &str) -> Result<Duration, String> {
+    match arg.parse::<f64>().map(Duration::try_from_secs_f64) {
+        Ok(Ok(duration)) => Ok(duration),
+        _ => Err(format!("`{}` is not a duration in seconds", arg)),
     }
 }
 
-fn acquire_vjoy_device(id: u32) -> Result<(), HidError> {
-    let status = rusty_vjoy::acquire_vjd(id);
+fn parse_pose_value(arg: &str) -> Result<(Axis, f64), String> {
+    let (name, share) = arg
+        .split_once('=')
+        .ok_or_else(|| format!("`{}` is not AXIS=SHARE, e.g. z=0.5", arg))?;
 
-    println!("vJoy device number {} acquired = {}", id, status);
+    let axis = Axis::ALL
+        .into_iter()
+        .find(|axis| axis.name().eq_ignore_ascii_case(name.trim()))
+        .ok_or_else(|| format!("`{}` is not an axis, try x, y, z, rx, ry or rz", name))?;
 
-    if status {
-        Ok(())
-    } else {
-        Err(HidError::OpenHidDeviceError)
+    match share.trim().parse::<f64>() {
+        Ok(share) if (-1.0..=1.0).contains(&share) => Ok((axis, share)),
+        _ => Err(format!("`{}` is not a share between -1 and 1", share)),
     }
 }
 
-fn find_space_navigator(api: &HidApi) -> HidResult<HidDevice> {
-    for device_info in api.device_list() {
-        if device_info.vendor_id() == VENDOR_ID && device_info.product_id() == PRODUCT_ID {
-            let dev = device_info.open_device(api)?;
-            println!("SpaceNavigator device found");
-            return Ok(dev);
-        }
-    }
+fn parse_usb_id(arg: &str) -> Result<u16, String> {
+    let result = match arg.strip_prefix("0x").or_else(|| arg.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => arg.parse(),
+    };
 
-    println!("Could not find SpaceNavigator");
-    Err(HidError::OpenHidDeviceError)
+    result.map_err(|_| format!("`{}` is not a valid USB ID (0-65535 or 0x0000-0xFFFF)", arg))
 }
 
 fn pause() {
@@ -149,134 +309,683 @@ fn pause() {
     let _ = stdin.read(&mut [0u8]).unwrap();
 }
 
-fn main() {
-    let vjoy_id = 1;
+/// Measure each axis and store the result for this unit.
+fn calibrate(
+    config: &Config,
+    vendor_id: u16,
+    product_id: Option<u16>,
+    serial: Option<&str>,
+    running: &AtomicBool,
+) -> Result<(), FeederError> {
+    let api = HidApi::new().map_err(FeederError::HidInit)?;
+    let (mut device, model) = device::find_space_navigator(&api, vendor_id, product_id, serial)?;
+    let parser = report::parser_for(
+        model.vendor_id,
+        model.product_id,
+        &config.buttons,
+        config.device.byte_order,
+    );
+
+    let calibration = match calibrate::calibrate(&mut device, parser.as_ref(), running)? {
+        Some(calibration) => calibration,
+        None => return Ok(()),
+    };
+
+    let mut calibrations = Calibrations::load().unwrap_or_else(|error| {
+        warn!("{}, starting over", error);
+        Calibrations::default()
+    });
+
+    calibrations.devices.insert(
+        calibrate::serial_of(&device),
+        DeviceCalibration {
+            vendor_id: model.vendor_id,
+            product_id: model.product_id,
+            axes: calibration,
+        },
+    );
+
+    calibrations.save()?;
+
+    Ok(())
+}
+
+/// Log the version and build, and those of vJoy, for bug reports.
+fn banner() {
+    info!(
+        "vjoy-feeder {} for {}",
+        env!("CARGO_PKG_VERSION"),
+        env!("TARGET")
+    );
+
+    #[cfg(windows)]
+    match vjoy::versions() {
+        Some((dll, driver)) => info!("vJoy DLL {:#06x}, driver {:#06x}", dll, driver),
+        None => info!("vJoy driver not enabled"),
+    }
+}
+
+/// Fail unless the vJoy driver is usable and each device fed has what the feeder needs.
+#[cfg(windows)]
+fn check_vjoy(feeder: &Feeder) -> Result<(), FeederError> {
+    let test = vjoy::self_test(&feeder.vjoy_requirements(), feeder.acquire_attempts());
+
+    if status::is_json() {
+        test.emit();
+    } else {
+        test.print();
+    }
+
+    test.into_result()
+}
+
+/// `builder` feeding the first vJoy device able to take its mapping instead of the configured ones.
+#[cfg(windows)]
+fn pick_vjoy(builder: FeederBuilder) -> Result<FeederBuilder, FeederError> {
+    vjoy::check_vjoy_enabled()?;
+
+    let feeder = builder.clone().build()?;
+    let requirements = feeder.vjoy_requirements();
+    let (_, fields, buttons) = &requirements[0];
+
+    //The device fed the rotation when split
+    let taken: Vec<u32> = requirements[feeder.vjoy_ids().len()..]
+        .iter()
+        .map(|&(id, _, _)| id)
+        .collect();
+
+    let id = vjoy::find_vjoy_device(fields, *buttons, &taken).ok_or(FeederError::NoFreeVJoy)?;
+
+    info!(
+        "Feeding vJoy device {}, the first free one fit for the mapping",
+        id
+    );
+
+    Ok(builder.vjoy_ids([id]))
+}
+
+/// Environment variable `name` parsed like the flag it stands for, `None` if unset.
+fn env_setting<T>(
+    name: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<T>, FeederError> {
+    let value = match env::var(name) {
+        Ok(value) => value,
+        Err(env::VarError::NotPresent) => return Ok(None),
+        Err(env::VarError::NotUnicode(_)) => {
+            return Err(FeederError::InvalidSettings(format!(
+                "{} is not valid unicode",
+                name
+            )))
+        }
+    };
+
+    parse(value.trim()).map(Some).map_err(|error| {
+        FeederError::InvalidSettings(format!(
+            "{}: {} (it overrides the config file, command-line flags override it)",
+            name, error
+        ))
+    })
+}
 
-    if check_vjoy_enabled().is_err() {
-        pause();
-        return;
+/// The config file settings overridden by the environment, then by the command line.
+fn apply_args(mut config: Config, args: &Args) -> Result<Config, FeederError> {
+    let ids = env_setting(ENV_VJOY_ID, |value| {
+        value
+            .split(',')
+            .map(parse_vjoy_id)
+            .collect::<Result<_, _>>()
+    })?;
+
+    if let Some(ids) = ids {
+        config.vjoy.ids = ids;
     }
 
-    if check_vjoy_versions().is_err() {
-        pause();
-        return;
+    if let Some(vendor_id) = env_setting(ENV_VENDOR_ID, parse_usb_id)? {
+        config.device.vendor_id = vendor_id;
     }
 
-    if check_vjoy_status(vjoy_id).is_err() {
-        pause();
-        return;
+    if let Some(product_id) = env_setting(ENV_PRODUCT_ID, parse_usb_id)? {
+        config.device.product_id = Some(product_id);
     }
 
-    if check_vjoy_axis(vjoy_id).is_err() {
-        pause();
-        return;
+    if let Some(serial) = env_setting(ENV_SERIAL, |value| Ok(value.to_string()))? {
+        config.device.serial = Some(serial);
     }
 
-    let api = match HidApi::new() {
-        Ok(api) => api,
-        Err(error) => {
-            println!("Error: {}", error);
-            pause();
-            return;
+    if !args.vjoy_id.is_empty() {
+        config.vjoy.ids = args.vjoy_id.clone();
+    }
+
+    let device = &mut config.device;
+
+    device.vendor_id = args.vendor_id.unwrap_or(device.vendor_id);
+    device.product_id = args.product_id.or(device.product_id);
+    device.serial = args.serial.clone().or(device.serial.take());
+    device.auto_zero_ms = args.auto_zero.unwrap_or(device.auto_zero_ms);
+    device.recenter_ms = args.recenter.unwrap_or(device.recenter_ms);
+    device.watchdog_ms = args.watchdog.unwrap_or(device.watchdog_ms);
+    device.non_blocking |= args.non_blocking;
+    device.coalesce_ms = args.coalesce.unwrap_or(device.coalesce_ms);
+
+    if let Some(host) = &args.osc {
+        config.osc = Some(OscConfig {
+            host: host.clone(),
+            ..config.osc.unwrap_or_default()
+        });
+    }
+
+    Ok(config)
+}
+
+fn run(args: &Args) -> Result<(), FeederError> {
+    match &args.generate_config {
+        Some(Some(path)) => return Ok(config::write_starter(path, args.force)?),
+        Some(None) => {
+            print!("{}", config::STARTER_CONFIG);
+            return Ok(());
         }
-    };
+        None => {}
+    }
 
-    let space_nav = match find_space_navigator(&api) {
-        Ok(space_nav) => space_nav,
-        Err(_) => {
-            pause();
-            return;
+    if let Some(path) = &args.import_vjoy {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let layout = import::parse_vjoyconfig(&text).map_err(FeederError::InvalidSettings)?;
+
+        print!("{}", import::skeleton(&layout));
+        return Ok(());
+    }
+
+    if args.list_devices {
+        let api = HidApi::new().map_err(FeederError::HidInit)?;
+        device::list_devices(&api);
+        return Ok(());
+    }
+
+    //Any vJoy call would fail without its DLL
+    #[cfg(windows)]
+    vjoy::check_vjoy_dll()?;
+
+    if args.list_vjoy {
+        #[cfg(windows)]
+        {
+            vjoy::check_vjoy_enabled()?;
+            vjoy::list_vjoy_devices();
         }
-    };
 
-    let blocking_mode = true;
+        #[cfg(not(windows))]
+        println!("No vJoy on this platform, uinput joysticks are created while feeding");
 
-    match space_nav.set_blocking_mode(blocking_mode) {
-        Ok(_) => println!("SpaceNavigator blocking mode = {}", blocking_mode),
-        Err(error) => {
-            println!("Error {}", error);
-            pause();
-            return;
+        return Ok(());
+    }
+
+    let config = apply_args(config::load(&args.config)?, args)?;
+
+    match args.print_config {
+        Some(ConfigFormat::Toml) => {
+            print!("{}", config.to_toml()?);
+            return Ok(());
+        }
+        Some(ConfigFormat::Json) => {
+            println!("{}", config.to_json());
+            return Ok(());
         }
+        None => debug!("Effective config:\n{}", config.to_toml()?),
     }
 
+    if !args.quiet {
+        banner();
+    }
+
+    let vendor_id = config.device.vendor_id;
+    let product_id = config.device.product_id;
+    let serial = config.device.serial.clone();
+
     let running = Arc::new(AtomicBool::new(true));
     let running_clone = running.clone();
 
     ctrlc::set_handler(move || {
-        rusty_vjoy::relinquish_vjd(vjoy_id);
         running_clone.store(false, Ordering::Relaxed);
-        println!("Press any buttons to exit...");
     })
     .expect("Error setting Ctrl-C handler");
 
-    if acquire_vjoy_device(vjoy_id).is_err() {
-        pause();
-        return;
-    }
-
-    let mut read_buffer = [0u8; 7]; //Other devices use different buffers???
-
-    let mut write_buffer = JoystickPosition {
-        bDevice: vjoy_id as u8, /* BYTE */
-        wThrottle: 0,           /* LONG */
-        wRudder: 0,             /* LONG */
-        wAileron: 0,            /* LONG */
-        wAxisX: 0,              /* LONG */
-        wAxisY: 0,              /* LONG */
-        wAxisZ: 0,              /* LONG */
-        wAxisXRot: 0,           /* LONG */
-        wAxisYRot: 0,           /* LONG */
-        wAxisZRot: 0,           /* LONG */
-        wSlider: 0,             /* LONG */
-        wDial: 0,               /* LONG */
-        wWheel: 0,              /* LONG */
-        wAxisVX: 0,             /* LONG */
-        wAxisVY: 0,             /* LONG */
-        wAxisVZ: 0,             /* LONG */
-        wAxisVBRX: 0,           /* LONG */
-        wAxisVBRY: 0,           /* LONG */
-        wAxisVBRZ: 0,           /* LONG */
-        lButtons: 0,            /* LONG */
-        bHats: 0,               /* DWORD */
-        bHatsEx1: 0,            /* DWORD */
-        bHatsEx2: 0,            /* DWORD */
-        bHatsEx3: 0,            /* DWORD */
-        lButtonsEx1: 0,         /* LONG */
-        lButtonsEx2: 0,         /* LONG */
-        lButtonsEx3: 0,         /* LONG */
-    };
+    if let Some(duration) = args.exit_after {
+        let running = running.clone();
+
+        //Left behind if the feeder stops first, exiting ends it
+        thread::spawn(move || {
+            thread::sleep(duration);
+
+            if running.swap(false, Ordering::Relaxed) {
+                info!("Exiting after {:?}", duration);
+            }
+        });
+    }
+
+    if args.calibrate {
+        return calibrate(&config, vendor_id, product_id, serial.as_deref(), &running);
+    }
 
-    println!("Program status nominal\nCtrl-c to exit");
+    if args.dump {
+        let api = HidApi::new().map_err(FeederError::HidInit)?;
+        let (device, _) =
+            device::find_space_navigator(&api, vendor_id, product_id, serial.as_deref())?;
 
-    while running.load(Ordering::Relaxed) {
-        space_nav.read(&mut read_buffer[..]).expect("Read Error: ");
+        device::dump_reports(&device, &running)?;
+        return Ok(());
+    }
+
+    let calibrations = Calibrations::load().unwrap_or_else(|error| {
+        warn!("{}, ignoring it", error);
+        Calibrations::default()
+    });
+
+    let mut builder = Feeder::builder()
+        .vjoy_ids(config.vjoy.ids)
+        .acquire_attempts(config.vjoy.acquire_attempts)
+        .device(vendor_id, product_id)
+        .byte_order(config.device.byte_order)
+        .axes(config.axes)
+        .buttons(config.buttons)
+        .profiles(config.profiles)
+        .gestures(config.gestures)
+        .keys(config.keys)
+        .presses(config.presses)
+        .chords(config.chords)
+        .axis_buttons(config.axis_buttons)
+        .wait(args.wait)
+        .blocking(!config.device.non_blocking)
+        .auto_zero(Duration::from_millis(config.device.auto_zero_ms))
+        .recenter(
+            Duration::from_millis(config.device.recenter_ms),
+            config.device.recenter_band,
+        )
+        .watchdog(Duration::from_millis(config.device.watchdog_ms))
+        .coalesce(Duration::from_millis(config.device.coalesce_ms))
+        .calibrations(calibrations);
+
+    if let Some(serial) = serial {
+        builder = builder.serial(serial);
+    }
 
-        match read_buffer[0] {
-            REPORT_ROTATION => {
-                write_buffer.wAxisXRot =
-                    i16::from_ne_bytes([read_buffer[1], read_buffer[2]]) as i32 * 47 + HALFMAXI16;
+    if args.watch {
+        builder = builder.watch_config(&args.config);
+    }
 
-                write_buffer.wAxisYRot =
-                    i16::from_ne_bytes([read_buffer[5], read_buffer[6]]) as i32 * 47 + HALFMAXI16;
+    if let Some(timeout) = args.wait_timeout {
+        builder = builder.wait_timeout(Duration::from_secs(timeout));
+    }
+
+    if let Some(path) = &args.record {
+        builder = builder.record(path);
+    }
 
-                write_buffer.wAxisZRot =
-                    i16::from_ne_bytes([read_buffer[3], read_buffer[4]]) as i32 * 47 + HALFMAXI16;
+    if let Some(path) = &args.replay {
+        builder = builder.replay(Replay {
+            path: path.clone(),
+            speed: args.speed,
+            looping: args.looping,
+        });
+    }
+
+    if args.demo {
+        builder = builder.demo(Waveform {
+            frequency: args.frequency,
+            amplitude: args.amplitude,
+        });
+    }
+
+    if let Some(name) = config.profile {
+        builder = builder.profile(name);
+    }
+
+    if let Some(split) = config.split {
+        builder = builder.split(split);
+    }
+
+    if let Some(osc) = config.osc {
+        builder = builder.osc(osc);
+    }
+
+    if let Some(hat) = config.hat {
+        builder = builder.hat(hat);
+    }
+
+    if let Some(rate) = args.rate {
+        builder = builder.rate(rate);
+    }
+
+    if args.once {
+        builder = builder.once(args.hold);
+    }
+
+    if !args.values.is_empty() {
+        let mut shares = [0.0; 6];
+
+        for &(axis, share) in &args.values {
+            shares[axis as usize] = share;
+        }
+
+        builder = builder.pose(shares);
+    }
+
+    #[cfg(windows)]
+    let (tray, auto_vjoy) = (args.tray, args.auto_vjoy);
+    #[cfg(not(windows))]
+    let (tray, auto_vjoy) = (false, false);
+
+    if !config.bindings.is_empty() {
+        if args.tui
+            || tray
+            || args.repl
+            || args.ws_port.is_some()
+            || args.metrics_port.is_some()
+            || args.replay.is_some()
+            || args.demo
+        {
+            return Err(FeederError::InvalidSettings(
+                "bindings feed every device, they cannot be combined with --tui, --tray, --repl, --ws-port, --metrics-port, --replay or --demo".to_string(),
+            ));
+        }
+
+        if args.serial.is_some() || !args.vjoy_id.is_empty() || auto_vjoy {
+            return Err(FeederError::InvalidSettings(
+                "bindings set the serial and vJoy device of each device, --serial, --vjoy-id and --auto-vjoy do not apply".to_string(),
+            ));
+        }
+
+        if config.apps.is_some() {
+            return Err(FeederError::InvalidSettings(
+                "bindings switch the profiles of each device on their own, [apps] does not apply"
+                    .to_string(),
+            ));
+        }
+
+        return run_bindings(builder, config.bindings, &running);
+    }
+
+    let snapshot = Arc::new(Mutex::new(Snapshot::default()));
+
+    if args.tui || tray || args.ws_port.is_some() || args.json {
+        builder = builder.snapshot(snapshot.clone()).console(!args.tui);
+    }
+
+    let metrics = Arc::new(Metrics::default());
+
+    if args.metrics_port.is_some() {
+        builder = builder.metrics(metrics.clone());
+    }
+
+    let tuning = Arc::new(Tuning::default());
+
+    if args.repl {
+        builder = builder.tuning(tuning.clone());
+    }
+
+    #[cfg(windows)]
+    if auto_vjoy {
+        builder = pick_vjoy(builder)?;
+    }
+
+    let feeder = builder.build()?;
+
+    #[cfg(windows)]
+    check_vjoy(&feeder)?;
+
+    let server = match args.ws_port {
+        Some(port) => Some(
+            ws::serve(port, args.ws_rate, snapshot.clone(), running.clone())
+                .map_err(FeederError::WebSocket)?,
+        ),
+        None => None,
+    };
+
+    let exporter = match args.metrics_port {
+        Some(port) => {
+            Some(metrics::serve(port, metrics, running.clone()).map_err(FeederError::Metrics)?)
+        }
+        None => None,
+    };
+
+    #[cfg(windows)]
+    let focus = match config.apps {
+        Some(apps) => {
+            let names = feeder.profile().names();
+
+            if let Some(name) = apps
+                .profiles
+                .values()
+                .chain(&apps.fallback)
+                .find(|name| !names.contains(name))
+            {
+                return Err(FeederError::InvalidSettings(format!(
+                    "no profile called {} for [apps]",
+                    name
+                )));
             }
-            REPORT_TRANSLATION => {
-                write_buffer.wAxisX =
-                    i16::from_ne_bytes([read_buffer[1], read_buffer[2]]) as i32 * 47 + HALFMAXI16;
 
-                write_buffer.wAxisY =
-                    i16::from_ne_bytes([read_buffer[5], read_buffer[6]]) as i32 * 47 + HALFMAXI16;
+            Some(vjoy_feeder::focus::watch(
+                apps,
+                feeder.profile().clone(),
+                running.clone(),
+            ))
+        }
+        None => None,
+    };
+
+    #[cfg(not(windows))]
+    if config.apps.is_some() {
+        warn!("[apps] follows the application in focus on Windows only, ignoring it");
+    }
+
+    if args.repl {
+        repl::start(
+            Console {
+                path: args.config.clone(),
+                profile: feeder.profile().clone(),
+                trims: feeder.trims().clone(),
+                tuning,
+            },
+            running.clone(),
+        );
+    }
+
+    let stream = args
+        .json
+        .then(|| status::stream(args.json_rate, snapshot.clone(), running.clone()));
+
+    if !args.tui && !tray {
+        let result = feeder.run(running.clone());
+
+        running.store(false, Ordering::Relaxed);
+
+        //The feed went on without the stream, its result still decides the exit code
+        if server.is_some_and(|server| server.join().is_err()) {
+            error!("WebSocket thread panicked");
+        }
 
-                write_buffer.wAxisZ =
-                    i16::from_ne_bytes([read_buffer[3], read_buffer[4]]) as i32 * 47 + HALFMAXI16;
+        if let Some(stream) = stream {
+            stream.join().expect("JSON status thread panicked");
+        }
+
+        if let Some(exporter) = exporter {
+            exporter.join().expect("Metrics thread panicked");
+        }
+
+        #[cfg(windows)]
+        if let Some(focus) = focus {
+            focus.join().expect("Focus thread panicked");
+        }
+
+        return result;
+    }
+
+    let trims = feeder.trims().clone();
+    let profile = feeder.profile().clone();
+    #[cfg(windows)]
+    let vjoy_ids = feeder.vjoy_ids().to_vec();
+    let feed_running = running.clone();
+
+    let feed = thread::spawn(move || {
+        let result = feeder.run(feed_running.clone());
+        feed_running.store(false, Ordering::Relaxed);
+        result
+    });
+
+    #[cfg(windows)]
+    let shown = if tray {
+        vjoy_feeder::tray::run(&snapshot, &profile, &vjoy_ids, &running)
+    } else {
+        tui::run(&snapshot, &trims, &profile, &args.config, &running)
+    };
+
+    #[cfg(not(windows))]
+    let shown = tui::run(&snapshot, &trims, &profile, &args.config, &running);
+
+    running.store(false, Ordering::Relaxed);
+
+    let result = feed.join().expect("Feeder thread panicked");
+
+    if server.is_some_and(|server| server.join().is_err()) {
+        error!("WebSocket thread panicked");
+    }
+
+    if let Some(stream) = stream {
+        stream.join().expect("JSON status thread panicked");
+    }
+
+    if let Some(exporter) = exporter {
+        exporter.join().expect("Metrics thread panicked");
+    }
+
+    #[cfg(windows)]
+    if let Some(focus) = focus {
+        focus.join().expect("Focus thread panicked");
+    }
+
+    if let Err(error) = shown {
+        error!("Front-end error: {}", error);
+    }
+
+    result
+}
+
+/// Feed each binding from its own thread until Ctrl-C, a failing one stops the others.
+fn run_bindings(
+    builder: FeederBuilder,
+    bindings: Vec<BindingConfig>,
+    running: &Arc<AtomicBool>,
+) -> Result<(), FeederError> {
+    let mut fed = Vec::new();
+
+    for binding in &bindings {
+        if let Some(id) = binding.vjoy_ids.iter().find(|id| fed.contains(*id)) {
+            return Err(FeederError::InvalidSettings(format!(
+                "vJoy device {} is in several bindings",
+                id
+            )));
+        }
+
+        fed.extend(&binding.vjoy_ids);
+    }
+
+    let feeders = bindings
+        .into_iter()
+        .map(|binding| {
+            builder
+                .clone()
+                .serial(binding.serial)
+                .vjoy_ids(binding.vjoy_ids)
+                .build()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    #[cfg(windows)]
+    for feeder in &feeders {
+        check_vjoy(feeder)?;
+    }
+
+    let feeds: Vec<_> = feeders
+        .into_iter()
+        .map(|feeder| {
+            let running = running.clone();
+
+            thread::spawn(move || {
+                let result = feeder.run(running.clone());
+
+                if result.is_err() {
+                    running.store(false, Ordering::Relaxed);
+                }
+
+                result
+            })
+        })
+        .collect();
+
+    let mut result = Ok(());
+
+    for feed in feeds {
+        if let Err(error) = feed.join().expect("Feeder thread panicked") {
+            match result {
+                Ok(()) => result = Err(error),
+                Err(_) => error!("{}", error),
             }
-            REPORT_BUTTONS => write_buffer.lButtons = read_buffer[1] as i32,
-            _ => {} //Otherwise do nothing
         }
+    }
+
+    result
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+
+    let level = match args.verbose {
+        0 => "info",
+        1 => "debug",
+        _ => "trace",
+    };
 
-        rusty_vjoy::update_vjd(vjoy_id, &mut write_buffer);
+    let mut logger =
+        env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(level));
+
+    if args.json {
+        status::set_json(true);
+
+        //Log records become events too, on stdout with the others
+        logger
+            .target(env_logger::Target::Stdout)
+            .format(|buf, record| {
+                let fields = json!({
+                    "level": record.level().as_str().to_lowercase(),
+                    "target": record.target(),
+                    "message": record.args().to_string(),
+                });
+
+                writeln!(buf, "{}", status::event("log", fields))
+            });
+    } else {
+        logger.format_timestamp(None);
+    }
+
+    logger.init();
+
+    match run(&args) {
+        Ok(()) => {
+            status::emit("exit", json!({ "code": 0 }));
+            ExitCode::SUCCESS
+        }
+        Err(error) => {
+            error!("{}", error);
+            status::emit(
+                "exit",
+                json!({ "code": error.exit_code(), "error": error.to_string() }),
+            );
+
+            if args.pause {
+                pause();
+            }
+
+            ExitCode::from(error.exit_code())
+        }
     }
 }
@@ -0,0 +1,260 @@
+use log::{debug, info, warn};
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::axis::Axis;
+
+//How often the listener checks for connections and `running`
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100);
+
+//Longest a client may take to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+//Span the report rate is averaged over
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Counters and gauges of the feed loop, updated without locking.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    pub reports: AtomicU64,
+    pub updates_sent: AtomicU64,
+    pub updates_skipped: AtomicU64,
+    pub reconnects: AtomicU64,
+    pub errors: AtomicU64,
+    /// Value fed to each axis
+    pub axes: [AtomicI32; 6],
+}
+
+impl Metrics {
+    /// Prometheus text exposition, with `rate` reports per second.
+    fn render(&self, rate: f64) -> String {
+        let mut text = String::new();
+
+        let counters = [
+            ("reports", "HID reports read", &self.reports),
+            ("updates_sent", "vJoy updates sent", &self.updates_sent),
+            (
+                "updates_skipped",
+                "vJoy updates skipped as unchanged",
+                &self.updates_skipped,
+            ),
+            ("reconnects", "HID device reconnections", &self.reconnects),
+            ("errors", "HID device errors", &self.errors),
+        ];
+
+        for (name, help, counter) in counters {
+            let _ = writeln!(text, "# HELP vjoy_feeder_{}_total {}", name, help);
+            let _ = writeln!(text, "# TYPE vjoy_feeder_{}_total counter", name);
+            let _ = writeln!(
+                text,
+                "vjoy_feeder_{}_total {}",
+                name,
+                counter.load(Ordering::Relaxed)
+            );
+        }
+
+        let _ = writeln!(
+            text,
+            "# HELP vjoy_feeder_reports_per_second HID reports read per second over the last {} s",
+            RATE_WINDOW.as_secs()
+        );
+        let _ = writeln!(text, "# TYPE vjoy_feeder_reports_per_second gauge");
+        let _ = writeln!(text, "vjoy_feeder_reports_per_second {:.3}", rate);
+
+        let _ = writeln!(text, "# HELP vjoy_feeder_axis Value fed to each axis");
+        let _ = writeln!(text, "# TYPE vjoy_feeder_axis gauge");
+
+        for axis in Axis::ALL {
+            let _ = writeln!(
+                text,
+                "vjoy_feeder_axis{{axis=\"{}\"}} {}",
+                axis.name().to_lowercase(),
+                self.axes[axis as usize].load(Ordering::Relaxed)
+            );
+        }
+
+        text
+    }
+}
+
+/// Report counts over the last `RATE_WINDOW`, sampled by the listener so scrapes don't disturb each other.
+#[derive(Debug)]
+struct RateWindow {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateWindow {
+    fn new(now: Instant, reports: u64) -> Self {
+        Self {
+            samples: VecDeque::from([(now, reports)]),
+        }
+    }
+
+    /// Add a sample at most every `ACCEPT_INTERVAL`, keeping just enough older ones to span the window.
+    fn sample(&mut self, now: Instant, reports: u64) {
+        if self
+            .samples
+            .back()
+            .is_some_and(|&(time, _)| now - time < ACCEPT_INTERVAL)
+        {
+            return;
+        }
+
+        self.samples.push_back((now, reports));
+
+        while self.samples.len() > 2 && now - self.samples[1].0 >= RATE_WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Reports per second between the oldest and the newest sample.
+    fn rate(&self) -> f64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(&(start, first)), Some(&(end, last))) if end > start => {
+                last.saturating_sub(first) as f64 / (end - start).as_secs_f64()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Serve `metrics` in the Prometheus text format on `http://localhost:port/metrics` until `running` is cleared.
+pub fn serve(
+    port: u16,
+    metrics: Arc<Metrics>,
+    running: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    listener.set_nonblocking(true)?;
+
+    info!("Serving metrics on http://localhost:{}/metrics", port);
+
+    let window = Arc::new(Mutex::new(RateWindow::new(
+        Instant::now(),
+        metrics.reports.load(Ordering::Relaxed),
+    )));
+
+    Ok(thread::spawn(move || {
+        while running.load(Ordering::Relaxed) {
+            window
+                .lock()
+                .unwrap()
+                .sample(Instant::now(), metrics.reports.load(Ordering::Relaxed));
+
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    debug!("Metrics client {} connected", address);
+
+                    let metrics = metrics.clone();
+                    let rate = window.lock().unwrap().rate();
+
+                    thread::spawn(move || {
+                        if let Err(error) = serve_client(stream, &metrics, rate) {
+                            debug!("Metrics request failed: {}", error);
+                        }
+                    });
+                }
+                Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(ACCEPT_INTERVAL)
+                }
+                Err(error) => {
+                    warn!("Metrics connection failed: {}", error);
+                    thread::sleep(ACCEPT_INTERVAL);
+                }
+            }
+        }
+    }))
+}
+
+fn serve_client(mut stream: TcpStream, metrics: &Metrics, rate: f64) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+
+    let mut lines = BufReader::new(&stream).lines();
+
+    let request = lines.next().transpose()?.unwrap_or_default();
+
+    //The headers are of no use
+    for line in lines {
+        if line?.is_empty() {
+            break;
+        }
+    }
+
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+
+    let (status, body) = if path == "/metrics" || path == "/" {
+        ("200 OK", metrics.render(rate))
+    } else {
+        ("404 Not Found", "Not found, try /metrics\n".to_string())
+    };
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_exposes_the_counters_rate_and_axes() {
+        let metrics = Metrics::default();
+        metrics.reports.store(120, Ordering::Relaxed);
+        metrics.errors.store(2, Ordering::Relaxed);
+        metrics.axes[Axis::RZ as usize].store(16383, Ordering::Relaxed);
+
+        let text = metrics.render(60.0);
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert!(lines.contains(&"# TYPE vjoy_feeder_reports_total counter"));
+        assert!(lines.contains(&"vjoy_feeder_reports_total 120"));
+        assert!(lines.contains(&"vjoy_feeder_errors_total 2"));
+        assert!(lines.contains(&"vjoy_feeder_updates_sent_total 0"));
+        assert!(lines.contains(&"vjoy_feeder_reports_per_second 60.000"));
+        assert!(lines.contains(&"vjoy_feeder_axis{axis=\"rz\"} 16383"));
+        assert!(text.ends_with('\n'));
+    }
+
+    #[test]
+    fn the_rate_spans_the_window_whoever_asks() {
+        let start = Instant::now();
+        let mut window = RateWindow::new(start, 0);
+
+        for second in 1..=10 {
+            window.sample(start + Duration::from_secs(second), second * 100);
+        }
+
+        //Asking twice, as two scrapers would, gives the same rate
+        assert_eq!(window.rate(), 100.0);
+        assert_eq!(window.rate(), 100.0);
+        assert_eq!(
+            window.samples.front().unwrap().0,
+            start + Duration::from_secs(5)
+        );
+    }
+
+    #[test]
+    fn samples_closer_than_the_accept_interval_are_skipped() {
+        let start = Instant::now();
+        let mut window = RateWindow::new(start, 0);
+
+        window.sample(start + ACCEPT_INTERVAL / 2, 10);
+        assert_eq!(window.rate(), 0.0);
+
+        window.sample(start + Duration::from_secs(2), 100);
+        assert_eq!(window.rate(), 50.0);
+    }
+}
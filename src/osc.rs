@@ -0,0 +1,205 @@
+use log::debug;
+
+use std::io;
+use std::net::UdpSocket;
+use std::time::{Duration, Instant};
+
+use crate::axis::Axis;
+use crate::config::OscConfig;
+
+//Placeholders of the address patterns
+const AXIS_PLACEHOLDER: &str = "{axis}";
+const BUTTON_PLACEHOLDER: &str = "{button}";
+
+/// OSC 1.0 message argument.
+#[derive(Debug, Clone, Copy)]
+enum Argument {
+    Float(f32),
+    Int(i32),
+}
+
+/// Sends the axes as floats from -1 to 1 and the buttons as 0 or 1 ints over UDP.
+///
+/// Only the values changed since the last frame are sent, at most `rate` frames per second.
+pub struct OscOutput {
+    socket: UdpSocket,
+    axis_address: String,
+    button_address: String,
+    interval: Duration,
+    next_frame: Instant,
+    axes: [Option<f32>; 6],
+    buttons: Option<u64>,
+    button_count: u32,
+}
+
+impl OscOutput {
+    pub fn new(config: &OscConfig, button_count: u32) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(&config.host)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            axis_address: config.axis_address.clone(),
+            button_address: config.button_address.clone(),
+            interval: Duration::from_secs_f64(1.0 / config.rate),
+            next_frame: Instant::now(),
+            axes: [None; 6],
+            buttons: None,
+            button_count,
+        })
+    }
+
+    /// Send the changes in `axes`, in `Axis::ALL` order, and `buttons` if a frame is due.
+    pub fn update(&mut self, axes: [Option<f32>; 6], buttons: u64, now: Instant) {
+        if now < self.next_frame {
+            return;
+        }
+
+        self.next_frame = now + self.interval;
+
+        for axis in Axis::ALL {
+            let value = axes[axis as usize];
+
+            if value.is_none() || value == self.axes[axis as usize] {
+                continue;
+            }
+
+            self.axes[axis as usize] = value;
+
+            let address = self
+                .axis_address
+                .replace(AXIS_PLACEHOLDER, &axis.name().to_lowercase());
+
+            self.send(&address, Argument::Float(value.unwrap()));
+        }
+
+        let changed = match self.buttons {
+            Some(last) => last ^ buttons,
+            None => u64::MAX,
+        };
+
+        self.buttons = Some(buttons);
+
+        for button in 0..self.button_count.min(64) {
+            if changed & 1 << button == 0 {
+                continue;
+            }
+
+            let address = self
+                .button_address
+                .replace(BUTTON_PLACEHOLDER, &(button + 1).to_string());
+
+            self.send(&address, Argument::Int((buttons >> button & 1) as i32));
+        }
+    }
+
+    fn send(&self, address: &str, argument: Argument) {
+        //Nobody may be listening yet, UDP drops are fine
+        if let Err(error) = self.socket.send(&encode(address, argument)) {
+            debug!("OSC message to {} not sent: {}", address, error);
+        }
+    }
+}
+
+/// OSC message with a single argument.
+fn encode(address: &str, argument: Argument) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(address.len() + 12);
+
+    push_string(&mut packet, address);
+
+    match argument {
+        Argument::Float(value) => {
+            push_string(&mut packet, ",f");
+            packet.extend_from_slice(&value.to_be_bytes());
+        }
+        Argument::Int(value) => {
+            push_string(&mut packet, ",i");
+            packet.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    packet
+}
+
+/// OSC string, null terminated and padded to a multiple of 4 bytes.
+fn push_string(packet: &mut Vec<u8>, text: &str) {
+    packet.extend_from_slice(text.as_bytes());
+    packet.resize((packet.len() + 4) & !3, 0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_float_message_matches_the_osc_spec_example() {
+        let packet = encode("/oscillator/4/frequency", Argument::Float(440.0));
+
+        assert_eq!(
+            packet,
+            b"/oscillator/4/frequency\0,f\0\0\x43\xdc\0\0".as_slice()
+        );
+    }
+
+    #[test]
+    fn an_int_message_has_the_i_type_tag() {
+        assert_eq!(
+            encode("/b/1", Argument::Int(1)),
+            b"/b/1\0\0\0\0,i\0\0\0\0\0\x01".as_slice()
+        );
+        assert_eq!(
+            encode("/b", Argument::Int(-2)),
+            b"/b\0\0,i\0\0\xff\xff\xff\xfe".as_slice()
+        );
+    }
+
+    #[test]
+    fn strings_are_null_terminated_and_padded_to_4_bytes() {
+        for (text, padded) in [("", 4), ("/", 4), ("/ab", 4), ("/abc", 8), ("/abcdef", 8)] {
+            let mut packet = Vec::new();
+            push_string(&mut packet, text);
+
+            assert_eq!(packet.len(), padded, "{:?}", text);
+            assert!(packet[text.len()..].iter().all(|&byte| byte == 0));
+        }
+    }
+
+    #[test]
+    fn only_changed_values_are_sent() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let config = OscConfig {
+            host: receiver.local_addr().unwrap().to_string(),
+            ..OscConfig::default()
+        };
+        let mut output = OscOutput::new(&config, 2).unwrap();
+        let now = Instant::now();
+
+        output.update([Some(0.5), None, None, None, None, None], 0b10, now);
+
+        let mut buf = [0u8; 64];
+        let mut received = Vec::new();
+
+        for _ in 0..3 {
+            let len = receiver.recv(&mut buf).unwrap();
+            received.push(buf[..len].to_vec());
+        }
+
+        assert_eq!(received[0], encode("/vjoy/axis/x", Argument::Float(0.5)));
+        assert_eq!(received[1], encode("/vjoy/button/1", Argument::Int(0)));
+        assert_eq!(received[2], encode("/vjoy/button/2", Argument::Int(1)));
+
+        //Nothing changed a frame later
+        output.update(
+            [Some(0.5), None, None, None, None, None],
+            0b10,
+            now + Duration::from_secs(1),
+        );
+        receiver.set_nonblocking(true).unwrap();
+        assert!(receiver.recv(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,302 @@
+use std::time::{Duration, Instant};
+
+use crate::buttons;
+use crate::config::{PressAction, PressConfig};
+
+//How long a tap or double press holds its vJoy button, so games polling it see it
+const PULSE: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PressKind {
+    Tap,
+    Long,
+    Double,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum State {
+    Idle,
+    /// Down since then, `second` for the second press of a double press
+    Down {
+        since: Instant,
+        second: bool,
+    },
+    /// Held past the long press time
+    Long,
+    /// Released then, waiting for a second press
+    Released {
+        at: Instant,
+    },
+}
+
+/// Tells taps, long presses and double presses of one device button apart.
+#[derive(Debug, Clone)]
+pub struct PressButton {
+    /// Bit of the device button
+    pub button: u64,
+    tap: Option<PressAction>,
+    long: Option<PressAction>,
+    double: Option<PressAction>,
+    long_time: Duration,
+    double_time: Duration,
+    state: State,
+    /// Bit of the vJoy button pulsed by the last press, and until when
+    pulse: Option<(u64, Instant)>,
+}
+
+impl PressButton {
+    pub fn new(config: &PressConfig) -> Self {
+        Self {
+            button: buttons::button_mask(&[config.button]),
+            tap: config.tap,
+            long: config.long,
+            double: config.double,
+            long_time: Duration::from_millis(config.long_ms),
+            double_time: Duration::from_millis(config.double_ms),
+            state: State::Idle,
+            pulse: None,
+        }
+    }
+
+    /// Follow whether the button is `down` at `now`, the press just recognized if it has an action.
+    pub fn update(&mut self, down: bool, now: Instant) -> Option<PressKind> {
+        match self.state {
+            State::Idle => {
+                if down {
+                    self.state = State::Down {
+                        since: now,
+                        second: false,
+                    };
+                }
+
+                None
+            }
+            State::Down { since, second } => {
+                if !down {
+                    self.state = State::Idle;
+
+                    //A double press ends with its second press
+                    if second {
+                        return None;
+                    }
+
+                    if self.double.is_some() {
+                        self.state = State::Released { at: now };
+                        return None;
+                    }
+
+                    return self.fire(PressKind::Tap, now);
+                }
+
+                if !second && self.long.is_some() && now >= since + self.long_time {
+                    self.state = State::Long;
+                    return self.fire(PressKind::Long, now);
+                }
+
+                None
+            }
+            State::Long => {
+                if !down {
+                    self.state = State::Idle;
+                }
+
+                None
+            }
+            State::Released { at } => {
+                if down {
+                    self.state = State::Down {
+                        since: now,
+                        second: true,
+                    };
+
+                    return self.fire(PressKind::Double, now);
+                }
+
+                if now >= at + self.double_time {
+                    self.state = State::Idle;
+                    return self.fire(PressKind::Tap, now);
+                }
+
+                None
+            }
+        }
+    }
+
+    pub fn action(&self, kind: PressKind) -> Option<PressAction> {
+        match kind {
+            PressKind::Tap => self.tap,
+            PressKind::Long => self.long,
+            PressKind::Double => self.double,
+        }
+    }
+
+    /// vJoy buttons held at `now`, by a long press or a pulse.
+    pub fn buttons(&self, now: Instant) -> u64 {
+        let long = match (self.state, self.long) {
+            (State::Long, Some(PressAction::Button(button))) => buttons::button_mask(&[button]),
+            _ => 0,
+        };
+
+        let pulse = match self.pulse {
+            Some((bit, until)) if now < until => bit,
+            _ => 0,
+        };
+
+        long | pulse
+    }
+
+    /// When the next press may be recognized or a pulse ends without the button changing.
+    pub fn deadline(&self) -> Option<Instant> {
+        let state = match self.state {
+            State::Down {
+                since,
+                second: false,
+            } if self.long.is_some() => Some(since + self.long_time),
+            State::Released { at } => Some(at + self.double_time),
+            _ => None,
+        };
+
+        let pulse = self.pulse.map(|(_, until)| until);
+
+        state.into_iter().chain(pulse).min()
+    }
+
+    pub fn reset(&mut self) {
+        self.state = State::Idle;
+        self.pulse = None;
+    }
+
+    fn fire(&mut self, kind: PressKind, now: Instant) -> Option<PressKind> {
+        let action = self.action(kind)?;
+
+        //A long press holds its button instead, see `buttons`
+        if let PressAction::Button(button) = action {
+            if kind != PressKind::Long {
+                self.pulse = Some((buttons::button_mask(&[button]), now + PULSE));
+            }
+        }
+
+        Some(kind)
+    }
+}
+
+/// Highest vJoy button pressed by `presses`, 0 if none.
+pub fn button_count(presses: &[PressConfig]) -> u32 {
+    presses
+        .iter()
+        .flat_map(|press| [press.tap, press.long, press.double])
+        .filter_map(|action| match action {
+            Some(PressAction::Button(button)) => Some(button),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    //Button 1 tapped for 5, held long for 6 and pressed twice for 7
+    fn press() -> PressButton {
+        PressButton::new(&PressConfig {
+            button: 1,
+            tap: Some(PressAction::Button(5)),
+            long: Some(PressAction::Button(6)),
+            double: Some(PressAction::Button(7)),
+            long_ms: 500,
+            double_ms: 300,
+        })
+    }
+
+    #[test]
+    fn a_tap_fires_once_the_double_press_time_is_over() {
+        let start = Instant::now();
+        let mut press = press();
+
+        assert_eq!(press.update(true, start), None);
+        assert_eq!(press.update(false, start + ms(50)), None);
+        assert_eq!(press.deadline(), Some(start + ms(350)));
+        assert_eq!(press.update(false, start + ms(349)), None);
+        assert_eq!(press.update(false, start + ms(350)), Some(PressKind::Tap));
+        assert_eq!(press.update(false, start + ms(400)), None);
+    }
+
+    #[test]
+    fn a_tap_fires_on_release_without_a_double_press_action() {
+        let start = Instant::now();
+        let mut press = PressButton::new(&PressConfig {
+            button: 1,
+            tap: Some(PressAction::Button(5)),
+            ..PressConfig::default()
+        });
+
+        press.update(true, start);
+        assert_eq!(press.deadline(), None);
+        assert_eq!(press.update(false, start + ms(50)), Some(PressKind::Tap));
+    }
+
+    #[test]
+    fn a_long_press_holds_its_button_and_suppresses_the_tap() {
+        let start = Instant::now();
+        let mut press = press();
+
+        press.update(true, start);
+        assert_eq!(press.deadline(), Some(start + ms(500)));
+        assert_eq!(press.update(true, start + ms(499)), None);
+        assert_eq!(press.update(true, start + ms(500)), Some(PressKind::Long));
+        assert_eq!(press.buttons(start + ms(2000)), 1 << 5);
+
+        //Released, neither a tap nor a double press waits
+        assert_eq!(press.update(false, start + ms(2000)), None);
+        assert_eq!(press.buttons(start + ms(2000)), 0);
+        assert_eq!(press.deadline(), None);
+        assert_eq!(press.update(false, start + ms(3000)), None);
+    }
+
+    #[test]
+    fn a_second_press_in_time_is_a_double_press() {
+        let start = Instant::now();
+        let mut press = press();
+
+        press.update(true, start);
+        press.update(false, start + ms(50));
+        assert_eq!(press.update(true, start + ms(200)), Some(PressKind::Double));
+
+        //Held on, the second press never turns long
+        assert_eq!(press.update(true, start + ms(1000)), None);
+        assert_eq!(press.update(false, start + ms(1100)), None);
+        assert_eq!(press.update(false, start + ms(2000)), None);
+    }
+
+    #[test]
+    fn taps_and_double_presses_pulse_their_button() {
+        let start = Instant::now();
+        let mut press = press();
+
+        press.update(true, start);
+        press.update(false, start + ms(50));
+        press.update(true, start + ms(100));
+
+        assert_eq!(press.buttons(start + ms(100)), 1 << 6);
+        assert_eq!(press.deadline(), Some(start + ms(200)));
+        assert_eq!(press.buttons(start + ms(199)), 1 << 6);
+        assert_eq!(press.buttons(start + ms(200)), 0);
+    }
+
+    #[test]
+    fn reset_forgets_a_press_in_progress() {
+        let start = Instant::now();
+        let mut press = press();
+
+        press.update(true, start);
+        press.reset();
+
+        assert_eq!(press.update(false, start + ms(600)), None);
+        assert_eq!(press.deadline(), None);
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, Instant};
+
+/// Measures the rest position again once the puck has sat untouched for a while, against drift.
+///
+/// Any axis straying more than `band` from its rest restarts the wait.
+#[derive(Debug, Clone)]
+pub struct IdleRecenter {
+    band: i32,
+    idle: Duration,
+    /// Start of the current idle stretch
+    since: Option<Instant>,
+    sums: [i64; 6],
+    samples: i64,
+}
+
+impl IdleRecenter {
+    pub fn new(band: u16, idle: Duration) -> Self {
+        Self {
+            band: band as i32,
+            idle,
+            since: None,
+            sums: [0; 6],
+            samples: 0,
+        }
+    }
+
+    /// Follow the deflections from rest, their mean once they stayed within the band for the idle time.
+    pub fn update(&mut self, deflections: [i32; 6], now: Instant) -> Option<[i32; 6]> {
+        if deflections
+            .iter()
+            .any(|deflection| deflection.abs() > self.band)
+        {
+            self.reset();
+            return None;
+        }
+
+        let since = *self.since.get_or_insert(now);
+
+        for (sum, deflection) in self.sums.iter_mut().zip(deflections) {
+            *sum += deflection as i64;
+        }
+
+        self.samples += 1;
+
+        if now < since + self.idle {
+            return None;
+        }
+
+        let samples = self.samples;
+        let drift = self.sums.map(|sum| (sum / samples) as i32);
+
+        //Still idle, the next measure starts afresh
+        self.since = Some(now);
+        self.sums = [0; 6];
+        self.samples = 0;
+
+        Some(drift)
+    }
+
+    pub fn reset(&mut self) {
+        self.since = None;
+        self.sums = [0; 6];
+        self.samples = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn the_mean_drift_comes_after_the_idle_time() {
+        let start = Instant::now();
+        let mut recenter = IdleRecenter::new(10, ms(1000));
+
+        assert_eq!(recenter.update([2, 0, 0, 0, 0, -4], start), None);
+        assert_eq!(recenter.update([4, 0, 0, 0, 0, -4], start + ms(500)), None);
+        assert_eq!(
+            recenter.update([6, 0, 0, 0, 0, -4], start + ms(1000)),
+            Some([4, 0, 0, 0, 0, -4])
+        );
+    }
+
+    #[test]
+    fn leaving_the_band_restarts_the_wait() {
+        let start = Instant::now();
+        let mut recenter = IdleRecenter::new(10, ms(1000));
+
+        recenter.update([0; 6], start);
+        assert_eq!(recenter.update([0, 11, 0, 0, 0, 0], start + ms(500)), None);
+
+        //The idle stretch starts again from here, without the samples before
+        assert_eq!(recenter.update([8; 6], start + ms(600)), None);
+        assert_eq!(recenter.update([8; 6], start + ms(1500)), None);
+        assert_eq!(recenter.update([8; 6], start + ms(1600)), Some([8; 6]));
+    }
+
+    #[test]
+    fn a_recenter_starts_the_next_measure() {
+        let start = Instant::now();
+        let mut recenter = IdleRecenter::new(10, ms(1000));
+
+        recenter.update([5; 6], start);
+        assert_eq!(recenter.update([5; 6], start + ms(1000)), Some([5; 6]));
+
+        //Measured afresh over a full idle time
+        assert_eq!(recenter.update([1; 6], start + ms(1500)), None);
+        assert_eq!(recenter.update([1; 6], start + ms(2000)), Some([1; 6]));
+    }
+}
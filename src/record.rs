@@ -0,0 +1,219 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+//Start of every recording, followed by the records
+pub const MAGIC: &[u8; 8] = b"VJFREC1\n";
+
+/// Slowest replay speed, keeping the delay of any record representable.
+pub const MIN_SPEED: f64 = 0.001;
+
+//How often buffered records are written out, bounding what a crash loses
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes raw HID reports to a recording.
+///
+/// Each record is the time since the recording started in microseconds (u64),
+/// the report length (u16), then the report itself starting with its report ID,
+/// integers being little endian.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    last_flush: Instant,
+}
+
+impl Recorder {
+    /// Start a recording at `path`, replacing any existing file.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(MAGIC)?;
+
+        let now = Instant::now();
+
+        Ok(Self {
+            writer,
+            start: now,
+            last_flush: now,
+        })
+    }
+
+    pub fn record(&mut self, report: &[u8]) -> io::Result<()> {
+        let now = Instant::now();
+        let time = (now - self.start).as_micros() as u64;
+        let len = report.len().min(u16::MAX as usize);
+
+        self.writer.write_all(&time.to_le_bytes())?;
+        self.writer.write_all(&(len as u16).to_le_bytes())?;
+        self.writer.write_all(&report[..len])?;
+
+        if now - self.last_flush >= FLUSH_INTERVAL {
+            self.writer.flush()?;
+            self.last_flush = now;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a recording back, pacing the reports as they were recorded.
+pub struct Player {
+    reader: BufReader<File>,
+    speed: f64,
+    looping: bool,
+    start: Instant,
+    first: Option<u64>,
+    next: Option<(u64, Vec<u8>)>,
+}
+
+impl Player {
+    /// Replay `path` at `speed` times the recorded pace, from the start again once done if `looping`.
+    pub fn open(path: &Path, speed: f64, looping: bool) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0u8; MAGIC.len()];
+
+        reader.read_exact(&mut magic)?;
+
+        if &magic != MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a vjoy-feeder recording",
+            ));
+        }
+
+        Ok(Self {
+            reader,
+            speed,
+            looping,
+            start: Instant::now(),
+            first: None,
+            next: None,
+        })
+    }
+
+    /// Copy the next report into `buf` once it is due, like `HidDevice::read_timeout`.
+    ///
+    /// Returns `None` at the end of the recording.
+    pub fn read_timeout(&mut self, buf: &mut [u8], timeout_ms: i32) -> io::Result<Option<usize>> {
+        let (time, report) = match self.next.take() {
+            Some(next) => next,
+            None => match self.read_record()? {
+                Some(next) => next,
+                None if self.looping => {
+                    self.rewind()?;
+
+                    match self.read_record()? {
+                        Some(next) => next,
+                        None => return Ok(None),
+                    }
+                }
+                None => return Ok(None),
+            },
+        };
+
+        let first = *self.first.get_or_insert(time);
+
+        //A record older than the first one is replayed at once
+        let elapsed = time.saturating_sub(first);
+        let due = Duration::try_from_secs_f64(elapsed as f64 / 1_000_000.0 / self.speed)
+            .ok()
+            .and_then(|delay| self.start.checked_add(delay))
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "record time out of range")
+            })?;
+        let wait = due.saturating_duration_since(Instant::now());
+        let timeout = Duration::from_millis(timeout_ms.max(0) as u64);
+
+        if wait > timeout {
+            thread::sleep(timeout);
+            self.next = Some((time, report));
+            return Ok(Some(0));
+        }
+
+        thread::sleep(wait);
+
+        let len = report.len().min(buf.len());
+        buf[..len].copy_from_slice(&report[..len]);
+
+        Ok(Some(len))
+    }
+
+    /// Next record, `None` at the end or on a record cut short by a crash.
+    fn read_record(&mut self) -> io::Result<Option<(u64, Vec<u8>)>> {
+        let mut header = [0u8; 10];
+
+        let record = self.reader.read_exact(&mut header).and_then(|()| {
+            let mut report = vec![0u8; u16::from_le_bytes([header[8], header[9]]) as usize];
+            self.reader.read_exact(&mut report)?;
+
+            Ok(report)
+        });
+
+        match record {
+            Ok(report) => {
+                let time = u64::from_le_bytes(header[..8].try_into().unwrap());
+                Ok(Some((time, report)))
+            }
+            Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn rewind(&mut self) -> io::Result<()> {
+        self.reader.seek(SeekFrom::Start(MAGIC.len() as u64))?;
+        self.start = Instant::now();
+        self.first = None;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn recording(name: &str, records: &[(u64, &[u8])]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("vjoy-feeder-{}.rec", name));
+        let mut file = File::create(&path).unwrap();
+
+        file.write_all(MAGIC).unwrap();
+
+        for (time, report) in records {
+            file.write_all(&time.to_le_bytes()).unwrap();
+            file.write_all(&(report.len() as u16).to_le_bytes())
+                .unwrap();
+            file.write_all(report).unwrap();
+        }
+
+        path
+    }
+
+    #[test]
+    fn a_record_older_than_the_first_is_replayed_at_once() {
+        let path = recording("older", &[(5_000_000, &[1, 2]), (0, &[3])]);
+        let mut player = Player::open(&path, MIN_SPEED, false).unwrap();
+        let mut buf = [0u8; 4];
+
+        player.read_timeout(&mut buf, 0).unwrap();
+        assert_eq!(player.read_timeout(&mut buf, 0).unwrap(), Some(1));
+        assert_eq!(buf[0], 3);
+        assert_eq!(player.read_timeout(&mut buf, 0).unwrap(), None);
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn a_delay_too_long_to_represent_is_an_error() {
+        let path = recording("far", &[(0, &[1]), (u64::MAX, &[2])]);
+        let mut player = Player::open(&path, 1e-300, false).unwrap();
+        let mut buf = [0u8; 4];
+
+        player.read_timeout(&mut buf, 0).unwrap();
+        let error = player.read_timeout(&mut buf, 0).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,87 @@
+use log::warn;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use crate::config::{self, Config};
+
+//How often the config file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+struct Shared {
+    /// Config loaded since the feed loop last took one
+    pending: Mutex<Option<Config>>,
+    stop: AtomicBool,
+}
+
+/// Thread loading the config file again each time its modification time changes.
+///
+/// A file that does not parse is reported and skipped, the feed loop keeps what it has.
+pub struct ConfigWatcher {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ConfigWatcher {
+    pub fn start(path: PathBuf) -> Self {
+        let shared = Arc::new(Shared {
+            pending: Mutex::new(None),
+            stop: AtomicBool::new(false),
+        });
+
+        let watched = shared.clone();
+
+        let thread = thread::spawn(move || {
+            let mut last = modified(&path);
+
+            while !watched.stop.load(Ordering::Relaxed) {
+                thread::park_timeout(POLL_INTERVAL);
+
+                let current = modified(&path);
+
+                //Gone for now, e.g. replaced by an editor
+                if current.is_none() || current == last {
+                    continue;
+                }
+
+                last = current;
+
+                match config::load(&path) {
+                    Ok(config) => *watched.pending.lock().unwrap() = Some(config),
+                    Err(error) => warn!("{}, keeping the current settings", error),
+                }
+            }
+        });
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    /// Config loaded since the last call, if the file changed meanwhile.
+    pub fn take(&self) -> Option<Config> {
+        self.shared.pending.lock().unwrap().take()
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
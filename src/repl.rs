@@ -0,0 +1,295 @@
+use std::io::{self, BufRead, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use toml::value::{Table, Value};
+
+use crate::axis::Axis;
+use crate::config::{self, AxesConfig, AxisConfig};
+use crate::feeder::{ProfileSwitch, Trims};
+
+const HELP: &str = "\
+set <setting> <axis> <value>  change a setting of [axes.<axis>], e.g. set gain x 60
+invert <axis>                 reverse the direction of the axis
+show [axis]                   print the settings of the profile in use
+save                          write them to the config file
+quit                          stop feeding";
+
+/// Axis settings of each profile, shared by the console editing them and the feed loop using them.
+#[derive(Debug, Default)]
+pub struct Tuning {
+    state: Mutex<TuningState>,
+}
+
+#[derive(Debug, Default)]
+struct TuningState {
+    /// As the feed loop last published them, with the console edits since
+    conversions: Vec<AxesConfig>,
+    /// Profile edited since the feed loop last took the settings, the latest one
+    edited: Option<usize>,
+}
+
+impl Tuning {
+    /// Settings of every profile if some were edited since the last call, with the index of the edited one.
+    pub fn take(&self) -> Option<(usize, Vec<AxesConfig>)> {
+        let mut state = self.state.lock().unwrap();
+        let index = state.edited.take()?;
+
+        Some((index, state.conversions.clone()))
+    }
+
+    /// Settings the feed loop uses, e.g. reloaded from the config file, for the console to edit on.
+    pub fn publish(&self, conversions: &[AxesConfig]) {
+        self.state.lock().unwrap().conversions = conversions.to_vec();
+    }
+
+    fn get(&self, index: usize) -> Result<AxesConfig, String> {
+        self.state
+            .lock()
+            .unwrap()
+            .conversions
+            .get(index)
+            .cloned()
+            .ok_or_else(|| "The feed has not started yet".to_string())
+    }
+
+    /// Edit the settings of profile `index` with `edit`, kept only if it succeeds.
+    fn edit<T>(
+        &self,
+        index: usize,
+        edit: impl FnOnce(&mut AxesConfig) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut state = self.state.lock().unwrap();
+        let mut axes = state
+            .conversions
+            .get(index)
+            .cloned()
+            .ok_or_else(|| "The feed has not started yet".to_string())?;
+
+        let result = edit(&mut axes)?;
+
+        state.conversions[index] = axes;
+        state.edited = Some(index);
+
+        Ok(result)
+    }
+}
+
+/// What the console edits: the axis settings of each profile, as the feed loop publishes them in `tuning`.
+pub struct Console {
+    pub path: PathBuf,
+    pub profile: Arc<ProfileSwitch>,
+    pub trims: Arc<Trims>,
+    pub tuning: Arc<Tuning>,
+}
+
+/// Read commands on stdin and apply them to the profile in use, until stdin closes or `quit`.
+///
+/// The thread is not joined, it blocks on stdin and ends with the process.
+pub fn start(console: Console, running: Arc<AtomicBool>) {
+    println!("Live tuning console, help for the commands");
+
+    thread::spawn(move || {
+        let stdin = io::stdin();
+
+        for line in stdin.lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            if words == ["quit"] || words == ["exit"] {
+                running.store(false, Ordering::Relaxed);
+                break;
+            }
+
+            match console.execute(&words) {
+                Ok(Some(text)) => println!("{}", text.trim_end()),
+                Ok(None) => {}
+                Err(error) => println!("{}", error),
+            }
+
+            let _ = io::stdout().flush();
+        }
+    });
+}
+
+impl Console {
+    fn execute(&self, words: &[&str]) -> Result<Option<String>, String> {
+        let index = self.profile.active();
+
+        match *words {
+            [] => Ok(None),
+            ["help"] => Ok(Some(HELP.to_string())),
+            ["set", "trim", axis, value] => {
+                let axis = parse_axis(axis)?;
+                let trim = value
+                    .parse()
+                    .map_err(|_| format!("{} is not a trim in vJoy units", value))?;
+
+                //Trims are shared with the feed loop and the front-ends, they apply at once
+                self.trims.set(axis, trim);
+                Ok(None)
+            }
+            ["set", "enabled" | "field", ..] => {
+                Err("enabled and field are set at startup, restart to change them".to_string())
+            }
+            ["set", setting, axis, value] => {
+                let axis = parse_axis(axis)?;
+
+                self.tuning.edit(index, |axes| {
+                    *axes.get_mut(axis) = with_setting(axes.get(axis), setting, value)?;
+                    Ok(None)
+                })
+            }
+            ["invert", axis] => {
+                let axis = parse_axis(axis)?;
+
+                self.tuning.edit(index, |axes| {
+                    let config = axes.get_mut(axis);
+                    config.invert = !config.invert;
+
+                    Ok(Some(format!(
+                        "{} {}",
+                        axis.name(),
+                        if config.invert { "inverted" } else { "upright" }
+                    )))
+                })
+            }
+            ["show"] => self.show(None),
+            ["show", axis] => self.show(Some(parse_axis(axis)?)),
+            ["save"] => {
+                let profile = (index > 0).then(|| self.profile.name());
+
+                config::save_axes(&self.path, profile, &self.current(index)?)
+                    .map_err(|error| error.to_string())?;
+
+                Ok(Some(format!("Saved to {}", self.path.display())))
+            }
+            _ => Err(format!("Unknown command {}, try help", words.join(" "))),
+        }
+    }
+
+    /// Settings of profile `index`, with the trims in use for the default one.
+    fn current(&self, index: usize) -> Result<AxesConfig, String> {
+        let mut axes = self.tuning.get(index)?;
+
+        //Profiles share the trims of [axes]
+        if index == 0 {
+            for axis in Axis::ALL {
+                axes.get_mut(axis).trim = self.trims.get(axis);
+            }
+        }
+
+        Ok(axes)
+    }
+
+    fn show(&self, axis: Option<Axis>) -> Result<Option<String>, String> {
+        let index = self.profile.active();
+        let axes = self.current(index)?;
+
+        let text = match axis {
+            Some(axis) => toml::to_string(axes.get(axis)),
+            None => toml::to_string(&axes),
+        };
+
+        let text = text.map_err(|error| error.to_string())?;
+
+        Ok(Some(format!("Profile {}\n{}", self.profile.name(), text)))
+    }
+}
+
+fn parse_axis(name: &str) -> Result<Axis, String> {
+    Axis::ALL
+        .into_iter()
+        .find(|axis| axis.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("No axis called {}, try x, y, z, rx, ry or rz", name))
+}
+
+/// `config` with `setting` parsed from `text`, checked as when loading the config file.
+fn with_setting(config: &AxisConfig, setting: &str, text: &str) -> Result<AxisConfig, String> {
+    let mut table = match Value::try_from(config).map_err(|error| error.to_string())? {
+        Value::Table(table) => table,
+        _ => Table::new(),
+    };
+
+    //Bare words such as filter names are strings
+    let value = toml::from_str::<Table>(&format!("value = {}", text))
+        .ok()
+        .and_then(|mut parsed| parsed.remove("value"))
+        .unwrap_or_else(|| Value::String(text.to_string()));
+
+    table.insert(setting.to_string(), value);
+
+    Value::Table(table)
+        .try_into()
+        .map_err(|error: toml::de::Error| format!("{} {}: {}", setting, text, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn console(tuning: Arc<Tuning>) -> Console {
+        Console {
+            path: PathBuf::from("config.toml"),
+            profile: Arc::new(ProfileSwitch::new(vec!["default".to_string()], 0)),
+            trims: Arc::new(Trims::new([0; 6])),
+            tuning,
+        }
+    }
+
+    #[test]
+    fn an_edit_reaches_the_feed_loop() {
+        let tuning = Arc::new(Tuning::default());
+        tuning.publish(&[AxesConfig::default()]);
+
+        console(tuning.clone())
+            .execute(&["set", "deadzone", "x", "20"])
+            .unwrap();
+
+        let (index, conversions) = tuning.take().unwrap();
+        assert_eq!(index, 0);
+        assert_eq!(conversions[0].x.deadzone, 20);
+        assert!(tuning.take().is_none());
+    }
+
+    #[test]
+    fn edits_apply_on_top_of_reloaded_settings() {
+        let tuning = Arc::new(Tuning::default());
+        let console = console(tuning.clone());
+        tuning.publish(&[AxesConfig::default()]);
+
+        //Reloaded by the feed loop from the config file
+        let mut reloaded = AxesConfig::default();
+        reloaded.y.deadzone = 30;
+        tuning.publish(&[reloaded]);
+
+        console.execute(&["invert", "x"]).unwrap();
+
+        let (_, conversions) = tuning.take().unwrap();
+        assert!(conversions[0].x.invert);
+        assert_eq!(conversions[0].y.deadzone, 30);
+    }
+
+    #[test]
+    fn a_bad_value_leaves_the_settings_alone() {
+        let tuning = Arc::new(Tuning::default());
+        tuning.publish(&[AxesConfig::default()]);
+
+        let result = console(tuning.clone()).execute(&["set", "deadzone", "x", "wide"]);
+
+        assert!(result.is_err());
+        assert!(tuning.take().is_none());
+    }
+
+    #[test]
+    fn nothing_is_edited_before_the_feed_starts() {
+        let tuning = Arc::new(Tuning::default());
+
+        assert!(console(tuning).execute(&["show"]).is_err());
+    }
+}
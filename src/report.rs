@@ -0,0 +1,240 @@
+use serde::{Deserialize, Serialize};
+
+use crate::axis::Axis;
+use crate::config::ButtonsConfig;
+
+//16 bit signed per axis
+pub const REPORT_TRANSLATION: u8 = 1; //left/right, foward/back, up/down
+pub const REPORT_ROTATION: u8 = 2; //pitch, roll, yaw
+
+//Report ID + 3 axes
+pub const AXES_REPORT_LEN: usize = 7;
+
+//Wireless receivers send translation then rotation in a single report 1
+pub const COMBINED_REPORT_LEN: usize = 13;
+
+//1 bit per button, little endian from byte 1
+pub const REPORT_BUTTONS: u8 = 3; //0 == no buttons, 1 == left button, 2 == right button 3 == both buttons
+
+//vJoy has 128 buttons but lButtons/lButtonsEx1 are enough for any puck
+pub const MAX_BUTTONS: u32 = 64;
+
+//SpaceMouse Pro, 15 buttons spread over the first 2 bytes
+pub const SPACEMOUSE_PRO_PRODUCT_ID: u16 = 50731;
+
+//1 byte
+pub const REPORT_LED: u8 = 4; //0 == off, 1+ == on
+
+/// Report ID as named in the dumps, also for IDs no parser knows.
+pub fn report_name(id: u8) -> &'static str {
+    match id {
+        REPORT_TRANSLATION => "translation",
+        REPORT_ROTATION => "rotation",
+        REPORT_BUTTONS => "buttons",
+        REPORT_LED => "led",
+        _ => "unknown",
+    }
+}
+
+/// Bytes as space separated lowercase hex, as in the dumps.
+pub fn hex(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+
+    bytes.join(" ")
+}
+
+/// Raw values decoded from one HID report, `None` for what the report does not carry.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Report {
+    pub axes: [Option<i16>; 6],
+    pub buttons: Option<u64>,
+    /// ID of a report the parser does not handle
+    pub unknown: Option<u8>,
+    /// ID of a report too short for its fields, skipped
+    pub truncated: Option<u8>,
+}
+
+/// Byte order of the 16 bit axis values in the reports.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ByteOrder {
+    /// SpaceNavigator
+    #[default]
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    /// Raw axis value from its two report bytes, in report order.
+    pub fn decode(self, bytes: [u8; 2]) -> i16 {
+        match self {
+            ByteOrder::Little => i16::from_le_bytes(bytes),
+            ByteOrder::Big => i16::from_be_bytes(bytes),
+        }
+    }
+
+    /// Report bytes of a raw axis value.
+    pub fn encode(self, value: i16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+/// Where the button bitmask sits in the buttons report.
+#[derive(Debug, Clone, Copy)]
+pub struct ButtonLayout {
+    pub count: u32,
+    /// Index of the first bitmask byte, after the report ID
+    pub offset: usize,
+}
+
+impl ButtonLayout {
+    /// Report length needed to hold every button bit.
+    fn report_len(&self) -> usize {
+        self.offset + (self.count as usize).div_ceil(8)
+    }
+
+    fn decode(&self, buf: &[u8]) -> u64 {
+        let bytes = &buf[self.offset..self.report_len()];
+
+        let bits = bytes
+            .iter()
+            .enumerate()
+            .fold(0u64, |bits, (i, &byte)| bits | (byte as u64) << (8 * i));
+
+        if self.count >= MAX_BUTTONS {
+            bits
+        } else {
+            bits & ((1 << self.count) - 1)
+        }
+    }
+}
+
+/// Decodes the input reports of one device model.
+pub trait ReportParser {
+    /// Size of the largest input report, including the report ID.
+    fn report_len(&self) -> usize;
+
+    /// Decode `buf`, which only holds the bytes actually read.
+    fn parse(&self, buf: &[u8], out: &mut Report);
+}
+
+/// SpaceNavigator layout, also used by the wireless 3Dconnexion receivers.
+pub struct SpaceNavigatorParser {
+    pub buttons: ButtonLayout,
+    pub byte_order: ByteOrder,
+}
+
+impl SpaceNavigatorParser {
+    fn set_axis(&self, out: &mut Report, axis: Axis, low: u8, high: u8) {
+        out.axes[axis as usize] = Some(self.byte_order.decode([low, high]));
+    }
+}
+
+impl ReportParser for SpaceNavigatorParser {
+    fn report_len(&self) -> usize {
+        COMBINED_REPORT_LEN.max(self.buttons.report_len())
+    }
+
+    fn parse(&self, buf: &[u8], out: &mut Report) {
+        let report_id = match buf.first() {
+            Some(&report_id) => report_id,
+            None => return,
+        };
+
+        let min_len = match report_id {
+            REPORT_TRANSLATION | REPORT_ROTATION => AXES_REPORT_LEN,
+            REPORT_BUTTONS => self.buttons.report_len(),
+            _ => 1,
+        };
+
+        if buf.len() < min_len {
+            out.truncated = Some(report_id);
+            return;
+        }
+
+        match report_id {
+            REPORT_ROTATION => {
+                self.set_axis(out, Axis::RX, buf[1], buf[2]);
+
+                self.set_axis(out, Axis::RY, buf[5], buf[6]);
+
+                self.set_axis(out, Axis::RZ, buf[3], buf[4]);
+            }
+            REPORT_TRANSLATION => {
+                self.set_axis(out, Axis::X, buf[1], buf[2]);
+
+                self.set_axis(out, Axis::Y, buf[5], buf[6]);
+
+                self.set_axis(out, Axis::Z, buf[3], buf[4]);
+
+                if buf.len() >= COMBINED_REPORT_LEN {
+                    self.set_axis(out, Axis::RX, buf[7], buf[8]);
+
+                    self.set_axis(out, Axis::RY, buf[11], buf[12]);
+
+                    self.set_axis(out, Axis::RZ, buf[9], buf[10]);
+                }
+            }
+            REPORT_BUTTONS => out.buttons = Some(self.buttons.decode(buf)),
+            _ => out.unknown = Some(report_id),
+        }
+    }
+}
+
+/// Number of buttons decoded for the device model, unless overridden.
+pub fn button_count(product_id: u16, config: &ButtonsConfig) -> u32 {
+    let count = match product_id {
+        SPACEMOUSE_PRO_PRODUCT_ID => 15,
+        _ => 2,
+    };
+
+    config.count.unwrap_or(count).min(MAX_BUTTONS)
+}
+
+/// Pick the parser for the matched device, `config` overrides the model's button layout.
+pub fn parser_for(
+    _vendor_id: u16,
+    product_id: u16,
+    config: &ButtonsConfig,
+    byte_order: ByteOrder,
+) -> Box<dyn ReportParser> {
+    let buttons = ButtonLayout {
+        count: button_count(product_id, config),
+        offset: config.offset.unwrap_or(1).max(1),
+    };
+
+    //All supported 3Dconnexion models share the SpaceNavigator layout so far
+    Box::new(SpaceNavigatorParser {
+        buttons,
+        byte_order,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn little_endian_takes_the_low_byte_first() {
+        assert_eq!(ByteOrder::Little.decode([0x34, 0x12]), 0x1234);
+        assert_eq!(ByteOrder::Little.decode([0xa2, 0xfe]), -350);
+    }
+
+    #[test]
+    fn big_endian_takes_the_high_byte_first() {
+        assert_eq!(ByteOrder::Big.decode([0x12, 0x34]), 0x1234);
+        assert_eq!(ByteOrder::Big.decode([0xfe, 0xa2]), -350);
+    }
+
+    #[test]
+    fn encode_is_the_reverse_of_decode() {
+        for order in [ByteOrder::Little, ByteOrder::Big] {
+            for value in [i16::MIN, -350, 0, 1, 350, i16::MAX] {
+                assert_eq!(order.decode(order.encode(value)), value);
+            }
+        }
+    }
+}
@@ -0,0 +1,351 @@
+use hidapi::{HidApi, HidDevice, HidError};
+
+use log::warn;
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::demo::Demo;
+use crate::device::{self, HidErrorKind};
+use crate::error::FeederError;
+use crate::record::Player;
+
+/// Where the feeder reads its reports from.
+pub trait InputSource {
+    /// Read a report into `buf`, waiting at most `timeout_ms`, 0 if none came meanwhile.
+    ///
+    /// Returns `None` once the source has nothing more to give.
+    fn read(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<Option<usize>, FeederError>;
+
+    /// Light the LED while feeding, on sources that have one.
+    fn set_led(&self, _on: bool) {}
+
+    /// Get going again after `error` was returned by `read`.
+    ///
+    /// Returns false if `running` was cleared meanwhile, `error` if it cannot be recovered.
+    fn recover(&mut self, error: FeederError, _running: &AtomicBool) -> Result<bool, FeederError> {
+        Err(error)
+    }
+
+    /// Wakes a `read` waiting on this source, from the watchdog thread once the device stalled.
+    ///
+    /// `None` for sources whose reads always return within their timeout.
+    fn stall_signal(&self) -> Option<StallSignal> {
+        None
+    }
+}
+
+/// Called from another thread to make a waiting `InputSource::read` return at once.
+pub type StallSignal = Box<dyn Fn() + Send>;
+
+impl InputSource for HidDevice {
+    fn read(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<Option<usize>, FeederError> {
+        Ok(Some(self.read_timeout(buf, timeout_ms)?))
+    }
+
+    fn set_led(&self, on: bool) {
+        device::update_led(self, on);
+    }
+}
+
+//Pause after a non-blocking read found nothing, so the loop doesn't spin
+const POLL_INTERVAL: Duration = Duration::from_millis(2);
+
+//Transient read errors in a row tolerated before giving up, and the pause after each
+const MAX_READ_ERRORS: u32 = 10;
+const READ_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+//Largest report read, a full speed USB packet
+const REPORT_BUFFER_LEN: usize = 64;
+
+//Reports the reader thread gets ahead of the feed loop
+const READ_QUEUE_LEN: usize = 64;
+
+//Longest a dropped source waits for its reader to turn the LED off and close the device
+const READER_STOP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// What the reader thread hands to `DeviceSource::read`.
+enum Read {
+    Report(Vec<u8>),
+    Error(HidError),
+    /// The watchdog gave up on the device
+    Stalled,
+}
+
+/// Thread reading the device, so a read wedged in the driver leaves the feed loop free.
+struct Reader {
+    stop: Arc<AtomicBool>,
+    /// LED states to write between reads
+    led: Sender<bool>,
+    /// Disconnected once the thread ended
+    done: Receiver<()>,
+}
+
+impl Reader {
+    /// Read `device` until stopped, handing the reads tagged with `generation` to `reads`.
+    fn spawn(
+        device: HidDevice,
+        blocking: bool,
+        generation: u64,
+        reads: SyncSender<(u64, Read)>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let (led, leds) = mpsc::channel();
+        let (finished, done) = mpsc::channel::<()>();
+        let stopped = stop.clone();
+
+        thread::spawn(move || {
+            let _finished = finished;
+            let mut buf = [0u8; REPORT_BUFFER_LEN];
+
+            loop {
+                for on in leds.try_iter() {
+                    device::update_led(&device, on);
+                }
+
+                if stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                let result = if blocking {
+                    device.read_timeout(&mut buf, device::READ_TIMEOUT_MS)
+                } else {
+                    device.read(&mut buf)
+                };
+
+                let read = match result {
+                    Ok(0) => {
+                        if !blocking {
+                            thread::sleep(POLL_INTERVAL);
+                        }
+
+                        continue;
+                    }
+                    Ok(size) => Read::Report(buf[..size].to_vec()),
+                    Err(error) => Read::Error(error),
+                };
+
+                let failed = matches!(read, Read::Error(_));
+
+                if reads.send((generation, read)).is_err() {
+                    break;
+                }
+
+                if failed {
+                    thread::sleep(READ_RETRY_DELAY);
+                }
+            }
+        });
+
+        Self { stop, led, done }
+    }
+}
+
+/// HID device that is reopened once unplugged and plugged back in, or once the watchdog tripped.
+///
+/// It is read on a thread of its own, which the watchdog can wake the feed loop from.
+/// Transient read errors are retried, up to `MAX_READ_ERRORS` in a row.
+pub struct DeviceSource {
+    pub api: HidApi,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Unit reconnected to, any with the IDs if `None`
+    pub serial: Option<String>,
+    /// Wait up to the timeout for each report, otherwise poll
+    pub blocking: bool,
+    /// Transient read errors since the last good read
+    errors: u32,
+    reader: Reader,
+    /// Reader of each device opened so far, ignoring readers of the previous ones
+    generation: u64,
+    reads: Receiver<(u64, Read)>,
+    sender: SyncSender<(u64, Read)>,
+}
+
+impl DeviceSource {
+    pub fn new(
+        api: HidApi,
+        device: HidDevice,
+        vendor_id: u16,
+        product_id: u16,
+        serial: Option<String>,
+        blocking: bool,
+    ) -> Self {
+        let (sender, reads) = mpsc::sync_channel(READ_QUEUE_LEN);
+
+        Self {
+            api,
+            vendor_id,
+            product_id,
+            serial,
+            blocking,
+            errors: 0,
+            reader: Reader::spawn(device, blocking, 0, sender.clone()),
+            generation: 0,
+            reads,
+            sender,
+        }
+    }
+}
+
+impl Drop for DeviceSource {
+    fn drop(&mut self) {
+        self.reader.stop.store(true, Ordering::Relaxed);
+
+        //Unless the read is wedged, the last LED state is written before the device closes
+        let _ = self.reader.done.recv_timeout(READER_STOP_TIMEOUT);
+    }
+}
+
+impl InputSource for DeviceSource {
+    fn read(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<Option<usize>, FeederError> {
+        let deadline = Instant::now() + Duration::from_millis(timeout_ms.max(0) as u64);
+
+        let error = loop {
+            match self
+                .reads
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                //Nothing read, the feed loop sees the watchdog tripped
+                Ok((_, Read::Stalled)) => return Ok(Some(0)),
+                Ok((generation, _)) if generation != self.generation => continue,
+                Ok((_, Read::Report(report))) => {
+                    self.errors = 0;
+
+                    let len = report.len().min(buf.len());
+                    buf[..len].copy_from_slice(&report[..len]);
+
+                    return Ok(Some(len));
+                }
+                Ok((_, Read::Error(error))) => break error,
+                //The source holds a sender, only the timeout ends the wait
+                Err(RecvTimeoutError::Timeout | RecvTimeoutError::Disconnected) => {
+                    return Ok(Some(0))
+                }
+            }
+        };
+
+        if device::classify_error(&error) != HidErrorKind::Transient {
+            return Err(FeederError::Hid(error));
+        }
+
+        self.errors += 1;
+
+        if self.errors >= MAX_READ_ERRORS {
+            return Err(FeederError::ReadFailed {
+                count: self.errors,
+                error,
+            });
+        }
+
+        warn!("SpaceNavigator read failed, retrying: {}", error);
+
+        Ok(Some(0))
+    }
+
+    fn set_led(&self, on: bool) {
+        let _ = self.reader.led.send(on);
+    }
+
+    fn recover(&mut self, error: FeederError, running: &AtomicBool) -> Result<bool, FeederError> {
+        let disconnected = match &error {
+            FeederError::Hid(error) => device::classify_error(error) == HidErrorKind::Disconnected,
+            FeederError::Stalled(_) => true,
+            //Messages are localized, the device list tells for sure
+            FeederError::ReadFailed { .. } => {
+                self.api.refresh_devices().is_ok()
+                    && !device::is_space_navigator_plugged(
+                        &self.api,
+                        self.vendor_id,
+                        Some(self.product_id),
+                        self.serial.as_deref(),
+                    )
+            }
+            _ => false,
+        };
+
+        if !disconnected {
+            return Err(error);
+        }
+
+        warn!("{}, waiting for it to reconnect...", error);
+
+        let device = match device::reconnect_space_navigator(
+            &mut self.api,
+            self.vendor_id,
+            self.product_id,
+            self.serial.as_deref(),
+            running,
+        ) {
+            Some(device) => device,
+            None => return Ok(false),
+        };
+
+        if let Err(error) = device.set_blocking_mode(self.blocking) {
+            warn!("Cannot set SpaceNavigator blocking mode: {}", error);
+        }
+
+        //The previous reader ends once its read returns, or never if it is wedged
+        self.reader.stop.store(true, Ordering::Relaxed);
+        self.generation += 1;
+        self.reader = Reader::spawn(device, self.blocking, self.generation, self.sender.clone());
+        self.errors = 0;
+
+        Ok(true)
+    }
+
+    fn stall_signal(&self) -> Option<StallSignal> {
+        let sender = self.sender.clone();
+
+        //A full queue wakes the read anyway
+        Some(Box::new(move || {
+            let _ = sender.try_send((0, Read::Stalled));
+        }))
+    }
+}
+
+impl InputSource for Player {
+    fn read(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<Option<usize>, FeederError> {
+        self.read_timeout(buf, timeout_ms)
+            .map_err(FeederError::Replay)
+    }
+}
+
+impl InputSource for Demo {
+    fn read(&mut self, buf: &mut [u8], timeout_ms: i32) -> Result<Option<usize>, FeederError> {
+        Ok(Some(self.read_timeout(buf, timeout_ms)))
+    }
+}
+
+/// Hands out scripted reports one per read, then runs dry.
+#[derive(Debug, Clone, Default)]
+pub struct MockSource {
+    reports: VecDeque<Vec<u8>>,
+}
+
+impl MockSource {
+    /// Reports as read from the device, starting with their report ID.
+    pub fn new(reports: impl IntoIterator<Item = Vec<u8>>) -> Self {
+        Self {
+            reports: reports.into_iter().collect(),
+        }
+    }
+
+    pub fn push(&mut self, report: Vec<u8>) {
+        self.reports.push_back(report);
+    }
+}
+
+impl InputSource for MockSource {
+    fn read(&mut self, buf: &mut [u8], _timeout_ms: i32) -> Result<Option<usize>, FeederError> {
+        Ok(self.reports.pop_front().map(|report| {
+            let len = report.len().min(buf.len());
+            buf[..len].copy_from_slice(&report[..len]);
+            len
+        }))
+    }
+}
@@ -0,0 +1,72 @@
+use serde_json::{json, Value};
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::feeder::Snapshot;
+use crate::ws;
+
+//Set once at startup, like the logger
+static JSON: AtomicBool = AtomicBool::new(false);
+
+/// Print the status as newline-delimited JSON events on stdout instead of text.
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+/// `{"event": event, ...fields}`, `fields` being an object.
+pub fn event(event: &str, fields: Value) -> Value {
+    let mut object = serde_json::Map::new();
+    object.insert("event".to_string(), json!(event));
+
+    if let Value::Object(fields) = fields {
+        object.extend(fields);
+    }
+
+    Value::Object(object)
+}
+
+/// Print the event on a line of its own in JSON mode, nothing otherwise.
+pub fn emit(name: &str, fields: Value) {
+    if !is_json() {
+        return;
+    }
+
+    //Held for the whole line, so events from several threads don't mix
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", event(name, fields));
+    let _ = stdout.flush();
+}
+
+/// Emit a `state` event with the axes and buttons fed every `interval` until `running` is cleared.
+pub fn stream(
+    interval: Duration,
+    snapshot: Arc<Mutex<Snapshot>>,
+    running: Arc<AtomicBool>,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        let mut next_frame = Instant::now();
+
+        while running.load(Ordering::Relaxed) {
+            thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+            next_frame += interval;
+
+            let state = {
+                let snapshot = snapshot.lock().unwrap();
+                let mut state = ws::state(&snapshot);
+
+                state["connected"] = json!(snapshot.connected);
+                state
+            };
+
+            emit("state", state);
+        }
+    })
+}
@@ -0,0 +1,265 @@
+use std::io;
+use std::mem;
+use std::ptr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use winapi::shared::minwindef::{LPARAM, LRESULT, UINT, WPARAM};
+use winapi::shared::windef::{HWND, POINT};
+use winapi::um::libloaderapi::GetModuleHandleW;
+use winapi::um::shellapi::{
+    Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NIM_MODIFY,
+    NOTIFYICONDATAW,
+};
+use winapi::um::wincon::GetConsoleWindow;
+use winapi::um::winuser::{
+    AppendMenuW, CreatePopupMenu, CreateWindowExW, DefWindowProcW, DestroyMenu, DestroyWindow,
+    DispatchMessageW, GetCursorPos, GetMessageW, GetWindowLongPtrW, LoadIconW, PostMessageW,
+    PostQuitMessage, RegisterClassW, SetForegroundWindow, SetTimer, SetWindowLongPtrW, ShowWindow,
+    TrackPopupMenu, TranslateMessage, GWLP_USERDATA, IDI_APPLICATION, MF_CHECKED, MF_GRAYED,
+    MF_SEPARATOR, MF_STRING, MSG, SW_HIDE, SW_SHOW, TPM_RIGHTBUTTON, WM_APP, WM_COMMAND,
+    WM_DESTROY, WM_LBUTTONUP, WM_NULL, WM_RBUTTONUP, WM_TIMER, WNDCLASSW,
+};
+
+use crate::feeder::{ProfileSwitch, Snapshot};
+
+const CLASS_NAME: &str = "vjoy-feeder tray";
+
+//Sent by the shell for clicks on the icon
+const TRAY_MESSAGE: UINT = WM_APP + 1;
+
+//How often the tooltip is refreshed and `running` checked
+const REFRESH_INTERVAL_MS: UINT = 500;
+
+//Menu item IDs, profiles follow `PROFILE_ITEM`
+const QUIT_ITEM: usize = 1;
+const STATUS_ITEM: usize = 2;
+const PROFILE_ITEM: usize = 100;
+
+struct Tray<'a> {
+    snapshot: &'a Mutex<Snapshot>,
+    profile: &'a ProfileSwitch,
+    vjoy_ids: &'a [u32],
+    running: &'a AtomicBool,
+    icon: NOTIFYICONDATAW,
+}
+
+impl Tray<'_> {
+    fn status(&self) -> String {
+        let ids: Vec<String> = self.vjoy_ids.iter().map(u32::to_string).collect();
+
+        let device = if self.snapshot.lock().unwrap().connected {
+            "connected"
+        } else {
+            "disconnected"
+        };
+
+        format!("Device {}, vJoy {}", device, ids.join(", "))
+    }
+
+    /// Show the status in the tooltip, cut to the 127 characters it holds.
+    unsafe fn refresh(&mut self) {
+        let tip = wide(&format!("vjoy-feeder\n{}", self.status()));
+        let len = tip.len().min(self.icon.szTip.len()) - 1;
+
+        self.icon.szTip = [0; 128];
+        self.icon.szTip[..len].copy_from_slice(&tip[..len]);
+
+        Shell_NotifyIconW(NIM_MODIFY, &mut self.icon);
+    }
+
+    unsafe fn show_menu(&self, window: HWND) {
+        let menu = CreatePopupMenu();
+
+        AppendMenuW(
+            menu,
+            MF_STRING | MF_GRAYED,
+            STATUS_ITEM,
+            wide(&self.status()).as_ptr(),
+        );
+        AppendMenuW(menu, MF_SEPARATOR, 0, ptr::null());
+
+        let names = self.profile.names();
+
+        if names.len() > 1 {
+            for (index, name) in names.iter().enumerate() {
+                let checked = if index == self.profile.active() {
+                    MF_CHECKED
+                } else {
+                    0
+                };
+
+                let label = wide(&format!("Profile {}", name));
+                AppendMenuW(
+                    menu,
+                    MF_STRING | checked,
+                    PROFILE_ITEM + index,
+                    label.as_ptr(),
+                );
+            }
+
+            AppendMenuW(menu, MF_SEPARATOR, 0, ptr::null());
+        }
+
+        AppendMenuW(menu, MF_STRING, QUIT_ITEM, wide("Quit").as_ptr());
+
+        let mut cursor: POINT = mem::zeroed();
+        GetCursorPos(&mut cursor);
+
+        //Without it the menu stays open when clicking elsewhere
+        SetForegroundWindow(window);
+        TrackPopupMenu(
+            menu,
+            TPM_RIGHTBUTTON,
+            cursor.x,
+            cursor.y,
+            0,
+            window,
+            ptr::null(),
+        );
+        PostMessageW(window, WM_NULL, 0, 0);
+
+        DestroyMenu(menu);
+    }
+}
+
+/// Run a tray icon showing the status until Quit is picked in its menu or `running` is cleared.
+///
+/// The console window is hidden meanwhile, the menu also switches between profiles.
+pub fn run(
+    snapshot: &Mutex<Snapshot>,
+    profile: &ProfileSwitch,
+    vjoy_ids: &[u32],
+    running: &AtomicBool,
+) -> io::Result<()> {
+    unsafe {
+        let instance = GetModuleHandleW(ptr::null());
+        let class_name = wide(CLASS_NAME);
+
+        let mut class: WNDCLASSW = mem::zeroed();
+        class.lpfnWndProc = Some(window_proc);
+        class.hInstance = instance;
+        class.lpszClassName = class_name.as_ptr();
+
+        if RegisterClassW(&class) == 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        //Never shown, it only receives the icon and menu messages
+        let window = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            class_name.as_ptr(),
+            0,
+            0,
+            0,
+            0,
+            0,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            instance,
+            ptr::null_mut(),
+        );
+
+        if window.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut icon: NOTIFYICONDATAW = mem::zeroed();
+        icon.cbSize = mem::size_of::<NOTIFYICONDATAW>() as u32;
+        icon.hWnd = window;
+        icon.uID = 1;
+        icon.uFlags = NIF_ICON | NIF_MESSAGE | NIF_TIP;
+        icon.uCallbackMessage = TRAY_MESSAGE;
+        icon.hIcon = LoadIconW(ptr::null_mut(), IDI_APPLICATION);
+
+        if Shell_NotifyIconW(NIM_ADD, &mut icon) == 0 {
+            DestroyWindow(window);
+            return Err(io::Error::other("the tray icon could not be added"));
+        }
+
+        let mut tray = Tray {
+            snapshot,
+            profile,
+            vjoy_ids,
+            running,
+            icon,
+        };
+
+        tray.refresh();
+
+        SetWindowLongPtrW(window, GWLP_USERDATA, &mut tray as *mut Tray as isize);
+        SetTimer(window, 1, REFRESH_INTERVAL_MS, None);
+
+        let console = GetConsoleWindow();
+
+        if !console.is_null() {
+            ShowWindow(console, SW_HIDE);
+        }
+
+        let mut message: MSG = mem::zeroed();
+
+        while GetMessageW(&mut message, ptr::null_mut(), 0, 0) > 0 {
+            TranslateMessage(&message);
+            DispatchMessageW(&message);
+        }
+
+        //Errors are still shown, and --pause can be answered
+        if !console.is_null() {
+            ShowWindow(console, SW_SHOW);
+        }
+
+        Ok(())
+    }
+}
+
+unsafe extern "system" fn window_proc(
+    window: HWND,
+    message: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let tray = GetWindowLongPtrW(window, GWLP_USERDATA) as *mut Tray;
+
+    if tray.is_null() {
+        return DefWindowProcW(window, message, wparam, lparam);
+    }
+
+    let tray = &mut *tray;
+
+    match message {
+        WM_TIMER => {
+            if tray.running.load(Ordering::Relaxed) {
+                tray.refresh();
+            } else {
+                DestroyWindow(window);
+            }
+        }
+        TRAY_MESSAGE => {
+            if matches!(lparam as UINT, WM_RBUTTONUP | WM_LBUTTONUP) {
+                tray.show_menu(window);
+            }
+        }
+        WM_COMMAND => match wparam & 0xffff {
+            QUIT_ITEM => {
+                tray.running.store(false, Ordering::Relaxed);
+                DestroyWindow(window);
+            }
+            item if item >= PROFILE_ITEM => tray.profile.select(item - PROFILE_ITEM),
+            _ => {}
+        },
+        WM_DESTROY => {
+            Shell_NotifyIconW(NIM_DELETE, &mut tray.icon);
+            SetWindowLongPtrW(window, GWLP_USERDATA, 0);
+            PostQuitMessage(0);
+        }
+        _ => return DefWindowProcW(window, message, wparam, lparam),
+    }
+
+    0
+}
+
+/// Null-terminated UTF-16 for the Windows API.
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain(Some(0)).collect()
+}
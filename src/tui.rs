@@ -0,0 +1,201 @@
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+
+use log::LevelFilter;
+
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, LineGauge, Paragraph};
+use ratatui::{Frame, Terminal};
+
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::axis::{Axis, MAX_DEFLECTION};
+use crate::config;
+use crate::feeder::{ProfileSwitch, Snapshot, Trims};
+
+//How long to wait for a key press between redraws
+const REFRESH_INTERVAL: Duration = Duration::from_millis(50);
+
+//vJoy units added or removed by each trim key press
+const TRIM_STEP: i32 = 50;
+
+/// Draw `snapshot` until `q` or Ctrl-C is pressed, or `running` is cleared.
+///
+/// The arrow keys adjust `trims`, which `s` saves to the config file at `config_path`,
+/// and `p` switches to the next profile.
+/// Logging is muted meanwhile since it would garble the screen.
+pub fn run(
+    snapshot: &Mutex<Snapshot>,
+    trims: &Trims,
+    profile: &ProfileSwitch,
+    config_path: &Path,
+    running: &AtomicBool,
+) -> io::Result<()> {
+    let level = log::max_level();
+    log::set_max_level(LevelFilter::Off);
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+
+    let result = draw_loop(snapshot, trims, profile, config_path, running);
+
+    running.store(false, Ordering::Relaxed);
+
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+
+    log::set_max_level(level);
+
+    result
+}
+
+fn draw_loop(
+    snapshot: &Mutex<Snapshot>,
+    trims: &Trims,
+    profile: &ProfileSwitch,
+    config_path: &Path,
+    running: &AtomicBool,
+) -> io::Result<()> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut selected = 0;
+    let mut status = String::new();
+
+    while running.load(Ordering::Relaxed) {
+        let state = snapshot.lock().unwrap().clone();
+        let trim = trims.values();
+
+        terminal.draw(|frame| draw(frame, &state, &trim, profile.name(), selected, &status))?;
+
+        if !event::poll(REFRESH_INTERVAL)? {
+            continue;
+        }
+
+        let key = match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press => key,
+            _ => continue,
+        };
+
+        let axis = Axis::ALL[selected];
+
+        match key.code {
+            KeyCode::Char('q') => break,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => break,
+            KeyCode::Up => selected = (selected + Axis::ALL.len() - 1) % Axis::ALL.len(),
+            KeyCode::Down => selected = (selected + 1) % Axis::ALL.len(),
+            KeyCode::Right => trims.nudge(axis, TRIM_STEP),
+            KeyCode::Left => trims.nudge(axis, -TRIM_STEP),
+            KeyCode::Char('0') => trims.set(axis, 0),
+            KeyCode::Char('p') => profile.next(),
+            KeyCode::Char('s') => {
+                status = match config::save_trims(config_path, &trims.values()) {
+                    Ok(()) => format!("Trims saved to {}", config_path.display()),
+                    Err(error) => error.to_string(),
+                };
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn draw(
+    frame: &mut Frame,
+    state: &Snapshot,
+    trims: &[i32; 6],
+    profile: &str,
+    selected: usize,
+    status: &str,
+) {
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2 * Axis::ALL.len() as u16 + 2),
+            Constraint::Length(3),
+            Constraint::Length(1),
+            Constraint::Min(0),
+        ])
+        .split(frame.size());
+
+    let axes_block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!("Axes, profile {}", profile));
+    let axes_area = axes_block.inner(rows[0]);
+    frame.render_widget(axes_block, rows[0]);
+
+    let lines = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(1); 2 * Axis::ALL.len()])
+        .split(axes_area);
+
+    for axis in Axis::ALL {
+        let index = axis as usize;
+        let (raw, value, range) = (state.raw[index], state.axes[index], state.ranges[index]);
+
+        let raw_ratio = (raw as f64 + MAX_DEFLECTION as f64) / (2 * MAX_DEFLECTION) as f64;
+        let span = (range.max - range.min).max(1) as f64;
+        let value_ratio = (value - range.min) as f64 / span;
+
+        frame.render_widget(
+            LineGauge::default()
+                .gauge_style(Style::default().fg(Color::DarkGray))
+                .ratio(raw_ratio.clamp(0.0, 1.0))
+                .label(format!("{:>2} raw   {:>6}", axis.name(), raw)),
+            lines[2 * index],
+        );
+
+        frame.render_widget(
+            LineGauge::default()
+                .gauge_style(Style::default().fg(Color::Green))
+                .ratio(value_ratio.clamp(0.0, 1.0))
+                .label(format!(
+                    "{:>2} vJoy  {:>6} trim {:>+6}{}",
+                    axis.name(),
+                    value,
+                    trims[index],
+                    if index == selected { " <" } else { "  " }
+                )),
+            lines[2 * index + 1],
+        );
+    }
+
+    let buttons: Vec<Span> = (0..state.button_count)
+        .map(|button| {
+            let style = if state.buttons & 1 << button != 0 {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Green)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            Span::styled(format!(" {} ", button + 1), style)
+        })
+        .collect();
+
+    frame.render_widget(
+        Paragraph::new(Line::from(buttons))
+            .block(Block::default().borders(Borders::ALL).title("Buttons")),
+        rows[1],
+    );
+
+    frame.render_widget(Paragraph::new(status), rows[2]);
+
+    frame.render_widget(
+        Paragraph::new(
+            "Up/Down select an axis, Left/Right trim it, 0 clears, s saves, p next profile, q or Ctrl-C exits",
+        ),
+        rows[3],
+    );
+}
@@ -0,0 +1,338 @@
+use log::{info, trace, warn};
+
+use rusty_vjoy::JoystickPosition;
+
+use serde_json::json;
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::mem;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::slice;
+
+use crate::axis::{self, AxisRange, Field};
+use crate::buttons;
+use crate::hat::HAT_CENTERED;
+use crate::joystick::VirtualJoystick;
+use crate::keys::Key;
+use crate::status;
+
+const UINPUT_PATH: &str = "/dev/uinput";
+const KEYBOARD_NAME: &str = "vjoy-feeder keys";
+
+//linux/input-event-codes.h
+const EV_SYN: u16 = 0x00;
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const SYN_REPORT: u16 = 0;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+const BUS_VIRTUAL: u16 = 0x06;
+
+//Joystick buttons, then the extra ones for the rest
+const BTN_JOYSTICK: u16 = 0x120;
+const BTN_JOYSTICK_COUNT: u32 = 16;
+const BTN_TRIGGER_HAPPY: u16 = 0x2c0;
+const BTN_TRIGGER_HAPPY_COUNT: u32 = 40;
+
+//linux/uinput.h ioctls, _IO and _IOW on 'U'
+const UI_DEV_CREATE: u64 = 0x5501;
+const UI_DEV_DESTROY: u64 = 0x5502;
+const UI_DEV_SETUP: u64 = iow(3, mem::size_of::<libc::uinput_setup>());
+const UI_ABS_SETUP: u64 = iow(4, mem::size_of::<libc::uinput_abs_setup>());
+const UI_SET_EVBIT: u64 = iow(100, mem::size_of::<libc::c_int>());
+const UI_SET_KEYBIT: u64 = iow(101, mem::size_of::<libc::c_int>());
+const UI_SET_ABSBIT: u64 = iow(103, mem::size_of::<libc::c_int>());
+
+const fn iow(number: u64, size: usize) -> u64 {
+    1 << 30 | (size as u64) << 16 | (b'U' as u64) << 8 | number
+}
+
+/// Absolute axis code standing in for a vJoy field, `None` for those Linux games have no use for.
+fn abs_code(field: Field) -> Option<u16> {
+    match field {
+        Field::X => Some(0x00),
+        Field::Y => Some(0x01),
+        Field::Z => Some(0x02),
+        Field::RX => Some(0x03),
+        Field::RY => Some(0x04),
+        Field::RZ => Some(0x05),
+        Field::Throttle => Some(0x06),
+        Field::Rudder => Some(0x07),
+        Field::Wheel => Some(0x08),
+        Field::Slider => Some(0x09),
+        Field::Dial => Some(0x0a),
+        Field::Aileron => Some(0x28),
+        _ => None,
+    }
+}
+
+/// Key code of button `index`, counted from 0.
+fn button_code(index: u32) -> u16 {
+    if index < BTN_JOYSTICK_COUNT {
+        BTN_JOYSTICK + index as u16
+    } else {
+        BTN_TRIGGER_HAPPY + (index - BTN_JOYSTICK_COUNT) as u16
+    }
+}
+
+/// Hat position as -1, 0 or 1 along x toward east and y toward south.
+fn hat_axes(hat: u32) -> (i32, i32) {
+    if hat == HAT_CENTERED {
+        return (0, 0);
+    }
+
+    let angle = (hat as f64 / 100.0).to_radians();
+
+    (angle.sin().round() as i32, -angle.cos().round() as i32)
+}
+
+/// Virtual joystick created through uinput, removed when dropped.
+///
+/// Axes have the vJoy default range, so the mappings need no change from Windows.
+pub struct UinputJoystick {
+    file: File,
+    name: String,
+    axes: Vec<(Field, u16)>,
+    buttons: u32,
+    hat: bool,
+    last: Option<JoystickPosition>,
+}
+
+impl UinputJoystick {
+    /// Joystick `id` with `fields`, `buttons` buttons and a hat if `hat`.
+    pub fn create(id: u32, fields: &[Field], buttons: u32, hat: bool) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(UINPUT_PATH)?;
+
+        let fd = file.as_raw_fd();
+
+        let mut axes = Vec::new();
+
+        for &field in fields {
+            match abs_code(field) {
+                Some(code) => axes.push((field, code)),
+                None => warn!("uinput has no axis for {}, it is not fed", field.name()),
+            }
+        }
+
+        let buttons = buttons.min(BTN_JOYSTICK_COUNT + BTN_TRIGGER_HAPPY_COUNT);
+        let range = AxisRange::default();
+
+        let mut abs_codes: Vec<(u16, AxisRange)> =
+            axes.iter().map(|&(_, code)| (code, range)).collect();
+
+        if hat {
+            let hat_range = AxisRange { min: -1, max: 1 };
+            abs_codes.push((ABS_HAT0X, hat_range));
+            abs_codes.push((ABS_HAT0Y, hat_range));
+        }
+
+        unsafe {
+            ioctl(fd, UI_SET_EVBIT, EV_KEY as libc::c_ulong)?;
+            ioctl(fd, UI_SET_EVBIT, EV_ABS as libc::c_ulong)?;
+
+            //Without buttons, games don't take it for a joystick
+            for index in 0..buttons.max(1) {
+                ioctl(fd, UI_SET_KEYBIT, button_code(index) as libc::c_ulong)?;
+            }
+
+            for &(code, range) in &abs_codes {
+                ioctl(fd, UI_SET_ABSBIT, code as libc::c_ulong)?;
+
+                let mut setup: libc::uinput_abs_setup = mem::zeroed();
+                setup.code = code;
+                setup.absinfo.minimum = range.min;
+                setup.absinfo.maximum = range.max;
+                setup.absinfo.value = range.center();
+
+                ioctl(fd, UI_ABS_SETUP, &setup as *const _ as libc::c_ulong)?;
+            }
+
+            let name = format!("vjoy-feeder {}", id);
+
+            let mut setup: libc::uinput_setup = mem::zeroed();
+            setup.id.bustype = BUS_VIRTUAL;
+            setup.id.version = 1;
+
+            for (target, byte) in setup.name.iter_mut().zip(name.bytes().take(79)) {
+                *target = byte as libc::c_char;
+            }
+
+            ioctl(fd, UI_DEV_SETUP, &setup as *const _ as libc::c_ulong)?;
+            ioctl(fd, UI_DEV_CREATE, 0)?;
+
+            info!("uinput joystick {} created", name);
+            status::emit("uinput_created", json!({ "id": id, "name": name }));
+
+            Ok(Self {
+                file,
+                name,
+                axes,
+                buttons,
+                hat,
+                last: None,
+            })
+        }
+    }
+
+    fn push(events: &mut Vec<libc::input_event>, kind: u16, code: u16, value: i32) {
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        event.type_ = kind;
+        event.code = code;
+        event.value = value;
+
+        events.push(event);
+    }
+}
+
+impl VirtualJoystick for UinputJoystick {
+    fn update(&mut self, position: &JoystickPosition) -> bool {
+        let mut events = Vec::new();
+
+        for &(field, code) in &self.axes {
+            let value = axis::read_field(position, field);
+
+            if self
+                .last
+                .is_none_or(|last| axis::read_field(&last, field) != value)
+            {
+                Self::push(&mut events, EV_ABS, code, value);
+            }
+        }
+
+        let bits = buttons::read_buttons(position);
+        let changed = match &self.last {
+            Some(last) => bits ^ buttons::read_buttons(last),
+            None => u64::MAX,
+        };
+
+        for index in 0..self.buttons {
+            if changed & 1 << index != 0 {
+                let value = (bits >> index & 1) as i32;
+                Self::push(&mut events, EV_KEY, button_code(index), value);
+            }
+        }
+
+        if self.hat && self.last.is_none_or(|last| last.bHats != position.bHats) {
+            let (x, y) = hat_axes(position.bHats);
+            Self::push(&mut events, EV_ABS, ABS_HAT0X, x);
+            Self::push(&mut events, EV_ABS, ABS_HAT0Y, y);
+        }
+
+        self.last = Some(*position);
+
+        if events.is_empty() {
+            return true;
+        }
+
+        Self::push(&mut events, EV_SYN, SYN_REPORT, 0);
+
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                events.as_ptr() as *const u8,
+                events.len() * mem::size_of::<libc::input_event>(),
+            )
+        };
+
+        match self.file.write_all(bytes) {
+            Ok(()) => true,
+            Err(error) => {
+                trace!("uinput joystick {} update failed: {}", self.name, error);
+
+                //Resend everything next time
+                self.last = None;
+                false
+            }
+        }
+    }
+}
+
+/// Keyboard created through uinput for keys sent by buttons, removed when dropped.
+pub struct UinputKeyboard {
+    file: File,
+}
+
+impl UinputKeyboard {
+    /// Keyboard able to send `keys`.
+    pub fn create(keys: &[Key]) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(UINPUT_PATH)?;
+
+        let fd = file.as_raw_fd();
+
+        unsafe {
+            ioctl(fd, UI_SET_EVBIT, EV_KEY as libc::c_ulong)?;
+
+            for key in keys {
+                ioctl(fd, UI_SET_KEYBIT, key.code as libc::c_ulong)?;
+            }
+
+            let mut setup: libc::uinput_setup = mem::zeroed();
+            setup.id.bustype = BUS_VIRTUAL;
+            setup.id.version = 1;
+
+            for (target, byte) in setup.name.iter_mut().zip(KEYBOARD_NAME.bytes()) {
+                *target = byte as libc::c_char;
+            }
+
+            ioctl(fd, UI_DEV_SETUP, &setup as *const _ as libc::c_ulong)?;
+            ioctl(fd, UI_DEV_CREATE, 0)?;
+        }
+
+        info!("uinput keyboard {} created", KEYBOARD_NAME);
+
+        Ok(Self { file })
+    }
+
+    pub fn send(&mut self, key: Key, down: bool) -> io::Result<()> {
+        let mut events = Vec::new();
+
+        UinputJoystick::push(&mut events, EV_KEY, key.code, down as i32);
+        UinputJoystick::push(&mut events, EV_SYN, SYN_REPORT, 0);
+
+        let bytes = unsafe {
+            slice::from_raw_parts(
+                events.as_ptr() as *const u8,
+                events.len() * mem::size_of::<libc::input_event>(),
+            )
+        };
+
+        self.file.write_all(bytes)
+    }
+}
+
+impl Drop for UinputKeyboard {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY, 0);
+        }
+
+        info!("uinput keyboard {} removed", KEYBOARD_NAME);
+    }
+}
+
+impl Drop for UinputJoystick {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = ioctl(self.file.as_raw_fd(), UI_DEV_DESTROY, 0);
+        }
+
+        info!("uinput joystick {} removed", self.name);
+        status::emit("uinput_removed", json!({ "name": self.name }));
+    }
+}
+
+/// `argument` is an int or a pointer, both passed as a register-sized integer.
+unsafe fn ioctl(fd: libc::c_int, request: u64, argument: libc::c_ulong) -> io::Result<()> {
+    if libc::ioctl(fd, request as _, argument) < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
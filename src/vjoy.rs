@@ -0,0 +1,638 @@
+use rusty_vjoy::JoystickPosition;
+
+use serde_json::json;
+
+use crate::axis::{self, AxisRange, Field};
+use crate::buttons;
+use crate::error::FeederError;
+use crate::hat::{self, HAT_CENTERED};
+use crate::joystick::{PositionSink, VirtualJoystick};
+
+#[cfg(windows)]
+use log::{debug, info, trace, warn};
+#[cfg(windows)]
+use rusty_vjoy::{HidUsage, VJDStat};
+#[cfg(windows)]
+use std::thread;
+#[cfg(windows)]
+use std::time::Duration;
+
+#[cfg(windows)]
+use crate::axis::Axis;
+use crate::status;
+#[cfg(target_os = "linux")]
+use crate::uinput::UinputJoystick;
+#[cfg(windows)]
+use crate::vjoy_dll;
+
+//vJoy supports up to 16 virtual devices, numbered from 1
+pub const VJOY_MIN_ID: u32 = 1;
+pub const VJOY_MAX_ID: u32 = 16;
+
+//Delay between attempts to acquire a vJoy device, doubling up to the max
+#[cfg(windows)]
+const ACQUIRE_MIN_DELAY: Duration = Duration::from_millis(100);
+#[cfg(windows)]
+const ACQUIRE_MAX_DELAY: Duration = Duration::from_secs(2);
+
+//Axes reported by --list-vjoy, the six device axes then the extended ones
+#[cfg(windows)]
+const LISTED_AXES: [(HidUsage, &str); 9] = [
+    (HidUsage::X, "X"),
+    (HidUsage::Y, "Y"),
+    (HidUsage::Z, "Z"),
+    (HidUsage::RX, "RX"),
+    (HidUsage::RY, "RY"),
+    (HidUsage::RZ, "RZ"),
+    (HidUsage::SL0, "Slider"),
+    (HidUsage::SL1, "Dial"),
+    (HidUsage::WHL, "Wheel"),
+];
+
+pub fn check_vjoy_id(id: u32) -> Result<u32, String> {
+    if (VJOY_MIN_ID..=VJOY_MAX_ID).contains(&id) {
+        Ok(id)
+    } else {
+        Err(format!(
+            "vJoy device ID must be between {} and {}, got {}",
+            VJOY_MIN_ID, VJOY_MAX_ID, id
+        ))
+    }
+}
+
+/// Fail if the vJoy DLL can't be loaded, before any call into it would.
+#[cfg(windows)]
+pub fn check_vjoy_dll() -> Result<(), FeederError> {
+    if vjoy_dll::is_loaded() {
+        Ok(())
+    } else {
+        Err(FeederError::VJoyDllMissing(vjoy_dll::VJOY_DLL))
+    }
+}
+
+#[cfg(windows)]
+pub fn check_vjoy_enabled() -> Result<(), FeederError> {
+    let status = vjoy_dll::vjoy_enabled();
+
+    debug!("vJoy driver installed & enabled = {}", status);
+
+    if status {
+        debug!(
+            "Driver Info;\n    Vendor: {}\n    Product : {}\n    Version Number: {}\n",
+            vjoy_dll::get_vjoy_manufacturer_string(),
+            vjoy_dll::get_vjoy_product_string(),
+            vjoy_dll::get_vjoy_serial_number_string(),
+        );
+        Ok(())
+    } else {
+        Err(FeederError::VJoyDisabled)
+    }
+}
+
+/// DLL and driver versions, `None` while the driver is disabled.
+#[cfg(windows)]
+pub fn versions() -> Option<(u16, u16)> {
+    if check_vjoy_enabled().is_err() {
+        return None;
+    }
+
+    let (_, dll_ver, driver_ver) = vjoy_dll::driver_match();
+
+    Some((dll_ver, driver_ver))
+}
+
+#[cfg(windows)]
+pub fn check_vjoy_versions() -> Result<(), FeederError> {
+    let (matching, dll_ver, driver_ver) = vjoy_dll::driver_match();
+
+    debug!("vJoy Driver match DLL version = {}", matching);
+
+    if matching {
+        Ok(())
+    } else {
+        Err(FeederError::VersionMismatch {
+            dll: dll_ver,
+            driver: driver_ver,
+        })
+    }
+}
+
+/// Fail if a field in `required` is missing, only warn about other mismatches.
+#[cfg(windows)]
+pub fn check_vjoy_axis(id: u32, required: &[Field], buttons: u32) -> Result<(), FeederError> {
+    let exists = Axis::ALL.map(|axis| field_exists(id, Field::from(axis)).unwrap_or(false));
+
+    let vjoy_buttons = vjoy_dll::get_vjd_button_number(id);
+
+    debug!(
+        "vJoy device {} capabilities;\n    Numner of buttons: {},\n    Axis X: {},\n    Axis Y: {},\n    Axis Z: {},\n    Axis RX: {},\n    Axis RY: {},\n    Axis RZ: {}",
+        id, vjoy_buttons, exists[0], exists[1], exists[2], exists[3], exists[4], exists[5]
+    );
+
+    let mut missing = Vec::new();
+
+    for &field in required {
+        match field_exists(id, field) {
+            Some(true) => {}
+            Some(false) => missing.push(field),
+            None => debug!(
+                "vJoy device {} cannot be asked whether it has {}",
+                id,
+                field.name()
+            ),
+        }
+    }
+
+    for (axis, exists) in Axis::ALL.into_iter().zip(exists) {
+        if !exists && !required.contains(&Field::from(axis)) {
+            warn!("vJoy device {} has no axis {}", id, axis.name());
+        }
+    }
+
+    if vjoy_buttons < buttons as i32 {
+        warn!(
+            "vJoy device {} has {} buttons, device buttons above are ignored",
+            id, vjoy_buttons
+        );
+    }
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(FeederError::CapabilityMismatch { id, axes: missing })
+    }
+}
+
+/// Whether vJoy device `id` has `field`, `None` if vJoy cannot tell.
+#[cfg(windows)]
+fn field_exists(id: u32, field: Field) -> Option<bool> {
+    field
+        .usage()
+        .map(|usage| vjoy_dll::get_vjd_axis_exist(id, usage))
+}
+
+#[cfg(windows)]
+pub fn get_vjoy_axis_range(id: u32, field: Field) -> AxisRange {
+    let range = field.usage().and_then(|usage| {
+        let (has_min, min) = vjoy_dll::get_vjd_axis_min(id, usage);
+        let (has_max, max) = vjoy_dll::get_vjd_axis_max(id, usage);
+
+        (has_min && has_max && min < max).then_some(AxisRange { min, max })
+    });
+
+    range.unwrap_or_else(|| {
+        let range = AxisRange::default();
+
+        warn!(
+            "vJoy device {} axis {} range unavailable, assuming {}..={}",
+            id,
+            field.name(),
+            range.min,
+            range.max
+        );
+
+        range
+    })
+}
+
+/// uinput axes are created with the vJoy default range.
+#[cfg(not(windows))]
+pub fn get_vjoy_axis_range(_id: u32, _field: Field) -> AxisRange {
+    AxisRange::default()
+}
+
+#[cfg(windows)]
+pub fn check_vjoy_status(id: u32) -> Result<(), FeederError> {
+    match vjoy_dll::get_vjd_status(id) {
+        VJDStat::VjdStatOwned => {
+            info!("vJoy device {} is already owned by this feeder", id);
+            Ok(())
+        }
+        VJDStat::VjdStatFree => {
+            debug!("vJoy device {} is free", id);
+            Ok(())
+        }
+        VJDStat::VjdStatBusy => Err(FeederError::VJoyBusy(id)),
+        VJDStat::VjdStatMissing => Err(FeederError::VJoyMissing(id)),
+        VJDStat::VjdStatUnknown => Err(FeederError::VJoyUnknown(id)),
+    }
+}
+
+/// One startup check and why it failed, if it did.
+#[derive(Debug)]
+pub struct Check {
+    pub name: String,
+    pub outcome: Result<(), FeederError>,
+}
+
+/// Outcome of every startup check, in the order they ran.
+#[derive(Debug, Default)]
+pub struct SelfTest {
+    pub checks: Vec<Check>,
+}
+
+impl SelfTest {
+    /// Record a check, returning whether it passed.
+    pub fn check(&mut self, name: impl Into<String>, outcome: Result<(), FeederError>) -> bool {
+        let passed = outcome.is_ok();
+
+        self.checks.push(Check {
+            name: name.into(),
+            outcome,
+        });
+
+        passed
+    }
+
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.outcome.is_ok())
+    }
+
+    /// Print a line per check, with the reason of each failure.
+    pub fn print(&self) {
+        println!("Self-test:");
+
+        for check in &self.checks {
+            match &check.outcome {
+                Ok(()) => println!("  \u{2713} {}", check.name),
+                Err(error) => println!("  \u{2717} {}: {}", check.name, error),
+            }
+        }
+    }
+
+    /// Emit a `check` event per check in JSON mode, with the reason of each failure.
+    pub fn emit(&self) {
+        for check in &self.checks {
+            status::emit(
+                "check",
+                json!({
+                    "name": check.name,
+                    "passed": check.outcome.is_ok(),
+                    "error": check.outcome.as_ref().err().map(ToString::to_string),
+                }),
+            );
+        }
+    }
+
+    /// The first failure, if any.
+    pub fn into_result(self) -> Result<(), FeederError> {
+        self.checks
+            .into_iter()
+            .map(|check| check.outcome)
+            .find(Result::is_err)
+            .unwrap_or(Ok(()))
+    }
+}
+
+/// Run every check on the driver and on each device with the axes and buttons it must have.
+///
+/// A busy device passes if acquiring it is retried, the other feeder may let go meanwhile.
+#[cfg(windows)]
+pub fn self_test(requirements: &[(u32, Vec<Field>, u32)], acquire_attempts: u32) -> SelfTest {
+    let mut test = SelfTest::default();
+
+    //Nothing else can be asked without the driver
+    if !test.check("vJoy driver enabled", check_vjoy_enabled()) {
+        return test;
+    }
+
+    test.check("vJoy driver and DLL versions match", check_vjoy_versions());
+
+    for (id, axes, buttons) in requirements {
+        let status = match check_vjoy_status(*id) {
+            Err(FeederError::VJoyBusy(id)) if acquire_attempts > 1 => {
+                warn!("vJoy device {} is owned by another feeder for now", id);
+                Ok(())
+            }
+            status => status,
+        };
+
+        if test.check(format!("vJoy device {} available", id), status) {
+            test.check(
+                format!("vJoy device {} has the mapped axes", id),
+                check_vjoy_axis(*id, axes, *buttons),
+            );
+        }
+    }
+
+    test
+}
+
+/// First free vJoy device with every field in `required` and at least `buttons` buttons,
+/// leaving out those in `taken`.
+#[cfg(windows)]
+pub fn find_vjoy_device(required: &[Field], buttons: u32, taken: &[u32]) -> Option<u32> {
+    (VJOY_MIN_ID..=VJOY_MAX_ID).find(|&id| {
+        !taken.contains(&id)
+            && matches!(vjoy_dll::get_vjd_status(id), VJDStat::VjdStatFree)
+            && required
+                .iter()
+                .all(|&field| field_exists(id, field) != Some(false))
+            && vjoy_dll::get_vjd_button_number(id) >= buttons as i32
+    })
+}
+
+/// Print the status and capabilities of every vJoy device, without acquiring any.
+#[cfg(windows)]
+pub fn list_vjoy_devices() {
+    for id in VJOY_MIN_ID..=VJOY_MAX_ID {
+        let status = match vjoy_dll::get_vjd_status(id) {
+            VJDStat::VjdStatOwned => "owned by this feeder",
+            VJDStat::VjdStatFree => "free",
+            VJDStat::VjdStatBusy => "owned by another feeder",
+            VJDStat::VjdStatMissing => {
+                println!("vJoy device {}: not installed or disabled", id);
+                continue;
+            }
+            VJDStat::VjdStatUnknown => "general error",
+        };
+
+        let axes: Vec<&str> = LISTED_AXES
+            .iter()
+            .filter(|(usage, _)| vjoy_dll::get_vjd_axis_exist(id, *usage))
+            .map(|(_, name)| *name)
+            .collect();
+
+        println!(
+            "vJoy device {}: {}, {} buttons, axes {}",
+            id,
+            status,
+            vjoy_dll::get_vjd_button_number(id),
+            if axes.is_empty() {
+                "none".to_owned()
+            } else {
+                axes.join(" ")
+            }
+        );
+    }
+}
+
+/// Acquire vJoy device `id`, trying up to `attempts` times with a growing delay.
+///
+/// A device busy with another feeder is only tried again once it is free, or on the last attempt.
+#[cfg(windows)]
+pub fn acquire_vjoy_device(id: u32, attempts: u32) -> Result<(), FeederError> {
+    let attempts = attempts.max(1);
+    let mut delay = ACQUIRE_MIN_DELAY;
+
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            thread::sleep(delay);
+            delay = (delay * 2).min(ACQUIRE_MAX_DELAY);
+        }
+
+        if attempt < attempts && vjoy_dll::get_vjd_status(id) == VJDStat::VjdStatBusy {
+            info!(
+                "vJoy device {} is owned by another feeder, attempt {} of {}",
+                id, attempt, attempts
+            );
+            continue;
+        }
+
+        if vjoy_dll::acquire_vjd(id) {
+            info!("vJoy device number {} acquired", id);
+            status::emit("vjoy_acquired", json!({ "id": id }));
+            return Ok(());
+        }
+
+        warn!(
+            "Could not acquire vJoy device {}, attempt {} of {}",
+            id, attempt, attempts
+        );
+    }
+
+    Err(FeederError::AcquireFailed { id, attempts })
+}
+
+/// Acquire all of `ids` or none, releasing the ones already acquired on failure.
+#[cfg(windows)]
+pub fn acquire_vjoy_devices(ids: &[u32], attempts: u32) -> Result<(), FeederError> {
+    for (index, &id) in ids.iter().enumerate() {
+        if let Err(error) = acquire_vjoy_device(id, attempts) {
+            for &acquired in &ids[..index] {
+                vjoy_dll::relinquish_vjd(acquired);
+                info!("vJoy device number {} relinquished", acquired);
+                status::emit("vjoy_relinquished", json!({ "id": acquired }));
+            }
+
+            return Err(error);
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether sending `new` would change anything compared to `old`.
+pub fn same_position(new: &JoystickPosition, old: &JoystickPosition) -> bool {
+    new.wThrottle == old.wThrottle
+        && new.wRudder == old.wRudder
+        && new.wAileron == old.wAileron
+        && new.wAxisX == old.wAxisX
+        && new.wAxisY == old.wAxisY
+        && new.wAxisZ == old.wAxisZ
+        && new.wAxisXRot == old.wAxisXRot
+        && new.wAxisYRot == old.wAxisYRot
+        && new.wAxisZRot == old.wAxisZRot
+        && new.wSlider == old.wSlider
+        && new.wDial == old.wDial
+        && new.wWheel == old.wWheel
+        && new.wAxisVX == old.wAxisVX
+        && new.wAxisVY == old.wAxisVY
+        && new.wAxisVZ == old.wAxisVZ
+        && new.wAxisVBRX == old.wAxisVBRX
+        && new.wAxisVBRY == old.wAxisVBRY
+        && new.wAxisVBRZ == old.wAxisVBRZ
+        && new.lButtons == old.lButtons
+        && new.bHats == old.bHats
+        && new.bHatsEx1 == old.bHatsEx1
+        && new.bHatsEx2 == old.bHatsEx2
+        && new.bHatsEx3 == old.bHatsEx3
+        && new.lButtonsEx1 == old.lButtonsEx1
+        && new.lButtonsEx2 == old.lButtonsEx2
+        && new.lButtonsEx3 == old.lButtonsEx3
+}
+
+/// Initial state of vJoy device `id`.
+pub fn new_position(id: u32) -> JoystickPosition {
+    JoystickPosition {
+        bDevice: id as u8,      /* BYTE */
+        wThrottle: 0,           /* LONG */
+        wRudder: 0,             /* LONG */
+        wAileron: 0,            /* LONG */
+        wAxisX: 0,              /* LONG */
+        wAxisY: 0,              /* LONG */
+        wAxisZ: 0,              /* LONG */
+        wAxisXRot: 0,           /* LONG */
+        wAxisYRot: 0,           /* LONG */
+        wAxisZRot: 0,           /* LONG */
+        wSlider: 0,             /* LONG */
+        wDial: 0,               /* LONG */
+        wWheel: 0,              /* LONG */
+        wAxisVX: 0,             /* LONG */
+        wAxisVY: 0,             /* LONG */
+        wAxisVZ: 0,             /* LONG */
+        wAxisVBRX: 0,           /* LONG */
+        wAxisVBRY: 0,           /* LONG */
+        wAxisVBRZ: 0,           /* LONG */
+        lButtons: 0,            /* LONG */
+        bHats: HAT_CENTERED,    /* DWORD */
+        bHatsEx1: HAT_CENTERED, /* DWORD */
+        bHatsEx2: HAT_CENTERED, /* DWORD */
+        bHatsEx3: HAT_CENTERED, /* DWORD */
+        lButtonsEx1: 0,         /* LONG */
+        lButtonsEx2: 0,         /* LONG */
+        lButtonsEx3: 0,         /* LONG */
+    }
+}
+
+/// Acquired vJoy device, relinquished when dropped.
+#[cfg(windows)]
+pub struct VJoyDevice {
+    id: u32,
+}
+
+#[cfg(windows)]
+impl VirtualJoystick for VJoyDevice {
+    fn update(&mut self, position: &JoystickPosition) -> bool {
+        let mut position = *position;
+        position.bDevice = self.id as u8;
+
+        let updated = vjoy_dll::update_vjd(self.id, &mut position);
+
+        if !updated {
+            trace!("vJoy device {} update failed", self.id);
+        }
+
+        updated
+    }
+}
+
+#[cfg(windows)]
+impl Drop for VJoyDevice {
+    fn drop(&mut self) {
+        vjoy_dll::relinquish_vjd(self.id);
+
+        info!("vJoy device number {} relinquished", self.id);
+        status::emit("vjoy_relinquished", json!({ "id": self.id }));
+    }
+}
+
+/// vJoy devices fed the same position.
+///
+/// On Linux each of `ids` is a uinput joystick instead, created when acquiring.
+pub struct VJoyOutput {
+    pub ids: Vec<u32>,
+    pub position: JoystickPosition,
+    /// Center of each field fed, written when centering
+    pub centers: Vec<(Field, i32)>,
+    last_sent: Option<JoystickPosition>,
+    joysticks: Vec<Box<dyn VirtualJoystick + Send>>,
+}
+
+impl VJoyOutput {
+    pub fn new(ids: Vec<u32>) -> Self {
+        Self {
+            position: new_position(ids[0]),
+            ids,
+            centers: Vec::new(),
+            last_sent: None,
+            joysticks: Vec::new(),
+        }
+    }
+
+    /// Take hold of each device, all of them or none, trying up to `attempts` times.
+    ///
+    /// `buttons` and `hat` are the controls fed, which uinput joysticks are created with.
+    #[cfg(windows)]
+    pub fn acquire(&mut self, attempts: u32, _buttons: u32, _hat: bool) -> Result<(), FeederError> {
+        acquire_vjoy_devices(&self.ids, attempts)?;
+
+        self.joysticks = self
+            .ids
+            .iter()
+            .map(|&id| Box::new(VJoyDevice { id }) as Box<dyn VirtualJoystick + Send>)
+            .collect();
+
+        Ok(())
+    }
+
+    /// Take hold of each device, all of them or none, trying up to `attempts` times.
+    ///
+    /// `buttons` and `hat` are the controls fed, which uinput joysticks are created with.
+    #[cfg(target_os = "linux")]
+    pub fn acquire(&mut self, _attempts: u32, buttons: u32, hat: bool) -> Result<(), FeederError> {
+        let fields: Vec<Field> = self.centers.iter().map(|&(field, _)| field).collect();
+
+        let joysticks: Result<Vec<_>, _> = self
+            .ids
+            .iter()
+            .map(|&id| UinputJoystick::create(id, &fields, buttons, hat))
+            .collect();
+
+        self.joysticks = joysticks
+            .map_err(FeederError::Uinput)?
+            .into_iter()
+            .map(|joystick| Box::new(joystick) as Box<dyn VirtualJoystick + Send>)
+            .collect();
+
+        Ok(())
+    }
+
+    /// Send to `sink` instead of acquiring the devices.
+    pub fn attach(&mut self, sink: &PositionSink) {
+        self.joysticks = self
+            .ids
+            .iter()
+            .map(|&id| Box::new(sink.joystick(id)) as Box<dyn VirtualJoystick + Send>)
+            .collect();
+    }
+
+    /// Send the position, `None` if skipped as unchanged, otherwise whether every device took it.
+    pub fn send(&mut self) -> Option<bool> {
+        if self
+            .last_sent
+            .is_some_and(|last| same_position(&self.position, &last))
+        {
+            return None;
+        }
+
+        let updated = self.update();
+
+        self.last_sent = updated.then_some(self.position);
+
+        Some(updated)
+    }
+
+    fn update(&mut self) -> bool {
+        let mut updated = true;
+
+        for joystick in self.joysticks.iter_mut() {
+            updated &= joystick.update(&self.position);
+        }
+
+        updated
+    }
+
+    /// Forget what was sent, so the next position goes through even if unchanged.
+    pub fn resend(&mut self) {
+        self.last_sent = None;
+    }
+
+    /// Center the axes and hat and release the buttons, so games don't see a frozen stick.
+    pub fn center(&mut self) {
+        for &(field, center) in &self.centers {
+            axis::write_field(&mut self.position, field, center);
+        }
+
+        buttons::write_buttons(&mut self.position, 0);
+        hat::write_hat(&mut self.position, HAT_CENTERED);
+
+        self.update();
+    }
+
+    /// Center, then let go of the devices.
+    pub fn relinquish(&mut self) {
+        self.center();
+        self.joysticks.clear();
+    }
+}
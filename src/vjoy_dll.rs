@@ -0,0 +1,218 @@
+//! vJoy interface functions, looked up in the DLL at runtime instead of linked.
+//!
+//! Linking the import library would have Windows refuse to start the feeder without vJoy,
+//! before it could say why. Without the DLL every call here fails as if the driver were off.
+
+use log::debug;
+use rusty_vjoy::{HidUsage, JoystickPosition, VJDStat};
+
+use std::ffi::c_void;
+use std::mem;
+use std::sync::OnceLock;
+
+use winapi::shared::minwindef::{BOOL, FARPROC};
+use winapi::um::libloaderapi::{GetProcAddress, LoadLibraryW};
+
+//Installed with the vJoy driver, next to it or on the PATH
+pub const VJOY_DLL: &str = "vJoyInterface.dll";
+
+struct Api {
+    vjoy_enabled: unsafe extern "C" fn() -> BOOL,
+    manufacturer_string: unsafe extern "C" fn() -> *const u16,
+    product_string: unsafe extern "C" fn() -> *const u16,
+    serial_number_string: unsafe extern "C" fn() -> *const u16,
+    driver_match: unsafe extern "C" fn(*mut u16, *mut u16) -> BOOL,
+    axis_exist: unsafe extern "C" fn(u32, u32) -> BOOL,
+    axis_min: unsafe extern "C" fn(u32, u32, *mut i32) -> BOOL,
+    axis_max: unsafe extern "C" fn(u32, u32, *mut i32) -> BOOL,
+    button_number: unsafe extern "C" fn(u32) -> i32,
+    status: unsafe extern "C" fn(u32) -> i32,
+    acquire: unsafe extern "C" fn(u32) -> BOOL,
+    relinquish: unsafe extern "C" fn(u32),
+    update: unsafe extern "C" fn(u32, *mut c_void) -> BOOL,
+}
+
+static API: OnceLock<Option<Api>> = OnceLock::new();
+
+fn api() -> Option<&'static Api> {
+    API.get_or_init(load).as_ref()
+}
+
+/// Load the DLL, kept for the rest of the process, and look up every function used.
+fn load() -> Option<Api> {
+    let name: Vec<u16> = VJOY_DLL.encode_utf16().chain(Some(0)).collect();
+    let module = unsafe { LoadLibraryW(name.as_ptr()) };
+
+    if module.is_null() {
+        debug!(
+            "Loading {} failed: {}",
+            VJOY_DLL,
+            std::io::Error::last_os_error()
+        );
+        return None;
+    }
+
+    macro_rules! function {
+        ($name:literal, $type:ty) => {{
+            let address = unsafe { GetProcAddress(module, concat!($name, "\0").as_ptr().cast()) };
+
+            if address.is_null() {
+                debug!("{} has no {}", VJOY_DLL, $name);
+                return None;
+            }
+
+            unsafe { mem::transmute::<FARPROC, $type>(address) }
+        }};
+    }
+
+    Some(Api {
+        vjoy_enabled: function!("vJoyEnabled", unsafe extern "C" fn() -> BOOL),
+        manufacturer_string: function!(
+            "GetvJoyManufacturerString",
+            unsafe extern "C" fn() -> *const u16
+        ),
+        product_string: function!("GetvJoyProductString", unsafe extern "C" fn() -> *const u16),
+        serial_number_string: function!(
+            "GetvJoySerialNumberString",
+            unsafe extern "C" fn() -> *const u16
+        ),
+        driver_match: function!(
+            "DriverMatch",
+            unsafe extern "C" fn(*mut u16, *mut u16) -> BOOL
+        ),
+        axis_exist: function!("GetVJDAxisExist", unsafe extern "C" fn(u32, u32) -> BOOL),
+        axis_min: function!(
+            "GetVJDAxisMin",
+            unsafe extern "C" fn(u32, u32, *mut i32) -> BOOL
+        ),
+        axis_max: function!(
+            "GetVJDAxisMax",
+            unsafe extern "C" fn(u32, u32, *mut i32) -> BOOL
+        ),
+        button_number: function!("GetVJDButtonNumber", unsafe extern "C" fn(u32) -> i32),
+        status: function!("GetVJDStatus", unsafe extern "C" fn(u32) -> i32),
+        acquire: function!("AcquireVJD", unsafe extern "C" fn(u32) -> BOOL),
+        relinquish: function!("RelinquishVJD", unsafe extern "C" fn(u32)),
+        update: function!("UpdateVJD", unsafe extern "C" fn(u32, *mut c_void) -> BOOL),
+    })
+}
+
+/// Whether the DLL and all its functions were found.
+pub fn is_loaded() -> bool {
+    api().is_some()
+}
+
+pub fn vjoy_enabled() -> bool {
+    api().is_some_and(|api| unsafe { (api.vjoy_enabled)() } != 0)
+}
+
+pub fn get_vjoy_manufacturer_string() -> String {
+    api().map_or_else(String::new, |api| {
+        wide_string(unsafe { (api.manufacturer_string)() })
+    })
+}
+
+pub fn get_vjoy_product_string() -> String {
+    api().map_or_else(String::new, |api| {
+        wide_string(unsafe { (api.product_string)() })
+    })
+}
+
+pub fn get_vjoy_serial_number_string() -> String {
+    api().map_or_else(String::new, |api| {
+        wide_string(unsafe { (api.serial_number_string)() })
+    })
+}
+
+/// Whether the DLL and driver versions match, with both versions.
+pub fn driver_match() -> (bool, u16, u16) {
+    let (mut dll, mut driver) = (0, 0);
+
+    let matching =
+        api().is_some_and(|api| unsafe { (api.driver_match)(&mut dll, &mut driver) } != 0);
+
+    (matching, dll, driver)
+}
+
+pub fn get_vjd_axis_exist(id: u32, axis: HidUsage) -> bool {
+    api().is_some_and(|api| unsafe { (api.axis_exist)(id, usage_id(axis)) } != 0)
+}
+
+pub fn get_vjd_axis_min(id: u32, axis: HidUsage) -> (bool, i32) {
+    let mut min = 0;
+    let found =
+        api().is_some_and(|api| unsafe { (api.axis_min)(id, usage_id(axis), &mut min) } != 0);
+
+    (found, min)
+}
+
+pub fn get_vjd_axis_max(id: u32, axis: HidUsage) -> (bool, i32) {
+    let mut max = 0;
+    let found =
+        api().is_some_and(|api| unsafe { (api.axis_max)(id, usage_id(axis), &mut max) } != 0);
+
+    (found, max)
+}
+
+pub fn get_vjd_button_number(id: u32) -> i32 {
+    api().map_or(0, |api| unsafe { (api.button_number)(id) })
+}
+
+pub fn get_vjd_status(id: u32) -> VJDStat {
+    match api().map(|api| unsafe { (api.status)(id) }) {
+        Some(0) => VJDStat::VjdStatOwned,
+        Some(1) => VJDStat::VjdStatFree,
+        Some(2) => VJDStat::VjdStatBusy,
+        Some(3) => VJDStat::VjdStatMissing,
+        _ => VJDStat::VjdStatUnknown,
+    }
+}
+
+pub fn acquire_vjd(id: u32) -> bool {
+    api().is_some_and(|api| unsafe { (api.acquire)(id) } != 0)
+}
+
+pub fn relinquish_vjd(id: u32) {
+    if let Some(api) = api() {
+        unsafe { (api.relinquish)(id) }
+    }
+}
+
+pub fn update_vjd(id: u32, position: &mut JoystickPosition) -> bool {
+    let position: *mut JoystickPosition = position;
+
+    api().is_some_and(|api| unsafe { (api.update)(id, position.cast()) } != 0)
+}
+
+/// HID usage ID of the axis, as the DLL takes it.
+fn usage_id(usage: HidUsage) -> u32 {
+    match usage {
+        HidUsage::X => 0x30,
+        HidUsage::Y => 0x31,
+        HidUsage::Z => 0x32,
+        HidUsage::RX => 0x33,
+        HidUsage::RY => 0x34,
+        HidUsage::RZ => 0x35,
+        HidUsage::SL0 => 0x36,
+        HidUsage::SL1 => 0x37,
+        HidUsage::WHL => 0x38,
+        HidUsage::POV => 0x39,
+    }
+}
+
+/// Copy of the NUL-terminated UTF-16 string the DLL returned, empty for none.
+fn wide_string(text: *const u16) -> String {
+    if text.is_null() {
+        return String::new();
+    }
+
+    let mut len = 0;
+
+    unsafe {
+        while *text.add(len) != 0 {
+            len += 1;
+        }
+
+        String::from_utf16_lossy(std::slice::from_raw_parts(text, len))
+    }
+}
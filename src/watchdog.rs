@@ -0,0 +1,142 @@
+use log::warn;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::source::StallSignal;
+
+//Checks per timeout, bounding how late a stall is noticed
+const CHECKS_PER_TIMEOUT: u32 = 4;
+
+struct Shared {
+    start: Instant,
+    timeout: Duration,
+    /// Milliseconds from `start` to the last report
+    last_report: AtomicU64,
+    tripped: AtomicBool,
+    stop: AtomicBool,
+}
+
+/// Thread tripping when the read loop got no report for `timeout`, e.g. from a wedged device.
+///
+/// On tripping it signals the source, waking a read blocked on the device, and the read loop
+/// then sees `take_tripped` and reconnects. Logging is done here so a stall shows up even
+/// on a source without a signal.
+pub struct Watchdog {
+    shared: Arc<Shared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watchdog {
+    pub fn start(timeout: Duration, signal: Option<StallSignal>) -> Self {
+        let shared = Arc::new(Shared {
+            start: Instant::now(),
+            timeout,
+            last_report: AtomicU64::new(0),
+            tripped: AtomicBool::new(false),
+            stop: AtomicBool::new(false),
+        });
+
+        let watched = shared.clone();
+
+        let thread = thread::spawn(move || {
+            let interval = (watched.timeout / CHECKS_PER_TIMEOUT).max(Duration::from_millis(1));
+
+            while !watched.stop.load(Ordering::Relaxed) {
+                thread::park_timeout(interval);
+
+                let last = Duration::from_millis(watched.last_report.load(Ordering::Relaxed));
+                let silent = watched.start.elapsed().saturating_sub(last);
+
+                if silent >= watched.timeout && !watched.tripped.swap(true, Ordering::Relaxed) {
+                    warn!(
+                        "Watchdog: no report for {} ms, restarting the device",
+                        silent.as_millis()
+                    );
+
+                    if let Some(signal) = &signal {
+                        signal();
+                    }
+                }
+            }
+        });
+
+        Self {
+            shared,
+            thread: Some(thread),
+        }
+    }
+
+    pub fn timeout(&self) -> Duration {
+        self.shared.timeout
+    }
+
+    /// A report came, or the device was just reopened.
+    pub fn feed(&self) {
+        let now = self.shared.start.elapsed().as_millis() as u64;
+
+        self.shared.last_report.store(now, Ordering::Relaxed);
+        self.shared.tripped.store(false, Ordering::Relaxed);
+    }
+
+    /// Whether the timeout expired since the last report, rearming the watchdog if so.
+    pub fn take_tripped(&self) -> bool {
+        if !self.shared.tripped.load(Ordering::Relaxed) {
+            return false;
+        }
+
+        self.feed();
+
+        true
+    }
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::mpsc;
+
+    #[test]
+    fn a_silent_source_is_signalled_once() {
+        let (signal, signalled) = mpsc::channel();
+        let watchdog = Watchdog::start(
+            Duration::from_millis(20),
+            Some(Box::new(move || {
+                let _ = signal.send(());
+            })),
+        );
+
+        assert!(signalled.recv_timeout(Duration::from_secs(1)).is_ok());
+
+        //Still tripped until the read loop takes it, without signalling again
+        thread::sleep(Duration::from_millis(100));
+        assert!(signalled.try_recv().is_err());
+        assert!(watchdog.take_tripped());
+    }
+
+    #[test]
+    fn reports_keep_it_from_tripping() {
+        let watchdog = Watchdog::start(Duration::from_millis(200), None);
+
+        for _ in 0..5 {
+            thread::sleep(Duration::from_millis(40));
+            watchdog.feed();
+        }
+
+        assert!(!watchdog.take_tripped());
+    }
+}
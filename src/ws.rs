@@ -0,0 +1,562 @@
+use log::{debug, info, warn};
+
+use serde_json::json;
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, Shutdown, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::axis::Axis;
+use crate::feeder::Snapshot;
+
+//Appended to the client key to prove the handshake, from RFC 6455
+const HANDSHAKE_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+//Longest a client may take to send its handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+//Longest a frame may wait for a client not reading, before it is dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+//Largest frame read from a client, which has nothing to send but control frames
+const MAX_CLIENT_PAYLOAD: u64 = 4096;
+
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+
+type Clients = Arc<Mutex<Vec<SyncSender<Arc<str>>>>>;
+
+/// Stream `snapshot` as JSON text frames to WebSocket clients on `ws://localhost:port`.
+///
+/// Sends a frame every `interval` until `running` is cleared. Each client has its own
+/// writer thread holding at most one frame, further frames are dropped while it lags.
+pub fn serve(
+    port: u16,
+    interval: Duration,
+    snapshot: Arc<Mutex<Snapshot>>,
+    running: Arc<AtomicBool>,
+) -> io::Result<JoinHandle<()>> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    listener.set_nonblocking(true)?;
+
+    info!("Streaming the state on ws://localhost:{}", port);
+
+    let clients: Clients = Arc::default();
+
+    Ok(thread::spawn(move || {
+        let mut next_frame = Instant::now();
+
+        while running.load(Ordering::Relaxed) {
+            loop {
+                match listener.accept() {
+                    Ok((stream, address)) => {
+                        debug!("WebSocket client {} connected", address);
+
+                        let clients = clients.clone();
+                        thread::spawn(move || serve_client(stream, clients));
+                    }
+                    Err(error) if error.kind() == io::ErrorKind::WouldBlock => break,
+                    Err(error) => {
+                        warn!("WebSocket connection failed: {}", error);
+                        break;
+                    }
+                }
+            }
+
+            thread::sleep(next_frame.saturating_duration_since(Instant::now()));
+            next_frame += interval;
+
+            let mut clients = clients.lock().unwrap();
+
+            if clients.is_empty() {
+                continue;
+            }
+
+            let frame: Arc<str> = state(&snapshot.lock().unwrap()).to_string().into();
+
+            clients.retain(|client| match client.try_send(frame.clone()) {
+                Ok(()) | Err(TrySendError::Full(_)) => true,
+                Err(TrySendError::Disconnected(_)) => false,
+            });
+        }
+
+        //Ends the writer threads
+        clients.lock().unwrap().clear();
+    }))
+}
+
+/// JSON of the axes fed to vJoy, by lowercase axis name, and the numbers of the pressed buttons.
+pub fn state(snapshot: &Snapshot) -> serde_json::Value {
+    let axes: serde_json::Map<String, serde_json::Value> = Axis::ALL
+        .into_iter()
+        .map(|axis| {
+            (
+                axis.name().to_lowercase(),
+                json!(snapshot.axes[axis as usize]),
+            )
+        })
+        .collect();
+
+    let buttons: Vec<u32> = (0..snapshot.button_count.min(64))
+        .filter(|&button| snapshot.buttons & 1 << button != 0)
+        .map(|button| button + 1)
+        .collect();
+
+    json!({ "axes": axes, "buttons": buttons })
+}
+
+/// Write the frames streamed to one client, while another thread answers what it sends.
+///
+/// The client is dropped once either side fails or it closes the connection.
+fn serve_client(mut stream: TcpStream, clients: Clients) {
+    let mut reader = match handshake(&mut stream) {
+        Ok(reader) => reader,
+        Err(error) => {
+            debug!("WebSocket handshake failed: {}", error);
+            return;
+        }
+    };
+
+    let (sender, frames) = mpsc::sync_channel::<Arc<str>>(1);
+    clients.lock().unwrap().push(sender);
+
+    let stream = Arc::new(Mutex::new(stream));
+    let replies = stream.clone();
+
+    thread::spawn(move || {
+        if let Err(error) = answer_client(&mut reader, &replies) {
+            debug!("WebSocket client gone: {}", error);
+        }
+
+        //Fails the next frame written, which drops the client
+        let _ = reader.get_ref().shutdown(Shutdown::Both);
+    });
+
+    for frame in frames {
+        if let Err(error) = write_frame(&stream, OPCODE_TEXT, frame.as_bytes()) {
+            debug!("WebSocket client gone: {}", error);
+            break;
+        }
+    }
+
+    //Ends the reader thread
+    let _ = stream.lock().unwrap().shutdown(Shutdown::Both);
+}
+
+/// Answer the upgrade request of a client, returning the reader of the frames it sends next.
+fn handshake(stream: &mut TcpStream) -> io::Result<BufReader<TcpStream>> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut key = None;
+    let mut upgrade = false;
+    let mut version = None;
+
+    for line in reader.by_ref().lines() {
+        let line = line?;
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            let (name, value) = (name.trim(), value.trim());
+
+            if name.eq_ignore_ascii_case("sec-websocket-key") {
+                key = Some(value.to_string());
+            } else if name.eq_ignore_ascii_case("upgrade") {
+                upgrade = value.eq_ignore_ascii_case("websocket");
+            } else if name.eq_ignore_ascii_case("sec-websocket-version") {
+                version = Some(value.to_string());
+            }
+        }
+    }
+
+    let key = match key {
+        Some(key) if upgrade => key,
+        _ => {
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n")?;
+            return Err(invalid_data("not a WebSocket upgrade request"));
+        }
+    };
+
+    //The only version of RFC 6455, advertised back to a client asking for another
+    if version.as_deref() != Some("13") {
+        stream.write_all(b"HTTP/1.1 426 Upgrade Required\r\nSec-WebSocket-Version: 13\r\n\r\n")?;
+        return Err(invalid_data("unsupported WebSocket version"));
+    }
+
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(&key)
+    )?;
+
+    //Clients may stay silent for as long as they watch
+    stream.set_read_timeout(None)?;
+
+    Ok(reader)
+}
+
+/// Answer pings with pongs and a close with a close, until the client closes.
+///
+/// Anything else a client sends is ignored.
+fn answer_client(reader: &mut impl Read, stream: &Mutex<TcpStream>) -> io::Result<()> {
+    loop {
+        let (opcode, payload) = read_frame(reader)?;
+
+        match opcode {
+            OPCODE_PING => write_frame(stream, OPCODE_PONG, &payload)?,
+            OPCODE_CLOSE => {
+                //Echoes the status code, if any
+                let code = payload.get(..2).unwrap_or_default();
+                return write_frame(stream, OPCODE_CLOSE, code);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Opcode and unmasked payload of the next frame sent by a client.
+fn read_frame(reader: &mut impl Read) -> io::Result<(u8, Vec<u8>)> {
+    let mut header = [0u8; 2];
+    reader.read_exact(&mut header)?;
+
+    //A client must mask every frame
+    if header[1] & 0x80 == 0 {
+        return Err(invalid_data("unmasked client frame"));
+    }
+
+    let len = match header[1] & 0x7f {
+        126 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_be_bytes(len) as u64
+        }
+        127 => {
+            let mut len = [0u8; 8];
+            reader.read_exact(&mut len)?;
+            u64::from_be_bytes(len)
+        }
+        len => len as u64,
+    };
+
+    if len > MAX_CLIENT_PAYLOAD {
+        return Err(invalid_data("client frame too large"));
+    }
+
+    let mut mask = [0u8; 4];
+    reader.read_exact(&mut mask)?;
+
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4];
+    }
+
+    Ok((header[0] & 0x0f, payload))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Sec-WebSocket-Accept answering the Sec-WebSocket-Key of a client.
+fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, HANDSHAKE_GUID).as_bytes()))
+}
+
+fn write_frame(stream: &Mutex<TcpStream>, opcode: u8, payload: &[u8]) -> io::Result<()> {
+    stream.lock().unwrap().write_all(&frame(opcode, payload))
+}
+
+/// Unmasked, unfragmented frame of `opcode`.
+fn frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let len = payload.len();
+    let mut frame = Vec::with_capacity(len + 10);
+
+    frame.push(0x80 | opcode);
+
+    if len < 126 {
+        frame.push(len as u8);
+    } else if len <= u16::MAX as usize {
+        frame.push(126);
+        frame.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        frame.push(127);
+        frame.extend_from_slice(&(len as u64).to_be_bytes());
+    }
+
+    frame.extend_from_slice(payload);
+
+    frame
+}
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for chunk in message.chunks(64) {
+        let mut w = [0u32; 80];
+
+        for (i, word) in chunk.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (state, value) in h.iter_mut().zip([a, b, c, d, e]) {
+            *state = state.wrapping_add(value);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+
+    for (bytes, word) in digest.chunks_mut(4).zip(h) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut text = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let bits = (chunk[0] as u32) << 16
+            | (*chunk.get(1).unwrap_or(&0) as u32) << 8
+            | *chunk.get(2).unwrap_or(&0) as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                text.push(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_frame(text: &str) -> Vec<u8> {
+        frame(OPCODE_TEXT, text.as_bytes())
+    }
+
+    #[test]
+    fn the_accept_key_matches_rfc_6455() {
+        //Example handshake of RFC 6455 section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[test]
+    fn sha1_matches_the_fips_vectors() {
+        let hex = |digest: [u8; 20]| -> String {
+            digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+        };
+
+        assert_eq!(hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            hex(sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+    }
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn a_short_text_frame_has_its_length_in_the_header() {
+        //Unmasked "Hello" of RFC 6455 section 5.7
+        assert_eq!(
+            text_frame("Hello"),
+            [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]
+        );
+    }
+
+    #[test]
+    fn longer_text_frames_have_an_extended_length() {
+        let text = "a".repeat(126);
+        let frame = text_frame(&text);
+        assert_eq!(frame[..4], [0x81, 126, 0x00, 0x7e]);
+        assert_eq!(frame.len(), 4 + 126);
+
+        let text = "a".repeat(u16::MAX as usize);
+        assert_eq!(text_frame(&text)[..4], [0x81, 126, 0xff, 0xff]);
+
+        let text = "a".repeat(u16::MAX as usize + 1);
+        let frame = text_frame(&text);
+        assert_eq!(frame[..10], [0x81, 127, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(frame.len(), 10 + text.len());
+    }
+
+    #[test]
+    fn a_masked_client_frame_is_unmasked() {
+        //Masked "Hello" of RFC 6455 section 5.7
+        let masked = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+
+        let (opcode, payload) = read_frame(&mut &masked[..]).unwrap();
+        assert_eq!(opcode, OPCODE_TEXT);
+        assert_eq!(payload, b"Hello");
+    }
+
+    #[test]
+    fn unmasked_or_oversized_client_frames_are_rejected() {
+        let unmasked = [0x89, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f];
+        assert!(read_frame(&mut &unmasked[..]).is_err());
+
+        let oversized = [0x82, 0xff, 0, 0, 0, 0, 0, 1, 0, 0];
+        assert!(read_frame(&mut &oversized[..]).is_err());
+    }
+
+    #[test]
+    fn a_pong_frame_matches_rfc_6455() {
+        //Unmasked Pong of RFC 6455 section 5.7
+        assert_eq!(
+            frame(OPCODE_PONG, b"Hello"),
+            [0x8a, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f]
+        );
+    }
+
+    //Client frame of `opcode` masked with a zero key
+    fn client_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8, 0, 0, 0, 0];
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn connect(request: &str) -> TcpStream {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (stream, _) = listener.accept().unwrap();
+
+        thread::spawn(move || serve_client(stream, Clients::default()));
+
+        client.set_read_timeout(Some(WRITE_TIMEOUT)).unwrap();
+        client.write_all(request.as_bytes()).unwrap();
+        client
+    }
+
+    fn read_response(client: &mut TcpStream) -> String {
+        let mut response = Vec::new();
+
+        while !response.ends_with(b"\r\n\r\n") {
+            let mut byte = [0u8];
+            client.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn a_client_is_answered_a_pong_and_dropped_after_a_close() {
+        let mut client = connect(
+            "GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        );
+
+        assert!(read_response(&mut client).starts_with("HTTP/1.1 101"));
+
+        client
+            .write_all(&client_frame(OPCODE_PING, b"Hello"))
+            .unwrap();
+        let mut pong = [0u8; 7];
+        client.read_exact(&mut pong).unwrap();
+        assert_eq!(pong, frame(OPCODE_PONG, b"Hello")[..]);
+
+        client
+            .write_all(&client_frame(OPCODE_CLOSE, &1000u16.to_be_bytes()))
+            .unwrap();
+        let mut close = [0u8; 4];
+        client.read_exact(&mut close).unwrap();
+        assert_eq!(close, [0x88, 0x02, 0x03, 0xe8]);
+
+        //Then the connection ends
+        let mut rest = Vec::new();
+        assert_eq!(client.read_to_end(&mut rest).unwrap(), 0);
+    }
+
+    #[test]
+    fn a_request_without_the_upgrade_or_version_is_refused() {
+        let mut client = connect(
+            "GET / HTTP/1.1\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+             Sec-WebSocket-Version: 13\r\n\r\n",
+        );
+        assert!(read_response(&mut client).starts_with("HTTP/1.1 400"));
+
+        let mut client = connect(
+            "GET / HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 8\r\n\r\n",
+        );
+        let response = read_response(&mut client);
+        assert!(response.starts_with("HTTP/1.1 426"));
+        assert!(response.contains("Sec-WebSocket-Version: 13"));
+    }
+}